@@ -3,7 +3,7 @@ mod mesh;
 
 use bytemuck::{Pod, Zeroable};
 use glam::{Mat3, Vec3};
-use softphys::{gpu::GpuSimulation, DistanceC, Particle, TetrahedralVolumeC};
+use plastica::{gpu::GpuSimulation, Body, DistanceC, Particle, TetrahedralVolumeC, WorldParams};
 use std::{borrow::Cow, f32::consts, future::Future, mem, pin::Pin, task};
 use wgpu::util::DeviceExt;
 
@@ -19,17 +19,14 @@ fn vertex(pos: [f32; 3]) -> Vertex {
     }
 }
 
-fn create_system(
+fn create_body(
     vertices: &[Vec3],
     edge_idx: &[usize],
     tet_idx: &[[usize; 4]],
-) -> (Vec<Particle>, Vec<DistanceC>, Vec<TetrahedralVolumeC>) {
-    let mut particles: Vec<_> = vertices
-        .into_iter()
-        .map(|p| Particle::new(*p, 0.0000001))
-        .collect();
+) -> Result<Body, plastica::Error> {
+    let particles: Vec<_> = vertices.iter().map(|p| Particle::new(*p, 0.)).collect();
 
-    let edge_constraints: Vec<_> = edge_idx
+    let distance_constraints = edge_idx
         .chunks(2)
         .map(|e| {
             DistanceC::new(
@@ -38,30 +35,21 @@ fn create_system(
                 100.0,
             )
         })
-        .collect();
+        .collect::<Result<_, _>>()?;
 
     let tet_constraints = tet_idx
         .iter()
-        .map(|e| {
-            let (p1, p2, p3, p4) = (
-                particles[e[0]].position,
-                particles[e[1]].position,
-                particles[e[2]].position,
-                particles[e[3]].position,
-            );
-
-            let vol = (p2 - p1).cross(p3 - p1).dot(p4 - p1) / 6.;
-
-            let inv_mass = if vol > 0.0 { 1.0 / (vol / 4.0) } else { 0.0 };
-            for i in e {
-                particles[*i].inv_mass += inv_mass;
-            }
-
-            TetrahedralVolumeC::new(e.map(|i| i as u32), vol, 0.0)
-        })
-        .collect();
-
-    (particles, edge_constraints, tet_constraints)
+        .map(|e| TetrahedralVolumeC::from_particles(e.map(|i| i as u32), &particles, 0.0))
+        .collect::<Result<_, _>>()?;
+
+    let mut body = Body {
+        particles,
+        distance_constraints,
+        tet_constraints,
+        ..Default::default()
+    };
+    body.set_masses_from_density(1000.)?;
+    Ok(body)
 }
 
 fn create_vertices(particles: &[Particle]) -> Vec<Vertex> {
@@ -159,18 +147,17 @@ impl framework::Example for Example {
             .vertices
             .iter_mut()
             .for_each(|v| *v = Mat3::from_axis_angle(Vec3::Y, 1.5) * (*v) + Vec3::Z);
-        let (mut particles, edge_constraints, tet_constraints) =
-            create_system(&bunny.vertices, &bunny.tet_edge_ids, &bunny.tet_ids);
-        particles
-            .iter_mut()
-            .for_each(|p| p.ext_acc = Vec3::new(0., 0., -10.));
+        let body = create_body(&bunny.vertices, &bunny.tet_edge_ids, &bunny.tet_ids).unwrap();
+        let params = WorldParams {
+            gravity: Vec3::new(0., 0., -10.),
+            substeps: 100,
+            ..Default::default()
+        };
 
-        // let mut simulation = CpuSimulation::new(softphys::cpu::SolverType::GaussSeidel);
-        // simulation.add_particles(particles);
-        // simulation.add_distance_constraints(edge_constraints);
-        // simulation.add_volume_constraints(tet_constraints);
-        let simulation =
-            GpuSimulation::new(device, &particles, &edge_constraints, &tet_constraints);
+        // let mut simulation = CpuSimulation::new(plastica::cpu::SolverType::GaussSeidel, params).unwrap();
+        // simulation.add_body(body).unwrap();
+        let mut simulation = pollster::block_on(GpuSimulation::new(device, params)).unwrap();
+        simulation.add_body(device, queue, body).unwrap();
 
         // Create the vertex and index buffers
         let vertex_size = mem::size_of::<Vertex>();
@@ -384,11 +371,11 @@ impl framework::Example for Example {
         queue: &wgpu::Queue,
         spawner: &framework::Spawner,
     ) {
-        //        self.simulation.simulate(10, 1. / 60.);
+        //        self.simulation.simulate(1. / 60., false).unwrap();
 
         device.push_error_scope(wgpu::ErrorFilter::Validation);
 
-        let particles = self.simulation.download_particles(device, queue);
+        let particles = self.simulation.download_particles(device, queue).unwrap();
 
         let mut encoder =
             device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
@@ -402,7 +389,7 @@ impl framework::Example for Example {
 
         {
             self.simulation
-                .simulate(device, &mut encoder, 100, 1. / 60.);
+                .simulate(device, queue, &mut encoder, 1. / 60.);
         }
         {
             let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -0,0 +1,189 @@
+use std::{fmt, sync::Arc};
+
+use encase::ShaderType;
+use glam::{Vec3, Vec4};
+
+use crate::{non_negative, Error, Particle};
+
+/// Drag and lift of the air flowing past a triangle, from its velocity relative to the wind at
+/// the triangle's center, the force being applied in equal parts to the three particles
+#[repr(C)]
+#[derive(Clone, Copy, Debug, ShaderType)]
+pub struct AeroTriangle {
+    pub(crate) particles_idx: [u32; 3],
+    /// Drag coefficient, along the relative wind
+    pub(crate) drag: f32,
+    /// Lift coefficient, across the relative wind
+    pub(crate) lift: f32,
+}
+
+impl AeroTriangle {
+    pub fn new(particles_idx: [u32; 3], drag: f32, lift: f32) -> Result<Self, Error> {
+        Ok(Self {
+            particles_idx,
+            drag: non_negative("drag coefficient", drag)?,
+            lift: non_negative("lift coefficient", lift)?,
+        })
+    }
+
+    /// Force of the `wind` on the triangle at `time`, in air of `air_density`
+    pub fn force(&self, particles: &[Particle], wind: &Wind, air_density: f32, time: f32) -> Vec3 {
+        let [p0, p1, p2] = self.particles_idx.map(|i| &particles[i as usize]);
+        let center = (p0.position + p1.position + p2.position) / 3.;
+        let wind = wind.velocity(center, time);
+        self.force_in_wind(particles, wind, air_density)
+    }
+
+    pub(crate) fn force_in_wind(
+        &self,
+        particles: &[Particle],
+        wind: Vec3,
+        air_density: f32,
+    ) -> Vec3 {
+        let [p0, p1, p2] = self.particles_idx.map(|i| &particles[i as usize]);
+        let relative = wind - (p0.velocity + p1.velocity + p2.velocity) / 3.;
+        let normal = (p1.position - p0.position).cross(p2.position - p0.position);
+        let area = normal.length() / 2.;
+        let (Some(normal), Some(flow)) = (normal.try_normalize(), relative.try_normalize()) else {
+            return Vec3::ZERO;
+        };
+        // Facing downstream, so the lift points away from the side the air hits
+        let normal = if normal.dot(flow) < 0. {
+            -normal
+        } else {
+            normal
+        };
+        let cos = normal.dot(flow);
+        let pressure = 0.5 * air_density * relative.length_squared();
+        pressure * area * cos * (self.drag * flow + self.lift * (normal - cos * flow))
+    }
+
+    pub(crate) fn offset(mut self, offset: u32) -> Self {
+        self.particles_idx = self.particles_idx.map(|i| i + offset);
+        self
+    }
+}
+
+/// Velocity of the air acting on [`AeroTriangle`]s
+#[derive(Clone)]
+pub enum Wind {
+    Constant(Vec3),
+    Turbulent(Turbulence),
+    /// Velocity at a position and time. The GPU simulation evaluates it on the CPU once per
+    /// step, at the triangle centers of the particles last read back.
+    Field(Arc<dyn Fn(Vec3, f32) -> Vec3 + Send + Sync>),
+}
+
+impl Default for Wind {
+    fn default() -> Self {
+        Self::Constant(Vec3::ZERO)
+    }
+}
+
+impl fmt::Debug for Wind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Constant(velocity) => f.debug_tuple("Constant").field(velocity).finish(),
+            Self::Turbulent(turbulence) => f.debug_tuple("Turbulent").field(turbulence).finish(),
+            Self::Field(_) => f.write_str("Field(..)"),
+        }
+    }
+}
+
+impl Wind {
+    pub fn velocity(&self, position: Vec3, time: f32) -> Vec3 {
+        match self {
+            Self::Constant(velocity) => *velocity,
+            Self::Turbulent(turbulence) => turbulence.velocity(position, time),
+            Self::Field(field) => field(position, time),
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        if let Self::Turbulent(turbulence) = self {
+            turbulence.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// Gusts of value noise around a mean wind, carried along by it
+#[derive(Clone, Copy, Debug)]
+pub struct Turbulence {
+    /// Mean velocity
+    pub velocity: Vec3,
+    /// Largest deviation from the mean velocity along each axis
+    pub amplitude: f32,
+    /// Size of the gusts
+    pub scale: f32,
+    /// Rate at which the gusts change as they are carried along, in 1/s
+    pub frequency: f32,
+    pub seed: u32,
+}
+
+impl Default for Turbulence {
+    fn default() -> Self {
+        Self {
+            velocity: Vec3::ZERO,
+            amplitude: 1.,
+            scale: 1.,
+            frequency: 1.,
+            seed: 0,
+        }
+    }
+}
+
+impl Turbulence {
+    pub fn velocity(&self, position: Vec3, time: f32) -> Vec3 {
+        let p = (position - self.velocity * time) / self.scale;
+        let p = p.extend(time * self.frequency);
+        let seed = self.seed.wrapping_mul(3);
+        let gust = Vec3::new(
+            value_noise(p, seed),
+            value_noise(p, seed.wrapping_add(1)),
+            value_noise(p, seed.wrapping_add(2)),
+        );
+        self.velocity + self.amplitude * gust
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        non_negative("turbulence amplitude", self.amplitude)?;
+        non_negative("turbulence frequency", self.frequency)?;
+        if !(self.scale > 0. && self.scale.is_finite()) {
+            return Err(Error::InvalidParameter {
+                name: "turbulence scale",
+                value: self.scale,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Smooth noise in [-1, 1] interpolating random values at integer coordinates, mirrored by
+/// `aero.wgsl`
+fn value_noise(p: Vec4, seed: u32) -> f32 {
+    let cell = p.floor();
+    let f = p - cell;
+    let s = f * f * (3. - 2. * f);
+    let cell = cell.to_array().map(|c| c as i32);
+    (0..16)
+        .map(|corner| {
+            let mut h = seed;
+            let mut weight = 1.;
+            for axis in 0..4 {
+                let bit = (corner >> axis) & 1;
+                h = hash(h ^ cell[axis].wrapping_add(bit) as u32);
+                weight *= if bit == 1 { s[axis] } else { 1. - s[axis] };
+            }
+            weight * ((h >> 8) as f32 / 8388607.5 - 1.)
+        })
+        .sum()
+}
+
+fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^ (x >> 16)
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use glam::Vec3;
+
+use crate::{
+    mass, non_negative, validate_indices, AeroTriangle, Body, DistanceC, Error, Particle,
+    TriangleStrainC,
+};
+
+/// Material of a cloth built with [`Body::cloth`]
+#[derive(Clone, Copy, Debug)]
+pub struct ClothParams {
+    /// Mass per unit area
+    pub density: f32,
+    /// Direction of the warp threads in the rest mesh, the weft running across it in the plane
+    /// of each triangle
+    pub warp_direction: Vec3,
+    pub warp_compliance: f32,
+    pub weft_compliance: f32,
+    /// Compliance of the edges running diagonally to the threads
+    pub shear_compliance: f32,
+    pub bending_compliance: f32,
+    pub damping: f32,
+    /// Resists stretch and shear with a [`TriangleStrainC`] per triangle instead of a
+    /// constraint per edge, the warp, weft and shear compliances then being per unit area
+    pub strain_constraints: bool,
+    /// Drag and lift coefficients of an [`AeroTriangle`] per triangle, none being added when
+    /// both are zero
+    pub drag_coefficient: f32,
+    pub lift_coefficient: f32,
+}
+
+impl Default for ClothParams {
+    fn default() -> Self {
+        Self {
+            density: 0.2,
+            warp_direction: Vec3::X,
+            warp_compliance: 0.,
+            weft_compliance: 0.,
+            shear_compliance: 1e-6,
+            bending_compliance: 1e-3,
+            damping: 0.,
+            strain_constraints: false,
+            drag_coefficient: 0.,
+            lift_coefficient: 0.,
+        }
+    }
+}
+
+impl Body {
+    /// Cloth made of the triangle mesh of `vertices` and `triangles`, with masses from the
+    /// triangle areas, a stretch constraint per edge or a strain constraint per triangle, and a
+    /// bending constraint across each edge shared by two triangles, between the vertices
+    /// opposite to it
+    pub fn cloth(
+        vertices: &[Vec3],
+        triangles: &[[u32; 3]],
+        params: &ClothParams,
+    ) -> Result<Self, Error> {
+        validate_indices(triangles.iter().map(|t| &t[..]), vertices.len() as u32)?;
+        for (name, value) in [
+            ("density", params.density),
+            ("warp compliance", params.warp_compliance),
+            ("weft compliance", params.weft_compliance),
+            ("shear compliance", params.shear_compliance),
+            ("bending compliance", params.bending_compliance),
+            ("damping", params.damping),
+            ("drag coefficient", params.drag_coefficient),
+            ("lift coefficient", params.lift_coefficient),
+        ] {
+            non_negative(name, value)?;
+        }
+
+        let mut particles: Vec<_> = vertices.iter().map(|v| Particle::new(*v, 0.)).collect();
+        let masses = mass::triangle_masses(&particles, triangles, params.density, 1.)?;
+        mass::set_masses(&mut particles, &masses);
+
+        // Vertices opposite to each edge, and a triangle normal to tell warp from weft
+        let mut edges: HashMap<[u32; 2], (Vec<u32>, Vec3)> = HashMap::new();
+        for tri in triangles {
+            let [p1, p2, p3] = tri.map(|i| vertices[i as usize]);
+            let normal = (p2 - p1).cross(p3 - p1).normalize_or_zero();
+            for k in 0..3 {
+                let (i, j, opposite) = (tri[k], tri[(k + 1) % 3], tri[(k + 2) % 3]);
+                edges
+                    .entry([i.min(j), i.max(j)])
+                    .or_insert_with(|| (Vec::new(), normal))
+                    .0
+                    .push(opposite);
+            }
+        }
+        // Sorted for a deterministic constraint order
+        let mut edges: Vec<_> = edges.into_iter().collect();
+        edges.sort_unstable_by_key(|(edge, _)| *edge);
+
+        let distance = |[i, j]: [u32; 2], compliance| {
+            DistanceC::new(
+                [i, j],
+                vertices[i as usize].distance(vertices[j as usize]),
+                compliance,
+            )
+            .and_then(|c| c.with_damping(params.damping))
+        };
+
+        let mut distance_constraints = Vec::new();
+        for ([i, j], (opposite, normal)) in &edges {
+            for (k, a) in opposite.iter().enumerate() {
+                for b in &opposite[k + 1..] {
+                    distance_constraints.push(distance([*a, *b], params.bending_compliance)?);
+                }
+            }
+            if params.strain_constraints {
+                continue;
+            }
+
+            let edge = (vertices[*j as usize] - vertices[*i as usize]).normalize_or_zero();
+            let warp = (params.warp_direction - *normal * normal.dot(params.warp_direction))
+                .normalize_or_zero();
+            let weft = normal.cross(warp);
+            // Within 22.5 degrees of a thread direction
+            let compliance = if edge.dot(warp).abs() > 0.92 {
+                params.warp_compliance
+            } else if edge.dot(weft).abs() > 0.92 {
+                params.weft_compliance
+            } else {
+                params.shear_compliance
+            };
+            distance_constraints.push(distance([*i, *j], compliance)?);
+        }
+
+        let triangle_strain_constraints = if params.strain_constraints {
+            let compliance = Vec3::new(
+                params.warp_compliance,
+                params.weft_compliance,
+                params.shear_compliance,
+            );
+            triangles
+                .iter()
+                .map(|t| {
+                    TriangleStrainC::from_particles(
+                        *t,
+                        &particles,
+                        params.warp_direction,
+                        compliance,
+                    )
+                })
+                .collect::<Result<_, _>>()?
+        } else {
+            Vec::new()
+        };
+
+        let aero_triangles = if params.drag_coefficient > 0. || params.lift_coefficient > 0. {
+            triangles
+                .iter()
+                .map(|t| AeroTriangle::new(*t, params.drag_coefficient, params.lift_coefficient))
+                .collect::<Result<_, _>>()?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            particles,
+            distance_constraints,
+            triangle_strain_constraints,
+            aero_triangles,
+            ..Default::default()
+        })
+    }
+}
@@ -1,11 +1,50 @@
+use std::{
+    mem,
+    ops::{Range, RangeBounds},
+};
+
 use glam::Vec3;
 use rayon::prelude::*;
 
-use crate::{ConstraintDelta, DistanceC, Particle, TetrahedralVolumeC};
+use crate::{
+    fiber, mass,
+    morph::{self, RestMorph},
+    AeroTriangle, AttachmentC, Body, BodyHandle, ConstraintDelta, DistanceC, Error, FiberC, Fluid,
+    Granular, IndexError, Instability, JointC, Particle, ParticleHandle, QuatParticle, RigidBody,
+    RigidBodyHandle, Rod, ShapeMatchingC, StepReport, SurfaceVolumeC, TetherC, TetrahedralVolumeC,
+    TriangleStrainC, Wind, WorldParams,
+};
+
+use self::{
+    damping::RigidModeDamping, fluid::Fluids, granular::Grains, neighbours::NeighbourGrid,
+    oriented::Oriented, pd::Pd, vbd::Vbd,
+};
+
+mod cholesky;
+mod damping;
+mod fluid;
+mod granular;
+mod neighbours;
+mod oriented;
+mod pd;
+mod vbd;
 
 pub trait Constraint {
-    /// Returns delta_x for each particle associated to this constraint
-    fn solve(&self, particles: &[Particle], delta: f32) -> Vec<ConstraintDelta> {
+    /// Lagrange multipliers `solve` accumulates over the iterations of a substep
+    const LAMBDAS: usize = 1;
+
+    /// Returns delta_x for each particle associated to this constraint, adding to the
+    /// multipliers `lambda` accumulated since the start of the substep, so that the compliance
+    /// does not depend on the number of iterations
+    fn solve(
+        &self,
+        particles: &[Particle],
+        delta: f32,
+        lambda: &mut [f32],
+    ) -> Vec<ConstraintDelta> {
+        if !self.is_active(particles) {
+            return Vec::new();
+        }
         let gradients = self.gradients(particles);
         let particles_idx = self.particles_idx();
 
@@ -14,43 +53,71 @@ pub trait Constraint {
             .map(|i| particles[*i as usize].inv_mass)
             .collect();
 
-        let xpbd_stiff = self.compliance() / delta / delta;
+        let compliance = self.solve_compliance(particles);
+        let xpbd_stiff = compliance / delta / delta;
+        // gamma = compliance_tilde * damping_tilde / delta
+        let gamma = compliance * self.damping() / delta;
+
+        let damping_term = gamma
+            * gradients
+                .iter()
+                .zip(particles_idx.iter())
+                .map(|(g, i)| {
+                    let p = &particles[*i as usize];
+                    g.dot(p.position - p.prev_position)
+                })
+                .sum::<f32>();
 
-        let lambda = -self.value(particles)
-            / (gradients
+        let denominator = (1. + gamma)
+            * gradients
                 .iter()
                 .zip(inv_masses.iter())
                 .map(|(g, w)| w * g.length_squared())
                 .sum::<f32>()
-                + xpbd_stiff);
-        let deltas = gradients.iter().zip(inv_masses).map(|(g, im)| {
-            debug_assert!(lambda.is_finite());
-            debug_assert!(im.is_finite());
-            debug_assert!(g.is_finite());
-            debug_assert!(
-                (lambda * im * (*g)).is_finite(),
-                "l: {} im: {} g: {}",
-                lambda,
-                im,
-                g
-            );
-            lambda * im * (*g)
-        });
+            + xpbd_stiff;
+        // Degenerate configurations and constraints between infinite masses can't be solved
+        if denominator == 0. {
+            return Vec::new();
+        }
+        let delta_lambda =
+            -(self.value(particles) + xpbd_stiff * lambda[0] + damping_term) / denominator;
+        lambda[0] += delta_lambda;
+        let deltas = gradients
+            .iter()
+            .zip(inv_masses)
+            .map(|(g, im)| delta_lambda * im * (*g));
 
         deltas
             .zip(particles_idx.iter())
-            .map(|(delta, particle_idx)| {
-                debug_assert!(delta.is_finite());
-                ConstraintDelta {
-                    particle_idx: *particle_idx,
-                    delta,
-                }
+            .map(|(delta, particle_idx)| ConstraintDelta {
+                particle_idx: *particle_idx,
+                delta,
             })
             .collect()
     }
 
     fn compliance(&self) -> f32;
 
+    /// Inequality constraints are only solved while violated, and are not counted when
+    /// averaging the deltas of the Jacobi solver otherwise
+    fn is_active(&self, _particles: &[Particle]) -> bool {
+        true
+    }
+
+    /// Disabled constraints must not be active either, and carry no weight in the Projective
+    /// Dynamics system
+    fn is_enabled(&self) -> bool {
+        true
+    }
+
+    /// Compliance used by the XPBD and VBD solvers, which may be lowered in configurations the
+    /// constraint has to recover from
+    fn solve_compliance(&self, _particles: &[Particle]) -> f32 {
+        self.compliance()
+    }
+
+    fn damping(&self) -> f32;
+
     fn particles_idx(&self) -> Vec<u32>;
 
     fn value(&self, particles: &[Particle]) -> f32;
@@ -65,29 +132,38 @@ impl Constraint for TetrahedralVolumeC {
     }
 
     #[inline]
-    fn particles_idx(&self) -> Vec<u32> {
-        self.particles_idx.to_vec()
+    fn is_active(&self, _particles: &[Particle]) -> bool {
+        self.is_enabled()
     }
 
     #[inline]
-    fn value(&self, particles: &[Particle]) -> f32 {
-        let (i1, i2, i3, i4) = (
-            self.particles_idx[0],
-            self.particles_idx[1],
-            self.particles_idx[2],
-            self.particles_idx[3],
-        );
+    fn is_enabled(&self) -> bool {
+        TetrahedralVolumeC::is_enabled(self)
+    }
 
-        let (p1, p2, p3, p4) = (
-            particles[i1 as usize].position,
-            particles[i2 as usize].position,
-            particles[i3 as usize].position,
-            particles[i4 as usize].position,
-        );
+    /// Inverted tetrahedra are solved as hard constraints until they recover
+    #[inline]
+    fn solve_compliance(&self, particles: &[Particle]) -> f32 {
+        if self.volume(particles) < 0. {
+            0.
+        } else {
+            self.compliance
+        }
+    }
 
-        let v = (p2 - p1).cross(p3 - p1).dot(p4 - p1) / 6.;
+    #[inline]
+    fn damping(&self) -> f32 {
+        self.damping
+    }
 
-        6. * (v - self.rest_volume)
+    #[inline]
+    fn particles_idx(&self) -> Vec<u32> {
+        self.particles_idx.to_vec()
+    }
+
+    #[inline]
+    fn value(&self, particles: &[Particle]) -> f32 {
+        6. * (self.volume(particles) - self.rest_volume)
     }
 
     #[inline]
@@ -121,18 +197,33 @@ impl Constraint for DistanceC {
         self.compliance
     }
 
+    #[inline]
+    fn damping(&self) -> f32 {
+        self.damping
+    }
+
     #[inline]
     fn particles_idx(&self) -> Vec<u32> {
         vec![self.particles_idx[0], self.particles_idx[1]]
     }
 
+    /// Slack strictly inside the range, so constraints with a single rest distance are always
+    /// solved
+    #[inline]
+    fn is_active(&self, particles: &[Particle]) -> bool {
+        let dist = self.distance(particles);
+        self.is_enabled() && !(self.min_distance < dist && dist < self.max_distance)
+    }
+
+    #[inline]
+    fn is_enabled(&self) -> bool {
+        DistanceC::is_enabled(self)
+    }
+
     #[inline]
     fn value(&self, particles: &[Particle]) -> f32 {
-        let (i1, i2) = (self.particles_idx[0], self.particles_idx[1]);
-        let dist = (particles[i2 as usize].position - particles[i1 as usize].position).length();
-        debug_assert!(dist.is_finite());
-        debug_assert!(dist >= 0.);
-        dist - self.rest_distance
+        let dist = self.distance(particles);
+        dist - dist.clamp(self.min_distance, self.max_distance)
     }
 
     #[inline]
@@ -147,59 +238,886 @@ impl Constraint for DistanceC {
     }
 }
 
+impl Constraint for ShapeMatchingC {
+    /// Moves every particle a fraction `stiffness` of the way to its goal position
+    fn solve(
+        &self,
+        particles: &[Particle],
+        _delta: f32,
+        _lambda: &mut [f32],
+    ) -> Vec<ConstraintDelta> {
+        self.goals(particles)
+            .into_iter()
+            .zip(&self.particles_idx)
+            .filter(|(_, i)| particles[**i as usize].inv_mass != 0.)
+            .map(|(goal, i)| ConstraintDelta {
+                delta: self.stiffness * (goal - particles[*i as usize].position),
+                particle_idx: *i,
+            })
+            .collect()
+    }
+
+    #[inline]
+    fn compliance(&self) -> f32 {
+        0.
+    }
+
+    #[inline]
+    fn damping(&self) -> f32 {
+        0.
+    }
+
+    #[inline]
+    fn particles_idx(&self) -> Vec<u32> {
+        self.particles_idx.clone()
+    }
+
+    /// Distance of the particles to their goal positions
+    fn value(&self, particles: &[Particle]) -> f32 {
+        self.goals(particles)
+            .iter()
+            .zip(&self.particles_idx)
+            .map(|(goal, i)| particles[*i as usize].position.distance_squared(*goal))
+            .sum::<f32>()
+            .sqrt()
+    }
+
+    fn gradients(&self, particles: &[Particle]) -> Vec<Vec3> {
+        let value = self.value(particles);
+        self.goals(particles)
+            .iter()
+            .zip(&self.particles_idx)
+            .map(|(goal, i)| (particles[*i as usize].position - *goal) / value)
+            .map(|g| if g.is_finite() { g } else { Vec3::ZERO })
+            .collect()
+    }
+}
+
+impl Constraint for TriangleStrainC {
+    /// One per strain
+    const LAMBDAS: usize = 3;
+
+    /// Solves the warp, weft and shear strains one after the other
+    fn solve(
+        &self,
+        particles: &[Particle],
+        delta: f32,
+        lambda: &mut [f32],
+    ) -> Vec<ConstraintDelta> {
+        let [p1, p2, p3] = self.particles_idx.map(|i| particles[i as usize]);
+        let positions = [p1.position, p2.position, p3.position];
+        let inv_masses = [p1.inv_mass, p2.inv_mass, p3.inv_mass];
+        let projected = self.project(positions, inv_masses, delta, lambda);
+        projected
+            .iter()
+            .zip(positions)
+            .zip(self.particles_idx)
+            .map(|((projected, position), particle_idx)| ConstraintDelta {
+                delta: *projected - position,
+                particle_idx,
+            })
+            .collect()
+    }
+
+    /// Compliance of the stiffest direction
+    #[inline]
+    fn compliance(&self) -> f32 {
+        self.compliance.min_element()
+    }
+
+    #[inline]
+    fn damping(&self) -> f32 {
+        0.
+    }
+
+    #[inline]
+    fn particles_idx(&self) -> Vec<u32> {
+        self.particles_idx.to_vec()
+    }
+
+    /// Frobenius norm of the strain tensor
+    fn value(&self, particles: &[Particle]) -> f32 {
+        let strains = self.strains(self.positions(particles));
+        (strains[0].0.powi(2) + strains[1].0.powi(2) + 2. * strains[2].0.powi(2)).sqrt()
+    }
+
+    fn gradients(&self, particles: &[Particle]) -> Vec<Vec3> {
+        let value = self.value(particles);
+        let strains = self.strains(self.positions(particles));
+        (0..3)
+            .map(|i| {
+                let g = (strains[0].0 * strains[0].1[i]
+                    + strains[1].0 * strains[1].1[i]
+                    + 2. * strains[2].0 * strains[2].1[i])
+                    / value;
+                if g.is_finite() {
+                    g
+                } else {
+                    Vec3::ZERO
+                }
+            })
+            .collect()
+    }
+}
+
+impl Constraint for SurfaceVolumeC {
+    #[inline]
+    fn compliance(&self) -> f32 {
+        self.compliance
+    }
+
+    #[inline]
+    fn damping(&self) -> f32 {
+        0.
+    }
+
+    #[inline]
+    fn particles_idx(&self) -> Vec<u32> {
+        self.particles_idx.clone()
+    }
+
+    #[inline]
+    fn value(&self, particles: &[Particle]) -> f32 {
+        self.volume(particles) - self.pressure * self.rest_volume
+    }
+
+    #[inline]
+    fn gradients(&self, particles: &[Particle]) -> Vec<Vec3> {
+        self.volume_gradients(particles)
+    }
+}
+
+impl Constraint for TetherC {
+    #[inline]
+    fn compliance(&self) -> f32 {
+        self.compliance
+    }
+
+    #[inline]
+    fn is_active(&self, particles: &[Particle]) -> bool {
+        self.distance(particles) > self.max_distance
+    }
+
+    #[inline]
+    fn damping(&self) -> f32 {
+        0.
+    }
+
+    #[inline]
+    fn particles_idx(&self) -> Vec<u32> {
+        self.particles_idx.to_vec()
+    }
+
+    #[inline]
+    fn value(&self, particles: &[Particle]) -> f32 {
+        (self.distance(particles) - self.max_distance).max(0.)
+    }
+
+    #[inline]
+    fn gradients(&self, particles: &[Particle]) -> Vec<Vec3> {
+        let [anchor, particle] = self.particles_idx.map(|i| particles[i as usize].position);
+        let dir = (particle - anchor).normalize_or_zero();
+        vec![-dir, dir]
+    }
+}
+
+impl Constraint for FiberC {
+    /// Compliance of the whole tetrahedron
+    #[inline]
+    fn compliance(&self) -> f32 {
+        self.compliance / self.rest_volume
+    }
+
+    #[inline]
+    fn damping(&self) -> f32 {
+        0.
+    }
+
+    #[inline]
+    fn particles_idx(&self) -> Vec<u32> {
+        self.particles_idx.to_vec()
+    }
+
+    #[inline]
+    fn value(&self, particles: &[Particle]) -> f32 {
+        self.fiber(particles).length() - (1. - self.activation)
+    }
+
+    #[inline]
+    fn gradients(&self, particles: &[Particle]) -> Vec<Vec3> {
+        let dir = self.fiber(particles).normalize_or_zero();
+        let w = self.weights;
+        vec![-(w.x + w.y + w.z) * dir, w.x * dir, w.y * dir, w.z * dir]
+    }
+}
+
 #[derive(Default)]
 pub struct CpuSimulation {
     particles: Vec<Particle>,
     distance_constraints: Vec<DistanceC>,
     volume_constraints: Vec<TetrahedralVolumeC>,
+    shape_matching_constraints: Vec<ShapeMatchingC>,
+    triangle_strain_constraints: Vec<TriangleStrainC>,
+    surface_volume_constraints: Vec<SurfaceVolumeC>,
+    tether_constraints: Vec<TetherC>,
+    fiber_constraints: Vec<FiberC>,
+    aero_triangles: Vec<AeroTriangle>,
+    /// Range of each body's particles
+    bodies: Vec<Range<u32>>,
+    oriented: Oriented,
+    fluids: Fluids,
+    grains: Grains,
     solver: SolverType,
+    params: WorldParams,
+    rigid_mode_damping: Vec<RigidModeDamping>,
+    vbd: Option<Vbd>,
+    pd: Option<Pd>,
+    /// Rest shape morphs in progress
+    morphs: Vec<(BodyHandle, RestMorph)>,
+    wind: Wind,
+    /// Simulated time, at which the wind is sampled
+    time: f64,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default)]
 pub enum SolverType {
+    #[default]
     GaussSeidel,
     Jacobi,
-}
-
-impl Default for SolverType {
-    fn default() -> Self {
-        Self::GaussSeidel
-    }
+    /// Per-vertex Newton steps on the implicit Euler energy, stiffness does not depend on the
+    /// number of substeps
+    VertexBlockDescent,
+    /// Local constraint projections and a prefactored global implicit step, robust for large
+    /// time steps. Constraint damping is not supported, since it would change the system every
+    /// step; use [`crate::WorldParams::damping`] or [`CpuSimulation::set_rigid_mode_damping`]
+    /// instead.
+    ProjectiveDynamics,
 }
 
 impl CpuSimulation {
-    pub fn new(solver: SolverType) -> Self {
-        Self {
+    pub fn new(solver: SolverType, params: WorldParams) -> Result<Self, Error> {
+        params.validate()?;
+        Ok(Self {
             solver,
+            params,
             ..Default::default()
-        }
+        })
+    }
+
+    pub fn world_params(&self) -> &WorldParams {
+        &self.params
+    }
+
+    pub fn set_world_params(&mut self, params: WorldParams) -> Result<(), Error> {
+        params.validate()?;
+        self.params = params;
+        Ok(())
     }
 
     pub fn particles(&self) -> &[Particle] {
         &self.particles
     }
 
-    pub fn add_particles(&mut self, particles: Vec<Particle>) {
-        self.particles.extend(particles)
+    /// Adds a body, whose constraints index into its own particles
+    pub fn add_body(&mut self, body: Body) -> Result<BodyHandle, Error> {
+        body.validate()?;
+        self.reset_solver_state();
+
+        let offset = self.particles.len() as u32;
+        self.push_constraints(body.offset_constraints(offset));
+        self.particles.extend(body.particles);
+
+        self.bodies.push(offset..self.particles.len() as u32);
+        Ok(BodyHandle(self.bodies.len() as u32 - 1))
+    }
+
+    /// Range of the body's particles in [`Self::particles`]
+    pub fn body_particles(&self, handle: BodyHandle) -> Result<Range<u32>, Error> {
+        Ok(self
+            .bodies
+            .get(handle.0 as usize)
+            .cloned()
+            .ok_or(IndexError::InvalidBody(handle))?)
+    }
+
+    /// Index of the particle in [`Self::particles`]
+    pub fn particle_index(&self, handle: ParticleHandle) -> Result<u32, Error> {
+        Ok(crate::particle_index(
+            self.body_particles(handle.body)?,
+            handle.index,
+        )?)
+    }
+
+    /// Mass of the particles of the body that can move
+    pub fn body_mass(&self, handle: BodyHandle) -> Result<f32, Error> {
+        Ok(mass::total_mass(self.body_slice(handle)?))
+    }
+
+    /// Center of mass of the particles of the body that can move
+    pub fn body_center_of_mass(&self, handle: BodyHandle) -> Result<Option<Vec3>, Error> {
+        Ok(mass::center_of_mass(self.body_slice(handle)?))
+    }
+
+    fn body_slice(&self, handle: BodyHandle) -> Result<&[Particle], Error> {
+        let range = self.body_particles(handle)?;
+        Ok(&self.particles[range.start as usize..range.end as usize])
+    }
+
+    /// Adds a rod, whose particles make up a body of their own
+    pub fn add_rod(&mut self, rod: Rod) -> Result<BodyHandle, Error> {
+        rod.validate()?;
+        let particles_offset = self.particles.len() as u32;
+        let orientations_offset = self.oriented.quat_particles.len() as u32;
+        let (stretch_shear, bend_twist) =
+            rod.offset_constraints(particles_offset, orientations_offset);
+
+        let handle = self.add_body(Body {
+            particles: rod.particles,
+            ..Default::default()
+        })?;
+        self.oriented
+            .stretch_shear_constraints
+            .extend(stretch_shear);
+        self.oriented.bend_twist_constraints.extend(bend_twist);
+        self.oriented.quat_particles.extend(rod.orientations);
+        self.oriented.rods.push((
+            handle,
+            orientations_offset..self.oriented.quat_particles.len() as u32,
+        ));
+        Ok(handle)
+    }
+
+    /// Adds a fluid, whose particles make up a body of their own
+    pub fn add_fluid(&mut self, fluid: Fluid) -> Result<BodyHandle, Error> {
+        fluid.validate()?;
+        let handle = self.add_body(Body {
+            particles: fluid.particles,
+            ..Default::default()
+        })?;
+        self.fluids
+            .phases
+            .push((self.body_particles(handle)?, fluid.params));
+        Ok(handle)
+    }
+
+    /// Adds a granular material, whose grains make up a body of their own
+    pub fn add_granular(&mut self, granular: Granular) -> Result<BodyHandle, Error> {
+        granular.validate()?;
+        let handle = self.add_body(Body {
+            particles: granular.particles,
+            ..Default::default()
+        })?;
+        self.grains
+            .phases
+            .push((self.body_particles(handle)?, granular.params));
+        Ok(handle)
+    }
+
+    pub fn quat_particles(&self) -> &[QuatParticle] {
+        &self.oriented.quat_particles
+    }
+
+    /// Range of the rod's orientations in [`Self::quat_particles`]
+    pub fn rod_orientations(&self, handle: BodyHandle) -> Result<Range<u32>, Error> {
+        Ok(self
+            .oriented
+            .rods
+            .iter()
+            .find(|(h, _)| *h == handle)
+            .map(|(_, range)| range.clone())
+            .ok_or(IndexError::InvalidBody(handle))?)
     }
 
-    pub fn add_distance_constraints(&mut self, constraints: Vec<DistanceC>) {
-        self.distance_constraints.extend(constraints)
+    pub fn add_rigid_body(&mut self, body: RigidBody) -> Result<RigidBodyHandle, Error> {
+        body.validate()?;
+        self.oriented.rigid_bodies.push(body);
+        Ok(RigidBodyHandle(self.oriented.rigid_bodies.len() as u32 - 1))
     }
 
-    pub fn add_volume_constraints(&mut self, constraints: Vec<TetrahedralVolumeC>) {
-        self.volume_constraints.extend(constraints)
+    pub fn rigid_bodies(&self) -> &[RigidBody] {
+        &self.oriented.rigid_bodies
     }
 
-    pub fn simulate(&mut self, substeps: u32, delta: f32, print_error: bool) {
+    pub fn rigid_body(&self, handle: RigidBodyHandle) -> Result<&RigidBody, Error> {
+        Ok(self
+            .oriented
+            .rigid_bodies
+            .get(handle.0 as usize)
+            .ok_or(IndexError::InvalidRigidBody(handle))?)
+    }
+
+    pub fn rigid_body_mut(&mut self, handle: RigidBodyHandle) -> Result<&mut RigidBody, Error> {
+        Ok(self
+            .oriented
+            .rigid_bodies
+            .get_mut(handle.0 as usize)
+            .ok_or(IndexError::InvalidRigidBody(handle))?)
+    }
+
+    /// Adds joints between rigid bodies or between rigid bodies and the world
+    pub fn add_joints(&mut self, joints: Vec<JointC>) -> Result<(), Error> {
+        for j in &joints {
+            j.bodies
+                .iter()
+                .flatten()
+                .try_for_each(|h| self.rigid_body(*h).map(|_| ()))?;
+        }
+        self.oriented.joints.extend(joints);
+        Ok(())
+    }
+
+    /// Current angle of a hinge joint, see [`JointC::angle`]
+    pub fn joint_angle(&self, joint: &JointC) -> f32 {
+        joint.angle(&self.oriented.rigid_bodies)
+    }
+
+    /// Attaches particles of soft bodies to rigid bodies
+    pub fn add_attachments(&mut self, attachments: Vec<AttachmentC>) -> Result<(), Error> {
+        let attachments = attachments
+            .into_iter()
+            .map(|a| {
+                self.rigid_body(a.body)?;
+                Ok((self.particle_index(a.particle)?, a))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        self.oriented.attachments.extend(attachments);
+        Ok(())
+    }
+
+    /// Damps the non-rigid part of the motion of the body by `coefficient`, which goes from 0
+    /// (no damping) to 1 (move rigidly)
+    pub fn set_rigid_mode_damping(
+        &mut self,
+        handle: BodyHandle,
+        coefficient: f32,
+    ) -> Result<(), Error> {
+        if !(0. ..=1.).contains(&coefficient) {
+            return Err(Error::InvalidParameter {
+                name: "rigid mode damping",
+                value: coefficient,
+            });
+        }
+        let particles = self.body_particles(handle)?;
+        self.rigid_mode_damping.retain(|d| d.particles != particles);
+        if coefficient > 0. {
+            self.rigid_mode_damping.push(RigidModeDamping {
+                particles,
+                coefficient,
+            });
+        }
+        Ok(())
+    }
+
+    /// Adds constraints between particles of an existing body, indexed locally to the body
+    pub fn add_distance_constraints(
+        &mut self,
+        handle: BodyHandle,
+        constraints: Vec<DistanceC>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            handle,
+            Body {
+                distance_constraints: constraints,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Adds constraints between particles of an existing body, indexed locally to the body
+    pub fn add_tet_constraints(
+        &mut self,
+        handle: BodyHandle,
+        constraints: Vec<TetrahedralVolumeC>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            handle,
+            Body {
+                tet_constraints: constraints,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Adds shape matching clusters of particles of an existing body, indexed locally to the body
+    pub fn add_shape_matching_constraints(
+        &mut self,
+        handle: BodyHandle,
+        constraints: Vec<ShapeMatchingC>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            handle,
+            Body {
+                shape_matching_constraints: constraints,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Adds triangle strain constraints between particles of an existing body, indexed locally
+    /// to the body
+    pub fn add_triangle_strain_constraints(
+        &mut self,
+        handle: BodyHandle,
+        constraints: Vec<TriangleStrainC>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            handle,
+            Body {
+                triangle_strain_constraints: constraints,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Adds enclosed volume constraints over surfaces of an existing body, indexed locally to
+    /// the body
+    pub fn add_surface_volume_constraints(
+        &mut self,
+        handle: BodyHandle,
+        constraints: Vec<SurfaceVolumeC>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            handle,
+            Body {
+                surface_volume_constraints: constraints,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Adds tethers between particles of an existing body, indexed locally to the body
+    pub fn add_tether_constraints(
+        &mut self,
+        handle: BodyHandle,
+        constraints: Vec<TetherC>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            handle,
+            Body {
+                tether_constraints: constraints,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Adds fiber constraints to tetrahedra of an existing body, indexed locally to the body
+    pub fn add_fiber_constraints(
+        &mut self,
+        handle: BodyHandle,
+        constraints: Vec<FiberC>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            handle,
+            Body {
+                fiber_constraints: constraints,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Adds aerodynamic triangles to an existing body, indexed locally to the body
+    pub fn add_aero_triangles(
+        &mut self,
+        handle: BodyHandle,
+        triangles: Vec<AeroTriangle>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            handle,
+            Body {
+                aero_triangles: triangles,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn wind(&self) -> &Wind {
+        &self.wind
+    }
+
+    /// Sets the wind blowing on the aerodynamic triangles of every body
+    pub fn set_wind(&mut self, wind: Wind) -> Result<(), Error> {
+        wind.validate()?;
+        self.wind = wind;
+        Ok(())
+    }
+
+    /// Sets the activation of every fiber of the body, see [`FiberC::set_activation`]
+    pub fn set_fiber_activation(
+        &mut self,
+        handle: BodyHandle,
+        activation: f32,
+    ) -> Result<(), Error> {
+        let particles = self.body_particles(handle)?;
+        let activation = fiber::validate_activation(activation)?;
+        for c in &mut self.fiber_constraints {
+            if particles.contains(&c.particles_idx[0]) {
+                c.activation = activation;
+            }
+        }
+        Ok(())
+    }
+
+    /// Updates the distance constraints of the body in `range`, indexed locally to the body in
+    /// the order they were added. Nothing is changed if `update` fails for any of them.
+    pub fn update_distance_constraints(
+        &mut self,
+        handle: BodyHandle,
+        range: impl RangeBounds<u32>,
+        update: impl FnMut(&mut DistanceC) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let particles = self.body_particles(handle)?;
+        if update_body_constraints(&mut self.distance_constraints, particles, range, update)? {
+            self.pd = None;
+        }
+        Ok(())
+    }
+
+    /// Updates the tetrahedral volume constraints of the body in `range`, indexed locally to the
+    /// body in the order they were added. Nothing is changed if `update` fails for any of them.
+    pub fn update_tet_constraints(
+        &mut self,
+        handle: BodyHandle,
+        range: impl RangeBounds<u32>,
+        update: impl FnMut(&mut TetrahedralVolumeC) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let particles = self.body_particles(handle)?;
+        if update_body_constraints(&mut self.volume_constraints, particles, range, update)? {
+            self.pd = None;
+        }
+        Ok(())
+    }
+
+    /// Moves the rest shape of the body to `positions`, one per particle, see
+    /// [`Body::set_rest_shape`]
+    pub fn set_rest_shape(&mut self, handle: BodyHandle, positions: &[Vec3]) -> Result<(), Error> {
+        self.morph_rest_shape(handle, positions, 0.)
+    }
+
+    /// Blends the rest values of the constraints of the body from their current values to those
+    /// of `positions` over `duration` seconds of simulated time, see [`Body::set_rest_shape`].
+    /// Replaces any morph of the body in progress.
+    pub fn morph_rest_shape(
+        &mut self,
+        handle: BodyHandle,
+        positions: &[Vec3],
+        duration: f32,
+    ) -> Result<(), Error> {
+        let particles = self.body_particles(handle)?;
+        morph::validate_rest_positions(positions, particles.len())?;
+        let mut rest = vec![Vec3::ZERO; self.particles.len()];
+        rest[particles.start as usize..particles.end as usize].copy_from_slice(positions);
+
+        let mut constraints = self.body_constraints(&particles);
+        let to = constraints.rest_shape_constraints(&rest)?;
+        let mut morph = RestMorph::new(constraints.clone(), to, duration)?;
+        self.morphs.retain(|(h, _)| *h != handle);
+        if morph.advance(0., &mut constraints) {
+            self.finish_rest_morph(&constraints);
+        } else {
+            self.morphs.push((handle, morph));
+        }
+        self.set_body_constraints(&particles, constraints);
+        Ok(())
+    }
+
+    /// Advances the rest shape morphs by `delta`, dropping the finished ones
+    fn advance_rest_morphs(&mut self, delta: f32) {
+        for (handle, mut morph) in mem::take(&mut self.morphs) {
+            let particles = self.bodies[handle.0 as usize].clone();
+            let mut constraints = self.body_constraints(&particles);
+            let finished = morph.advance(delta, &mut constraints);
+            if finished {
+                self.finish_rest_morph(&constraints);
+            }
+            self.set_body_constraints(&particles, constraints);
+            if !finished {
+                self.morphs.push((handle, morph));
+            }
+        }
+    }
+
+    /// The Projective Dynamics system only follows large changes of the weights of tetrahedra
+    /// and fibers during a morph, so it is refactorized with the final ones
+    fn finish_rest_morph(&mut self, constraints: &Body) {
+        if !(constraints.tet_constraints.is_empty() && constraints.fiber_constraints.is_empty()) {
+            self.pd = None;
+        }
+    }
+
+    /// Puts back rest shape morphs saved before [`Self::advance_rest_morphs`], along with the
+    /// rest values they had blended
+    fn restore_rest_morphs(&mut self, morphs: Vec<(BodyHandle, RestMorph)>) {
+        for (handle, morph) in &morphs {
+            let particles = self.bodies[handle.0 as usize].clone();
+            let mut constraints = self.body_constraints(&particles);
+            morph.blend(&mut constraints);
+            self.set_body_constraints(&particles, constraints);
+        }
+        self.morphs = morphs;
+    }
+
+    /// Copies of the constraints of the body occupying `particles` that have rest values, with
+    /// global indices
+    fn body_constraints(&self, particles: &Range<u32>) -> Body {
+        Body {
+            distance_constraints: constraints_of_body(&self.distance_constraints, particles),
+            tet_constraints: constraints_of_body(&self.volume_constraints, particles),
+            shape_matching_constraints: constraints_of_body(
+                &self.shape_matching_constraints,
+                particles,
+            ),
+            triangle_strain_constraints: constraints_of_body(
+                &self.triangle_strain_constraints,
+                particles,
+            ),
+            surface_volume_constraints: constraints_of_body(
+                &self.surface_volume_constraints,
+                particles,
+            ),
+            fiber_constraints: constraints_of_body(&self.fiber_constraints, particles),
+            ..Default::default()
+        }
+    }
+
+    /// Overwrites the constraints of the body occupying `particles` with those returned by
+    /// [`Self::body_constraints`]
+    fn set_body_constraints(&mut self, particles: &Range<u32>, constraints: Body) {
+        set_constraints_of_body(
+            &mut self.distance_constraints,
+            particles,
+            constraints.distance_constraints,
+        );
+        set_constraints_of_body(
+            &mut self.volume_constraints,
+            particles,
+            constraints.tet_constraints,
+        );
+        set_constraints_of_body(
+            &mut self.shape_matching_constraints,
+            particles,
+            constraints.shape_matching_constraints,
+        );
+        set_constraints_of_body(
+            &mut self.triangle_strain_constraints,
+            particles,
+            constraints.triangle_strain_constraints,
+        );
+        set_constraints_of_body(
+            &mut self.surface_volume_constraints,
+            particles,
+            constraints.surface_volume_constraints,
+        );
+        set_constraints_of_body(
+            &mut self.fiber_constraints,
+            particles,
+            constraints.fiber_constraints,
+        );
+    }
+
+    /// Adds the constraints of `constraints`, indexed locally to the body
+    fn add_constraints(&mut self, handle: BodyHandle, constraints: Body) -> Result<(), Error> {
+        let particles = self.body_particles(handle)?;
+        constraints.validate_constraints(particles.len() as u32)?;
+        self.push_constraints(constraints.offset_constraints(particles.start));
+        Ok(())
+    }
+
+    /// Appends constraints with global indices
+    fn push_constraints(&mut self, constraints: Body) {
+        self.reset_solver_state();
+        self.distance_constraints
+            .extend(constraints.distance_constraints);
+        self.volume_constraints.extend(constraints.tet_constraints);
+        self.shape_matching_constraints
+            .extend(constraints.shape_matching_constraints);
+        self.triangle_strain_constraints
+            .extend(constraints.triangle_strain_constraints);
+        self.surface_volume_constraints
+            .extend(constraints.surface_volume_constraints);
+        self.tether_constraints
+            .extend(constraints.tether_constraints);
+        self.fiber_constraints.extend(constraints.fiber_constraints);
+        self.aero_triangles.extend(constraints.aero_triangles);
+    }
+
+    /// Drops cached solver data, which is rebuilt on the next step
+    fn reset_solver_state(&mut self) {
+        self.vbd = None;
+        self.pd = None;
+    }
+
+    /// Advances the simulation by `delta`. Steps that become unstable are rolled back and
+    /// retried with more substeps, see [`crate::StabilityParams`]. When every attempt fails the
+    /// particles, rest shape morphs and time are left as they were before the step.
+    pub fn simulate(&mut self, delta: f32, print_error: bool) -> Result<StepReport, Error> {
+        let stability = self.params.stability;
+        let snapshot = self.particles.clone();
+        let rigid_snapshot = self.oriented.rigid_bodies.clone();
+        let quat_snapshot = self.oriented.quat_particles.clone();
+        let morphs_snapshot = self.morphs.clone();
+        self.advance_rest_morphs(delta);
+        let mut report = StepReport {
+            substeps: self.params.substeps,
+            ..Default::default()
+        };
+
+        loop {
+            match self.step(delta, report.substeps, print_error)? {
+                Ok(()) => {
+                    report.stable = true;
+                    report.inverted_tets = self.inverted_tets();
+                    self.time += delta as f64;
+                    return Ok(report);
+                }
+                Err(instability) => {
+                    self.particles.copy_from_slice(&snapshot);
+                    self.oriented.rigid_bodies.copy_from_slice(&rigid_snapshot);
+                    self.oriented.quat_particles.copy_from_slice(&quat_snapshot);
+                    report.instabilities.push(instability);
+                    match report.substeps.checked_mul(2) {
+                        Some(substeps)
+                            if report.instabilities.len() <= stability.max_retries as usize =>
+                        {
+                            report.substeps = substeps;
+                        }
+                        _ => {
+                            self.restore_rest_morphs(morphs_snapshot);
+                            report.inverted_tets = self.inverted_tets();
+                            return Ok(report);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn inverted_tets(&self) -> u32 {
+        self.volume_constraints
+            .par_iter()
+            .filter(|c| c.is_enabled() && c.volume(&self.particles) < 0.)
+            .count() as u32
+    }
+
+    /// The outer error is returned when the step cannot be taken, the inner one when the step
+    /// became unstable
+    fn step(
+        &mut self,
+        delta: f32,
+        substeps: u32,
+        print_error: bool,
+    ) -> Result<Result<(), Instability>, Error> {
         fn add_constraints_jacobi<T: Constraint + Sync>(
             particles: &mut [Particle],
             constraints: &[T],
+            lambdas: &mut Vec<f32>,
             delta: f32,
+            w: f32,
         ) {
+            lambdas.resize(constraints.len() * T::LAMBDAS, 0.);
             let x_deltas: Vec<_> = constraints
                 .par_iter()
-                .flat_map(|c| c.solve(particles, delta))
+                .zip(lambdas.par_chunks_mut(T::LAMBDAS))
+                .flat_map(|(c, lambda)| c.solve(particles, delta, lambda))
                 .collect();
 
             particles.par_iter_mut().enumerate().for_each(|(idx, p)| {
@@ -213,9 +1131,8 @@ impl CpuSimulation {
                     }
                 });
 
-                const W: f32 = 1.5;
                 if num_constraints > 0 {
-                    p.position += W * total_delta / num_constraints as f32;
+                    p.position += w * total_delta / num_constraints as f32;
                 }
             })
         }
@@ -223,10 +1140,26 @@ impl CpuSimulation {
         fn add_constraints_gauss_seidel<T: Constraint + Sync>(
             particles: &mut [Particle],
             constraints: &[T],
+            lambdas: &mut Vec<f32>,
+            delta: f32,
+        ) {
+            add_constraints_gauss_seidel_if(particles, constraints, lambdas, delta, |_| true);
+        }
+
+        /// Solves only the constraints `filter` returns true for
+        fn add_constraints_gauss_seidel_if<T: Constraint + Sync>(
+            particles: &mut [Particle],
+            constraints: &[T],
+            lambdas: &mut Vec<f32>,
             delta: f32,
+            filter: impl Fn(&T) -> bool,
         ) {
-            for c in constraints {
-                for p_delta in c.solve(particles, delta) {
+            lambdas.resize(constraints.len() * T::LAMBDAS, 0.);
+            for (c, lambda) in constraints.iter().zip(lambdas.chunks_mut(T::LAMBDAS)) {
+                if !filter(c) {
+                    continue;
+                }
+                for p_delta in c.solve(particles, delta, lambda) {
                     particles[p_delta.particle_idx as usize].position += p_delta.delta;
                 }
             }
@@ -240,29 +1173,91 @@ impl CpuSimulation {
             particles,
             distance_constraints,
             volume_constraints,
+            shape_matching_constraints,
+            triangle_strain_constraints,
+            surface_volume_constraints,
+            tether_constraints,
+            fiber_constraints,
+            bodies: _,
+            oriented,
+            fluids,
+            grains,
             solver,
+            params,
+            rigid_mode_damping,
+            vbd,
+            pd,
+            morphs: _,
+            aero_triangles,
+            wind,
+            time,
         } = self;
 
+        if let (SolverType::VertexBlockDescent, None) = (&solver, &vbd) {
+            *vbd = Some(Vbd::new(
+                particles,
+                distance_constraints,
+                volume_constraints,
+            ));
+        }
+
         let sub_delta = delta / substeps as f32;
 
-        for _ in 0..substeps {
+        if let SolverType::ProjectiveDynamics = solver {
+            if pd.as_ref().is_none_or(|pd| {
+                pd.delta() != sub_delta
+                    || pd.weights_changed(
+                        distance_constraints,
+                        volume_constraints,
+                        fiber_constraints,
+                    )
+            }) {
+                *pd = Some(
+                    Pd::new(
+                        particles,
+                        distance_constraints,
+                        volume_constraints,
+                        fiber_constraints,
+                        sub_delta,
+                    )
+                    .map_err(|_| Error::NotPositiveDefinite)?,
+                );
+            }
+        }
+
+        let mut lambdas = Lambdas::default();
+        for i in 0..substeps {
+            lambdas.clear();
+            add_aero_forces(
+                particles,
+                aero_triangles,
+                wind,
+                params.air_density,
+                (*time + i as f64 * sub_delta as f64) as f32,
+                sub_delta,
+            );
             particles.iter_mut().for_each(|p| {
+                if p.inv_mass != 0. {
+                    p.velocity += params.gravity * sub_delta;
+                }
                 p.velocity += p.ext_acc * sub_delta;
                 p.prev_position = p.position;
                 p.position += p.velocity * sub_delta;
 
-                // TODO: Remove
-                if p.position.z < 0.0 {
-                    p.position = p.prev_position;
-                    p.position.z = 0.;
+                if let Some(ground) = params.ground {
+                    if ground.normal.dot(p.position) < ground.offset {
+                        let prev_height = ground.normal.dot(p.prev_position) - ground.offset;
+                        p.position = p.prev_position - prev_height * ground.normal;
+                    }
                 }
-                debug_assert!(
-                    p.position.is_finite(),
-                    "p: {}, v: {}",
-                    p.position,
-                    p.velocity
-                )
             });
+            oriented.integrate(params.gravity, sub_delta);
+            let radius = fluids.search_radius().max(grains.search_radius());
+            if radius > 0. {
+                let grid = NeighbourGrid::new(particles, radius);
+                fluids.find_neighbours(particles, &grid);
+                grains.find_contacts(particles, &grid);
+            }
 
             if print_error {
                 println!("Distance error: {}", error(particles, distance_constraints));
@@ -271,20 +1266,337 @@ impl CpuSimulation {
 
             match solver {
                 SolverType::GaussSeidel => {
-                    add_constraints_gauss_seidel(particles, distance_constraints, sub_delta);
-                    add_constraints_gauss_seidel(particles, volume_constraints, sub_delta);
+                    for _ in 0..params.iterations {
+                        add_constraints_gauss_seidel(
+                            particles,
+                            distance_constraints,
+                            &mut lambdas.distance,
+                            sub_delta,
+                        );
+                        add_constraints_gauss_seidel(
+                            particles,
+                            volume_constraints,
+                            &mut lambdas.volume,
+                            sub_delta,
+                        );
+                        add_constraints_gauss_seidel(
+                            particles,
+                            fiber_constraints,
+                            &mut lambdas.fiber,
+                            sub_delta,
+                        );
+                        add_constraints_gauss_seidel(
+                            particles,
+                            shape_matching_constraints,
+                            &mut lambdas.shape_matching,
+                            sub_delta,
+                        );
+                        add_constraints_gauss_seidel(
+                            particles,
+                            triangle_strain_constraints,
+                            &mut lambdas.strain,
+                            sub_delta,
+                        );
+                        add_constraints_gauss_seidel(
+                            particles,
+                            surface_volume_constraints,
+                            &mut lambdas.surface_volume,
+                            sub_delta,
+                        );
+                        add_constraints_gauss_seidel(
+                            particles,
+                            tether_constraints,
+                            &mut lambdas.tether,
+                            sub_delta,
+                        );
+                        oriented.solve(particles, sub_delta);
+                        fluids.solve(particles);
+                        grains.solve(particles);
+                    }
                 }
                 SolverType::Jacobi => {
-                    add_constraints_jacobi(particles, distance_constraints, sub_delta);
-                    add_constraints_jacobi(particles, volume_constraints, sub_delta);
+                    for _ in 0..params.iterations {
+                        let w = params.jacobi_weight;
+                        add_constraints_jacobi(
+                            particles,
+                            distance_constraints,
+                            &mut lambdas.distance,
+                            sub_delta,
+                            w,
+                        );
+                        add_constraints_jacobi(
+                            particles,
+                            volume_constraints,
+                            &mut lambdas.volume,
+                            sub_delta,
+                            w,
+                        );
+                        add_constraints_jacobi(
+                            particles,
+                            fiber_constraints,
+                            &mut lambdas.fiber,
+                            sub_delta,
+                            w,
+                        );
+                        add_constraints_jacobi(
+                            particles,
+                            shape_matching_constraints,
+                            &mut lambdas.shape_matching,
+                            sub_delta,
+                            w,
+                        );
+                        add_constraints_jacobi(
+                            particles,
+                            triangle_strain_constraints,
+                            &mut lambdas.strain,
+                            sub_delta,
+                            w,
+                        );
+                        add_constraints_jacobi(
+                            particles,
+                            surface_volume_constraints,
+                            &mut lambdas.surface_volume,
+                            sub_delta,
+                            w,
+                        );
+                        add_constraints_jacobi(
+                            particles,
+                            tether_constraints,
+                            &mut lambdas.tether,
+                            sub_delta,
+                            w,
+                        );
+                        oriented.solve(particles, sub_delta);
+                        fluids.solve(particles);
+                        grains.solve(particles);
+                    }
+                }
+                SolverType::ProjectiveDynamics => {
+                    if let Some(pd) = pd {
+                        pd.solve(
+                            particles,
+                            distance_constraints,
+                            volume_constraints,
+                            fiber_constraints,
+                            params.iterations,
+                        );
+                    }
+                    // Range constraints are left out of the system, see `pd::distance_weight`
+                    add_constraints_gauss_seidel_if(
+                        particles,
+                        distance_constraints,
+                        &mut lambdas.distance,
+                        sub_delta,
+                        DistanceC::is_range,
+                    );
+                    add_constraints_gauss_seidel(
+                        particles,
+                        shape_matching_constraints,
+                        &mut lambdas.shape_matching,
+                        sub_delta,
+                    );
+                    add_constraints_gauss_seidel(
+                        particles,
+                        triangle_strain_constraints,
+                        &mut lambdas.strain,
+                        sub_delta,
+                    );
+                    add_constraints_gauss_seidel(
+                        particles,
+                        surface_volume_constraints,
+                        &mut lambdas.surface_volume,
+                        sub_delta,
+                    );
+                    add_constraints_gauss_seidel(
+                        particles,
+                        tether_constraints,
+                        &mut lambdas.tether,
+                        sub_delta,
+                    );
+                    oriented.solve(particles, sub_delta);
+                    fluids.solve(particles);
+                    grains.solve(particles);
+                }
+                SolverType::VertexBlockDescent => {
+                    if let Some(vbd) = vbd {
+                        vbd.solve(
+                            particles,
+                            distance_constraints,
+                            volume_constraints,
+                            sub_delta,
+                            params.iterations,
+                        );
+                    }
+                    add_constraints_gauss_seidel(
+                        particles,
+                        fiber_constraints,
+                        &mut lambdas.fiber,
+                        sub_delta,
+                    );
+                    add_constraints_gauss_seidel(
+                        particles,
+                        shape_matching_constraints,
+                        &mut lambdas.shape_matching,
+                        sub_delta,
+                    );
+                    add_constraints_gauss_seidel(
+                        particles,
+                        triangle_strain_constraints,
+                        &mut lambdas.strain,
+                        sub_delta,
+                    );
+                    add_constraints_gauss_seidel(
+                        particles,
+                        surface_volume_constraints,
+                        &mut lambdas.surface_volume,
+                        sub_delta,
+                    );
+                    add_constraints_gauss_seidel(
+                        particles,
+                        tether_constraints,
+                        &mut lambdas.tether,
+                        sub_delta,
+                    );
+                    oriented.solve(particles, sub_delta);
+                    fluids.solve(particles);
+                    grains.solve(particles);
                 }
             }
 
+            let velocity_scale = (-params.damping * sub_delta).exp();
             particles.iter_mut().for_each(|p| {
-                debug_assert!(p.position.is_finite());
-                p.velocity = (p.position - p.prev_position) / sub_delta;
-                debug_assert!(p.velocity.is_finite());
+                p.velocity = velocity_scale * (p.position - p.prev_position) / sub_delta;
+            });
+            oriented.update_velocities(sub_delta, velocity_scale);
+            fluids.update_velocities(particles, sub_delta);
+
+            rigid_mode_damping.iter().for_each(|d| d.apply(particles));
+
+            if let Some(instability) = find_instability(particles, params.stability.max_speed) {
+                return Ok(Err(instability));
+            }
+        }
+
+        Ok(Ok(()))
+    }
+}
+
+/// Lagrange multipliers of the XPBD constraints, accumulated over the iterations of a substep
+#[derive(Default)]
+struct Lambdas {
+    distance: Vec<f32>,
+    volume: Vec<f32>,
+    fiber: Vec<f32>,
+    shape_matching: Vec<f32>,
+    strain: Vec<f32>,
+    surface_volume: Vec<f32>,
+    tether: Vec<f32>,
+}
+
+impl Lambdas {
+    /// Resets the multipliers for a new substep
+    fn clear(&mut self) {
+        for lambdas in [
+            &mut self.distance,
+            &mut self.volume,
+            &mut self.fiber,
+            &mut self.shape_matching,
+            &mut self.strain,
+            &mut self.surface_volume,
+            &mut self.tether,
+        ] {
+            lambdas.clear();
+        }
+    }
+}
+
+/// Returns the first particle that is not finite or moves faster than `max_speed`
+fn find_instability(particles: &[Particle], max_speed: f32) -> Option<Instability> {
+    particles.par_iter().enumerate().find_map_first(|(idx, p)| {
+        let particle_idx = idx as u32;
+        let speed = p.velocity.length();
+        if !p.position.is_finite() || !speed.is_finite() {
+            Some(Instability::NonFinite { particle_idx })
+        } else if speed > max_speed {
+            Some(Instability::ExcessiveSpeed {
+                particle_idx,
+                speed,
             })
+        } else {
+            None
+        }
+    })
+}
+
+/// Applies `update` to copies of the constraints in `range` of the body occupying `particles`,
+/// and writes them back once every update succeeded. Returns whether a compliance or enabled
+/// state changed, which the Projective Dynamics system has to be rebuilt for.
+fn update_body_constraints<T: Constraint + Copy>(
+    constraints: &mut [T],
+    particles: Range<u32>,
+    range: impl RangeBounds<u32>,
+    mut update: impl FnMut(&mut T) -> Result<(), Error>,
+) -> Result<bool, Error> {
+    let mut body_constraints: Vec<_> = constraints
+        .iter_mut()
+        .filter(|c| particles.contains(&c.particles_idx()[0]))
+        .collect();
+    let range = crate::constraint_range(range, body_constraints.len() as u32)?;
+    let mut updated: Vec<T> = body_constraints[range.clone()]
+        .iter()
+        .map(|c| **c)
+        .collect();
+    for c in &mut updated {
+        update(c)?;
+    }
+
+    let mut weights_changed = false;
+    for (c, updated) in body_constraints[range].iter_mut().zip(updated) {
+        weights_changed |=
+            c.compliance() != updated.compliance() || c.is_enabled() != updated.is_enabled();
+        **c = updated;
+    }
+    Ok(weights_changed)
+}
+
+/// Adds the velocity the aerodynamic forces on `triangles` give their particles over `delta`
+fn add_aero_forces(
+    particles: &mut [Particle],
+    triangles: &[AeroTriangle],
+    wind: &Wind,
+    air_density: f32,
+    time: f32,
+    delta: f32,
+) {
+    let forces: Vec<_> = triangles
+        .par_iter()
+        .map(|t| t.force(particles, wind, air_density, time))
+        .collect();
+    for (t, force) in triangles.iter().zip(forces) {
+        for i in t.particles_idx {
+            let p = &mut particles[i as usize];
+            p.velocity += force / 3. * p.inv_mass * delta;
         }
     }
 }
+
+fn constraints_of_body<T: Constraint + Clone>(constraints: &[T], particles: &Range<u32>) -> Vec<T> {
+    constraints
+        .iter()
+        .filter(|c| particles.contains(&c.particles_idx()[0]))
+        .cloned()
+        .collect()
+}
+
+fn set_constraints_of_body<T: Constraint>(
+    constraints: &mut [T],
+    particles: &Range<u32>,
+    body_constraints: Vec<T>,
+) {
+    let constraints = constraints
+        .iter_mut()
+        .filter(|c| particles.contains(&c.particles_idx()[0]));
+    for (c, body_c) in constraints.zip(body_constraints) {
+        *c = body_c;
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+
+/// Cholesky factorization of a sparse symmetric positive definite matrix, stored in envelope
+/// (skyline) form after a reverse Cuthill-McKee reordering to keep the envelope narrow.
+pub struct EnvelopeCholesky {
+    /// `perm[new] = old`
+    perm: Vec<usize>,
+    /// First column stored for each row of the factor
+    first: Vec<usize>,
+    /// Offset of each row into `values`, rows span `first[i]..=i`
+    row_start: Vec<usize>,
+    values: Vec<f64>,
+}
+
+#[derive(Debug)]
+pub struct NotPositiveDefinite;
+
+impl EnvelopeCholesky {
+    /// Factorizes the `n` x `n` matrix given as `(row, col, value)` entries of its lower
+    /// triangle. Repeated entries are summed.
+    pub fn new(n: usize, entries: &[(usize, usize, f64)]) -> Result<Self, NotPositiveDefinite> {
+        let perm = reverse_cuthill_mckee(n, entries);
+        let mut inv_perm = vec![0; n];
+        for (new, old) in perm.iter().enumerate() {
+            inv_perm[*old] = new;
+        }
+
+        let mut first: Vec<_> = (0..n).collect();
+        for (i, j, _) in entries {
+            let (i, j) = (inv_perm[*i], inv_perm[*j]);
+            let (i, j) = (i.max(j), i.min(j));
+            first[i] = first[i].min(j);
+        }
+
+        let mut row_start = Vec::with_capacity(n + 1);
+        let mut len = 0;
+        for (i, f) in first.iter().enumerate() {
+            row_start.push(len);
+            len += i - f + 1;
+        }
+        row_start.push(len);
+
+        let mut values = vec![0.; len];
+        for (i, j, v) in entries {
+            let (i, j) = (inv_perm[*i], inv_perm[*j]);
+            let (i, j) = (i.max(j), i.min(j));
+            values[row_start[i] + j - first[i]] += v;
+        }
+
+        let mut factor = Self {
+            perm,
+            first,
+            row_start,
+            values,
+        };
+        factor.factorize()?;
+        Ok(factor)
+    }
+
+    #[inline]
+    fn at(&self, i: usize, j: usize) -> f64 {
+        self.values[self.row_start[i] + j - self.first[i]]
+    }
+
+    fn factorize(&mut self) -> Result<(), NotPositiveDefinite> {
+        for i in 0..self.first.len() {
+            for j in self.first[i]..i {
+                let start = self.first[i].max(self.first[j]);
+                let sum: f64 = (start..j).map(|k| self.at(i, k) * self.at(j, k)).sum();
+                let idx = self.row_start[i] + j - self.first[i];
+                self.values[idx] = (self.values[idx] - sum) / self.at(j, j);
+            }
+
+            let sum: f64 = (self.first[i]..i).map(|k| self.at(i, k).powi(2)).sum();
+            let idx = self.row_start[i] + i - self.first[i];
+            let d = self.values[idx] - sum;
+            if d <= 0. || !d.is_finite() {
+                return Err(NotPositiveDefinite);
+            }
+            self.values[idx] = d.sqrt();
+        }
+        Ok(())
+    }
+
+    /// Solves `A x = b` in place
+    pub fn solve(&self, b: &mut [f64]) {
+        let n = self.first.len();
+        let mut y: Vec<_> = self.perm.iter().map(|old| b[*old]).collect();
+
+        for i in 0..n {
+            let sum: f64 = (self.first[i]..i).map(|k| self.at(i, k) * y[k]).sum();
+            y[i] = (y[i] - sum) / self.at(i, i);
+        }
+
+        for i in (0..n).rev() {
+            y[i] /= self.at(i, i);
+            let yi = y[i];
+            let row = &self.values[self.row_start[i]..self.row_start[i] + i - self.first[i]];
+            for (y, l) in y[self.first[i]..i].iter_mut().zip(row) {
+                *y -= l * yi;
+            }
+        }
+
+        for (new, old) in self.perm.iter().enumerate() {
+            b[*old] = y[new];
+        }
+    }
+}
+
+fn reverse_cuthill_mckee(n: usize, entries: &[(usize, usize, f64)]) -> Vec<usize> {
+    let mut adjacency = vec![Vec::new(); n];
+    for (i, j, _) in entries {
+        if i != j {
+            adjacency[*i].push(*j);
+            adjacency[*j].push(*i);
+        }
+    }
+    adjacency.iter_mut().for_each(|a| {
+        a.sort_unstable();
+        a.dedup();
+    });
+
+    let mut by_degree: Vec<_> = (0..n).collect();
+    by_degree.sort_by_key(|i| adjacency[*i].len());
+
+    let mut visited = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    let mut queue = VecDeque::new();
+    for start in by_degree {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        queue.push_back(start);
+        while let Some(i) = queue.pop_front() {
+            order.push(i);
+            let mut neighbours: Vec<_> = adjacency[i].iter().filter(|j| !visited[**j]).collect();
+            neighbours.sort_by_key(|j| adjacency[**j].len());
+            for j in neighbours {
+                visited[*j] = true;
+                queue.push_back(*j);
+            }
+        }
+    }
+
+    order.reverse();
+    order
+}
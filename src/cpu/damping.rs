@@ -0,0 +1,59 @@
+use std::ops::Range;
+
+use glam::{Mat3, Vec3};
+
+use crate::Particle;
+
+/// Damping that only removes the velocity of a set of particles that deviates from their rigid
+/// body motion (Müller et al., Position Based Dynamics, section 3.5)
+pub struct RigidModeDamping {
+    pub particles: Range<u32>,
+    pub coefficient: f32,
+}
+
+impl RigidModeDamping {
+    pub fn apply(&self, particles: &mut [Particle]) {
+        let particles = &mut particles[self.particles.start as usize..self.particles.end as usize];
+        let dynamic = || particles.iter().filter(|p| p.inv_mass != 0.);
+
+        let mass: f32 = dynamic().map(|p| 1. / p.inv_mass).sum();
+        if mass == 0. {
+            return;
+        }
+        let center = dynamic().map(|p| p.position / p.inv_mass).sum::<Vec3>() / mass;
+        let velocity = dynamic().map(|p| p.velocity / p.inv_mass).sum::<Vec3>() / mass;
+
+        let (angular_momentum, inertia) = dynamic().fold(
+            (Vec3::ZERO, Mat3::ZERO),
+            |(angular_momentum, inertia), p| {
+                let r = p.position - center;
+                let r_cross = Mat3::from_cols(
+                    Vec3::new(0., r.z, -r.y),
+                    Vec3::new(-r.z, 0., r.x),
+                    Vec3::new(r.y, -r.x, 0.),
+                );
+                (
+                    angular_momentum + r.cross(p.velocity / p.inv_mass),
+                    inertia + r_cross * r_cross.transpose() * (1. / p.inv_mass),
+                )
+            },
+        );
+
+        // Regularized relative to the size of the body so that collinear bodies, whose inertia
+        // is singular along their axis, keep their rotation around the other two
+        let trace = inertia.x_axis.x + inertia.y_axis.y + inertia.z_axis.z;
+        let angular_velocity = if trace > 0. {
+            (inertia + Mat3::from_diagonal(Vec3::splat(trace * 1e-4))).inverse() * angular_momentum
+        } else {
+            Vec3::ZERO
+        };
+
+        particles
+            .iter_mut()
+            .filter(|p| p.inv_mass != 0.)
+            .for_each(|p| {
+                let rigid_velocity = velocity + angular_velocity.cross(p.position - center);
+                p.velocity += self.coefficient * (rigid_velocity - p.velocity);
+            });
+    }
+}
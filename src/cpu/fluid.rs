@@ -0,0 +1,220 @@
+use std::{f32::consts::PI, ops::Range};
+
+use glam::Vec3;
+use rayon::prelude::*;
+
+use crate::{FluidParams, Particle};
+
+use super::neighbours::NeighbourGrid;
+
+/// Fluid phases and the neighbours of their particles, found once per substep
+#[derive(Default)]
+pub struct Fluids {
+    pub phases: Vec<(Range<u32>, FluidParams)>,
+    /// Global index of every fluid particle with finite mass and the index of its phase
+    particles: Vec<(u32, usize)>,
+    /// Particles of any body closer than the kernel radius to each fluid particle
+    neighbours: Vec<Vec<u32>>,
+    /// Whether each particle of the simulation belongs to a fluid
+    is_fluid: Vec<bool>,
+}
+
+impl Fluids {
+    pub fn search_radius(&self) -> f32 {
+        self.phases
+            .iter()
+            .map(|(_, params)| params.kernel_radius)
+            .fold(0., f32::max)
+    }
+
+    pub fn find_neighbours(&mut self, particles: &[Particle], grid: &NeighbourGrid) {
+        if self.phases.is_empty() {
+            return;
+        }
+        self.particles = self
+            .phases
+            .iter()
+            .enumerate()
+            .flat_map(|(phase, (range, _))| range.clone().map(move |i| (i, phase)))
+            .filter(|(i, _)| particles[*i as usize].inv_mass != 0.)
+            .collect();
+        self.is_fluid = vec![false; particles.len()];
+        for (i, _) in &self.particles {
+            self.is_fluid[*i as usize] = true;
+        }
+        self.neighbours = self
+            .particles
+            .par_iter()
+            .map(|(i, phase)| grid.neighbours(particles, *i, self.phases[*phase].1.kernel_radius))
+            .collect();
+    }
+
+    /// Solves the density constraint of every fluid particle once, moving the particles of
+    /// other bodies it overlaps as well
+    pub fn solve(&self, particles: &mut [Particle]) {
+        if self.particles.is_empty() {
+            return;
+        }
+        let particles_ref = &*particles;
+
+        // Lambda of each fluid particle scaled by its rest volume
+        let mut scaled_lambdas = vec![0.; particles.len()];
+        let lambdas: Vec<_> = self
+            .particles
+            .par_iter()
+            .zip(&self.neighbours)
+            .map(|((i, phase), neighbours)| {
+                let params = &self.phases[*phase].1;
+                let h = params.kernel_radius;
+                let p = particles_ref[*i as usize];
+                let volume = 1. / (p.inv_mass * params.rest_density);
+
+                let mut density = poly6(0., h);
+                let mut gradient = Vec3::ZERO;
+                let mut gradients_sum = 0.;
+                for j in neighbours {
+                    let q = particles_ref[*j as usize];
+                    let r = p.position - q.position;
+                    density += poly6(r.length_squared(), h);
+                    let g = volume * spiky_gradient(r, h);
+                    gradient += g;
+                    gradients_sum += q.inv_mass * g.length_squared();
+                }
+                // Particles at the surface lack neighbours to reach the rest density, so only
+                // compression is resisted
+                let value = (volume * density - 1.).max(0.);
+                let denominator =
+                    p.inv_mass * (gradient.length_squared() + params.relaxation) + gradients_sum;
+                if denominator == 0. {
+                    0.
+                } else {
+                    -value / denominator * volume
+                }
+            })
+            .collect();
+        for ((i, _), lambda) in self.particles.iter().zip(&lambdas) {
+            scaled_lambdas[*i as usize] = *lambda;
+        }
+
+        let deltas: Vec<_> = self
+            .particles
+            .par_iter()
+            .zip(&self.neighbours)
+            .map(|((i, phase), neighbours)| {
+                let params = &self.phases[*phase].1;
+                let h = params.kernel_radius;
+                let p = particles_ref[*i as usize];
+                let volume = 1. / (p.inv_mass * params.rest_density);
+                let reference = poly6((params.artificial_pressure_distance * h).powi(2), h);
+
+                let mut delta = Vec3::ZERO;
+                let mut others = Vec::new();
+                for j in neighbours {
+                    let q = particles_ref[*j as usize];
+                    let r = p.position - q.position;
+                    let g = spiky_gradient(r, h);
+                    let correction = -params.artificial_pressure
+                        * (poly6(r.length_squared(), h) / reference)
+                            .powi(params.artificial_pressure_exponent);
+                    let lambda_j = scaled_lambdas[*j as usize];
+                    delta += (p.inv_mass * (scaled_lambdas[*i as usize] + lambda_j)
+                        + volume * correction)
+                        * g;
+                    // Particles of other bodies have no density constraint of their own, their
+                    // share of this one is applied here
+                    if !self.is_fluid[*j as usize] && q.inv_mass != 0. {
+                        others.push((*j, -q.inv_mass * scaled_lambdas[*i as usize] * g));
+                    }
+                }
+                (delta, others)
+            })
+            .collect();
+
+        for ((i, _), (delta, others)) in self.particles.iter().zip(deltas) {
+            particles[*i as usize].position += delta;
+            for (j, delta) in others {
+                particles[j as usize].position += delta;
+            }
+        }
+    }
+
+    /// Applies vorticity confinement and XSPH viscosity to the velocities of the fluid particles
+    pub fn update_velocities(&self, particles: &mut [Particle], delta: f32) {
+        if self.particles.is_empty() {
+            return;
+        }
+        let particles_ref = &*particles;
+
+        let mut vorticities = vec![Vec3::ZERO; particles.len()];
+        let fluid_vorticities: Vec<_> = self
+            .particles
+            .par_iter()
+            .zip(&self.neighbours)
+            .map(|((i, phase), neighbours)| {
+                let params = &self.phases[*phase].1;
+                let p = particles_ref[*i as usize];
+                let volume = 1. / (p.inv_mass * params.rest_density);
+                neighbours
+                    .iter()
+                    .map(|j| {
+                        let q = particles_ref[*j as usize];
+                        let r = p.position - q.position;
+                        volume
+                            * (q.velocity - p.velocity)
+                                .cross(-spiky_gradient(r, params.kernel_radius))
+                    })
+                    .sum::<Vec3>()
+            })
+            .collect();
+        for ((i, _), vorticity) in self.particles.iter().zip(&fluid_vorticities) {
+            vorticities[*i as usize] = *vorticity;
+        }
+
+        let velocities: Vec<_> = self
+            .particles
+            .par_iter()
+            .zip(&self.neighbours)
+            .map(|((i, phase), neighbours)| {
+                let params = &self.phases[*phase].1;
+                let h = params.kernel_radius;
+                let p = particles_ref[*i as usize];
+                let volume = 1. / (p.inv_mass * params.rest_density);
+
+                let mut location = Vec3::ZERO;
+                let mut viscosity = Vec3::ZERO;
+                for j in neighbours {
+                    let q = particles_ref[*j as usize];
+                    let r = p.position - q.position;
+                    location += volume * vorticities[*j as usize].length() * spiky_gradient(r, h);
+                    viscosity += volume * poly6(r.length_squared(), h) * (q.velocity - p.velocity);
+                }
+                let vorticity = vorticities[*i as usize];
+                let confinement = params.vorticity * location.normalize_or_zero().cross(vorticity);
+                p.velocity + delta * confinement + params.viscosity * viscosity
+            })
+            .collect();
+
+        for ((i, _), velocity) in self.particles.iter().zip(velocities) {
+            particles[*i as usize].velocity = velocity;
+        }
+    }
+}
+
+/// Poly6 kernel, from the squared distance
+fn poly6(r2: f32, h: f32) -> f32 {
+    if r2 >= h * h {
+        0.
+    } else {
+        315. / (64. * PI * h.powi(9)) * (h * h - r2).powi(3)
+    }
+}
+
+/// Gradient of the spiky kernel
+fn spiky_gradient(r: Vec3, h: f32) -> Vec3 {
+    let length = r.length();
+    if length >= h || length == 0. {
+        Vec3::ZERO
+    } else {
+        -45. / (PI * h.powi(6)) * (h - length).powi(2) * r / length
+    }
+}
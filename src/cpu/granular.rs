@@ -0,0 +1,135 @@
+use std::ops::Range;
+
+use glam::Vec3;
+use rayon::prelude::*;
+
+use crate::{GranularParams, Particle};
+
+use super::neighbours::NeighbourGrid;
+
+/// Granular phases and the particles near their grains, found once per substep
+#[derive(Default)]
+pub struct Grains {
+    pub phases: Vec<(Range<u32>, GranularParams)>,
+    /// Global index of every grain with finite mass and the index of its phase
+    particles: Vec<(u32, usize)>,
+    /// Particles of any body within the search radius of each grain
+    contacts: Vec<Vec<u32>>,
+    /// Whether each particle of the simulation is a grain
+    is_grain: Vec<bool>,
+}
+
+impl Grains {
+    pub fn search_radius(&self) -> f32 {
+        self.phases
+            .iter()
+            .map(|(_, params)| params.search_radius())
+            .fold(0., f32::max)
+    }
+
+    pub fn find_contacts(&mut self, particles: &[Particle], grid: &NeighbourGrid) {
+        if self.phases.is_empty() {
+            return;
+        }
+        self.particles = self
+            .phases
+            .iter()
+            .enumerate()
+            .flat_map(|(phase, (range, _))| range.clone().map(move |i| (i, phase)))
+            .filter(|(i, _)| particles[*i as usize].inv_mass != 0.)
+            .collect();
+        self.is_grain = vec![false; particles.len()];
+        for (i, _) in &self.particles {
+            self.is_grain[*i as usize] = true;
+        }
+        self.contacts = self
+            .particles
+            .par_iter()
+            .map(|(i, phase)| grid.neighbours(particles, *i, self.phases[*phase].1.search_radius()))
+            .collect();
+    }
+
+    /// Solves the contacts of every grain once, averaging the corrections of each grain over
+    /// its contacts and moving the particles of other bodies it touches as well
+    pub fn solve(&self, particles: &mut [Particle]) {
+        if self.particles.is_empty() {
+            return;
+        }
+        let particles_ref = &*particles;
+
+        let deltas: Vec<_> = self
+            .particles
+            .par_iter()
+            .zip(&self.contacts)
+            .map(|((i, phase), contacts)| {
+                let params = &self.phases[*phase].1;
+                let p = particles_ref[*i as usize];
+
+                let mut corrections = Vec::new();
+                for j in contacts {
+                    let q = particles_ref[*j as usize];
+                    let Some(normal) = (p.position - q.position).try_normalize() else {
+                        continue;
+                    };
+                    let depth = 2. * params.radius - (p.position - q.position).length();
+                    let correction = if depth > 0. {
+                        contact_correction(&p, &q, normal, depth, params)
+                    } else if params.cohesion > 0. {
+                        params.cohesion * depth * normal
+                    } else {
+                        continue;
+                    };
+                    corrections.push((*j, correction / (p.inv_mass + q.inv_mass)));
+                }
+
+                let count = corrections.len().max(1) as f32;
+                let delta = corrections
+                    .iter()
+                    .map(|(_, c)| p.inv_mass * *c)
+                    .sum::<Vec3>()
+                    / count;
+                // Particles of other bodies have no contacts of their own, their share of this
+                // grain's is applied here
+                let others: Vec<_> = corrections
+                    .into_iter()
+                    .filter(|(j, _)| !self.is_grain[*j as usize])
+                    .map(|(j, c)| (j, -particles_ref[j as usize].inv_mass * c / count))
+                    .collect();
+                (delta, others)
+            })
+            .collect();
+
+        for ((i, _), (delta, others)) in self.particles.iter().zip(deltas) {
+            particles[*i as usize].position += delta;
+            for (j, delta) in others {
+                particles[j as usize].position += delta;
+            }
+        }
+    }
+}
+
+/// Relative correction separating two touching particles and resisting their tangential motion
+/// since the start of the substep
+fn contact_correction(
+    p: &Particle,
+    q: &Particle,
+    normal: Vec3,
+    depth: f32,
+    params: &GranularParams,
+) -> Vec3 {
+    let motion = (p.position - p.prev_position) - (q.position - q.prev_position);
+    let tangential = motion - motion.dot(normal) * normal;
+    let length = tangential.length();
+    let friction = if length < params.static_friction * depth {
+        tangential
+    } else {
+        tangential * (params.kinetic_friction * depth / length).min(1.)
+    };
+    let remaining = tangential - friction;
+    let damping = if remaining == Vec3::ZERO {
+        Vec3::ZERO
+    } else {
+        remaining * (params.tangential_damping * depth / remaining.length()).min(1.)
+    };
+    depth * normal - friction - damping
+}
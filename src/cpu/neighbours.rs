@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+use glam::{IVec3, Vec3};
+
+use crate::Particle;
+
+/// Particles binned in a uniform grid, to find the particles near a given one without testing
+/// every pair
+pub struct NeighbourGrid {
+    cell_size: f32,
+    cells: HashMap<IVec3, Vec<u32>>,
+}
+
+impl NeighbourGrid {
+    /// Grid of the current positions of `particles`, with cells at least as large as the
+    /// largest radius queried
+    pub fn new(particles: &[Particle], cell_size: f32) -> Self {
+        let mut cells: HashMap<IVec3, Vec<u32>> = HashMap::new();
+        for (i, p) in particles.iter().enumerate() {
+            cells
+                .entry(cell(p.position, cell_size))
+                .or_default()
+                .push(i as u32);
+        }
+        Self { cell_size, cells }
+    }
+
+    /// Particles other than `idx` closer than `radius` to it
+    pub fn neighbours(&self, particles: &[Particle], idx: u32, radius: f32) -> Vec<u32> {
+        let position = particles[idx as usize].position;
+        let center = cell(position, self.cell_size);
+        let mut neighbours = Vec::new();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let Some(cell) = self.cells.get(&(center + IVec3::new(x, y, z))) else {
+                        continue;
+                    };
+                    neighbours.extend(cell.iter().filter(|j| {
+                        **j != idx
+                            && particles[**j as usize].position.distance_squared(position)
+                                < radius * radius
+                    }));
+                }
+            }
+        }
+        neighbours
+    }
+}
+
+fn cell(position: Vec3, cell_size: f32) -> IVec3 {
+    (position / cell_size).floor().as_ivec3()
+}
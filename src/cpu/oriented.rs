@@ -0,0 +1,60 @@
+use std::ops::Range;
+
+use glam::Vec3;
+
+use crate::{
+    AttachmentC, BendTwistC, BodyHandle, JointC, Particle, QuatParticle, RigidBody, StretchShearC,
+};
+
+/// Rigid bodies and rods, which carry orientations besides the particle positions, and the
+/// constraints acting on them
+#[derive(Default)]
+pub struct Oriented {
+    pub rigid_bodies: Vec<RigidBody>,
+    pub joints: Vec<JointC>,
+    /// Attachments with the global index of their particle
+    pub attachments: Vec<(u32, AttachmentC)>,
+    pub quat_particles: Vec<QuatParticle>,
+    pub stretch_shear_constraints: Vec<StretchShearC>,
+    pub bend_twist_constraints: Vec<BendTwistC>,
+    /// Range of each rod's orientations in `quat_particles`
+    pub rods: Vec<(BodyHandle, Range<u32>)>,
+}
+
+impl Oriented {
+    pub fn integrate(&mut self, gravity: Vec3, delta: f32) {
+        self.rigid_bodies
+            .iter_mut()
+            .for_each(|b| b.integrate(gravity, delta));
+        self.quat_particles
+            .iter_mut()
+            .for_each(|q| q.integrate(delta));
+    }
+
+    /// Solves every constraint once, one after the other
+    pub fn solve(&mut self, particles: &mut [Particle], delta: f32) {
+        let rigid_bodies = &mut self.rigid_bodies;
+        for j in &self.joints {
+            j.solve(rigid_bodies, delta);
+        }
+        for (particle_idx, a) in &self.attachments {
+            a.solve(*particle_idx, particles, rigid_bodies, delta);
+        }
+
+        for c in &self.stretch_shear_constraints {
+            c.solve(particles, &mut self.quat_particles, delta);
+        }
+        for c in &self.bend_twist_constraints {
+            c.solve(&mut self.quat_particles, delta);
+        }
+    }
+
+    pub fn update_velocities(&mut self, delta: f32, velocity_scale: f32) {
+        self.rigid_bodies
+            .iter_mut()
+            .for_each(|b| b.update_velocities(delta, velocity_scale));
+        self.quat_particles
+            .iter_mut()
+            .for_each(|q| q.update_velocity(delta, velocity_scale));
+    }
+}
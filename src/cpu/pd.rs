@@ -0,0 +1,288 @@
+use glam::{DVec3, Vec3};
+use rayon::prelude::*;
+
+use crate::{DistanceC, FiberC, Particle, TetrahedralVolumeC};
+
+use super::{
+    cholesky::{EnvelopeCholesky, NotPositiveDefinite},
+    Constraint,
+};
+
+/// Compliances below this are clamped, since the constraint weights are 1 / compliance
+const MIN_COMPLIANCE: f32 = 1e-9;
+
+/// Ratio between the weight of a constraint and its factorized one beyond which the system is
+/// factorized again, either way. Smaller changes keep the factorized weights, so that morphing
+/// rest shapes, which the weights of tetrahedra and fibers depend on, only refactorizes a few
+/// times over the morph.
+const REFACTOR_RATIO: f64 = 1.25;
+
+/// Projective Dynamics solver state. The global system matrix
+/// `M / h^2 + sum(w_i S_i^T A_i^T A_i S_i)` is constant for a given time step and set of
+/// constraints, so it is factorized once and reused every iteration.
+pub struct Pd {
+    delta: f32,
+    /// Index of each particle in the reduced system, `None` for particles with infinite mass
+    free_idx: Vec<Option<usize>>,
+    free_n: usize,
+    /// Coupling between free and pinned particles, moved to the right hand side
+    pinned_coupling: Vec<(usize, usize, f64)>,
+    factor: EnvelopeCholesky,
+    /// Weights of the distance, volume and fiber constraints in that order, as factorized
+    weights: Vec<f64>,
+    inertial_positions: Vec<Vec3>,
+    projections: Vec<Vec3>,
+}
+
+/// Positions of the particles of `c` projected onto its constraint manifold and then centered,
+/// i.e. the target of `A_i S_i x` where `A_i` is the centering matrix
+fn project<T: Constraint>(c: &T, particles: &[Particle], out: &mut Vec<Vec3>) {
+    let particles_idx = c.particles_idx();
+    let gradients = c.gradients(particles);
+    let grad_sum: f32 = gradients.iter().map(|g| g.length_squared()).sum();
+    let lambda = if grad_sum > 0. {
+        -c.value(particles) / grad_sum
+    } else {
+        0.
+    };
+
+    let start = out.len();
+    out.extend(
+        particles_idx
+            .iter()
+            .zip(gradients)
+            .map(|(i, g)| particles[*i as usize].position + lambda * g),
+    );
+    let center = out[start..].iter().sum::<Vec3>() / particles_idx.len() as f32;
+    out[start..].iter_mut().for_each(|p| *p -= center);
+}
+
+fn weight<T: Constraint>(c: &T) -> f64 {
+    if c.is_enabled() {
+        1. / c.compliance().max(MIN_COMPLIANCE) as f64
+    } else {
+        0.
+    }
+}
+
+/// The projection of a distance constraint moves each of its particles by half its value, so
+/// doubling the weight gives it the stiffness it has with the XPBD solvers. Range constraints
+/// are left out of the system, which would keep pulling on them while they are slack.
+fn distance_weight(c: &DistanceC) -> f64 {
+    if c.is_range() {
+        0.
+    } else {
+        weight(c) * 2.
+    }
+}
+
+/// The projection of a tetrahedron moves its particles by its value over its gradient norm, so
+/// scaling the weight by that norm in the rest shape gives tetrahedra close to it the stiffness
+/// they have with the XPBD solvers
+fn volume_weight(c: &TetrahedralVolumeC) -> f64 {
+    weight(c) * c.rest_gradient_norm_squared() as f64
+}
+
+/// The projection of a fiber moves its particles by its value over its gradient norm, which is
+/// the same in every configuration, so scaling the weight by it gives fibers the stiffness they
+/// have with the XPBD solvers
+fn fiber_weight(c: &FiberC) -> f64 {
+    weight(c) * c.gradient_norm_squared() as f64
+}
+
+/// Current weights of the distance, volume and fiber constraints in that order
+fn weights<'a>(
+    distance_constraints: &'a [DistanceC],
+    volume_constraints: &'a [TetrahedralVolumeC],
+    fiber_constraints: &'a [FiberC],
+) -> impl Iterator<Item = f64> + 'a {
+    distance_constraints
+        .iter()
+        .map(distance_weight)
+        .chain(volume_constraints.iter().map(volume_weight))
+        .chain(fiber_constraints.iter().map(fiber_weight))
+}
+
+/// Particles of the distance, volume and fiber constraints in that order
+fn particles_idx<'a>(
+    distance_constraints: &'a [DistanceC],
+    volume_constraints: &'a [TetrahedralVolumeC],
+    fiber_constraints: &'a [FiberC],
+) -> impl Iterator<Item = Vec<u32>> + 'a {
+    distance_constraints
+        .iter()
+        .map(|c| c.particles_idx())
+        .chain(volume_constraints.iter().map(|c| c.particles_idx()))
+        .chain(fiber_constraints.iter().map(|c| c.particles_idx()))
+}
+
+/// Adds `w S^T A^T A S` to the lower triangle of the system, `A` being the centering matrix
+fn add_constraint_entries(particles_idx: &[u32], w: f64, entries: &mut Vec<(usize, usize, f64)>) {
+    let n = particles_idx.len() as f64;
+    for (a, i) in particles_idx.iter().enumerate() {
+        for (b, j) in particles_idx.iter().enumerate().take(a + 1) {
+            let value = if a == b { 1. - 1. / n } else { -1. / n };
+            entries.push((*i as usize, *j as usize, w * value));
+        }
+    }
+}
+
+impl Pd {
+    pub fn new(
+        particles: &[Particle],
+        distance_constraints: &[DistanceC],
+        volume_constraints: &[TetrahedralVolumeC],
+        fiber_constraints: &[FiberC],
+        delta: f32,
+    ) -> Result<Self, NotPositiveDefinite> {
+        let mut free_n = 0;
+        let free_idx: Vec<_> = particles
+            .iter()
+            .map(|p| {
+                (p.inv_mass != 0.).then(|| {
+                    free_n += 1;
+                    free_n - 1
+                })
+            })
+            .collect();
+
+        let weights: Vec<_> =
+            weights(distance_constraints, volume_constraints, fiber_constraints).collect();
+        let mut entries = Vec::new();
+        particles_idx(distance_constraints, volume_constraints, fiber_constraints)
+            .zip(&weights)
+            .for_each(|(particles_idx, w)| {
+                add_constraint_entries(&particles_idx, *w, &mut entries)
+            });
+
+        let mut reduced = Vec::with_capacity(entries.len() + free_n);
+        let mut pinned_coupling = Vec::new();
+        for (i, j, v) in entries {
+            match (free_idx[i], free_idx[j]) {
+                (Some(i), Some(j)) => reduced.push((i, j, v)),
+                (Some(f), None) => pinned_coupling.push((f, j, v)),
+                (None, Some(f)) => pinned_coupling.push((f, i, v)),
+                (None, None) => {}
+            }
+        }
+        let h2 = (delta as f64).powi(2);
+        reduced.extend(
+            particles
+                .iter()
+                .zip(&free_idx)
+                .filter_map(|(p, i)| i.map(|i| (i, i, 1. / p.inv_mass as f64 / h2))),
+        );
+
+        let factor = EnvelopeCholesky::new(free_n, &reduced)?;
+
+        Ok(Self {
+            delta,
+            free_idx,
+            free_n,
+            pinned_coupling,
+            factor,
+            weights,
+            inertial_positions: Vec::new(),
+            projections: Vec::new(),
+        })
+    }
+
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+
+    /// Whether the weight of a constraint moved away from the factorized one by more than
+    /// [`REFACTOR_RATIO`], or the number of constraints changed
+    pub fn weights_changed(
+        &self,
+        distance_constraints: &[DistanceC],
+        volume_constraints: &[TetrahedralVolumeC],
+        fiber_constraints: &[FiberC],
+    ) -> bool {
+        let n = distance_constraints.len() + volume_constraints.len() + fiber_constraints.len();
+        n != self.weights.len()
+            || weights(distance_constraints, volume_constraints, fiber_constraints)
+                .zip(&self.weights)
+                .any(|(w, factorized)| {
+                    w > factorized * REFACTOR_RATIO || w * REFACTOR_RATIO < *factorized
+                })
+    }
+
+    /// Must be called after the particles have been moved to their inertial positions
+    pub fn solve(
+        &mut self,
+        particles: &mut [Particle],
+        distance_constraints: &[DistanceC],
+        volume_constraints: &[TetrahedralVolumeC],
+        fiber_constraints: &[FiberC],
+        iterations: u32,
+    ) {
+        self.inertial_positions.clear();
+        self.inertial_positions
+            .extend(particles.iter().map(|p| p.position));
+        let h2 = (self.delta as f64).powi(2);
+
+        for _ in 0..iterations {
+            // Local step
+            self.projections.clear();
+            distance_constraints
+                .iter()
+                .for_each(|c| project(c, particles, &mut self.projections));
+            volume_constraints
+                .iter()
+                .for_each(|c| project(c, particles, &mut self.projections));
+            fiber_constraints
+                .iter()
+                .for_each(|c| project(c, particles, &mut self.projections));
+
+            // Global step, right hand side M / h^2 y + sum(w_i S_i^T A_i^T p_i)
+            let mut rhs = vec![DVec3::ZERO; self.free_n];
+            for ((p, y), i) in particles
+                .iter()
+                .zip(&self.inertial_positions)
+                .zip(&self.free_idx)
+            {
+                if let Some(i) = i {
+                    rhs[*i] = y.as_dvec3() / p.inv_mass as f64 / h2;
+                }
+            }
+
+            // With the factorized weights, which may lag behind the current ones
+            let mut projections = self.projections.iter();
+            let constraints =
+                particles_idx(distance_constraints, volume_constraints, fiber_constraints)
+                    .zip(&self.weights);
+            for (particles_idx, w) in constraints {
+                for i in particles_idx {
+                    let p = projections.next().unwrap().as_dvec3();
+                    if let Some(i) = self.free_idx[i as usize] {
+                        rhs[i] += *w * p;
+                    }
+                }
+            }
+
+            for (f, pinned, v) in &self.pinned_coupling {
+                rhs[*f] -= *v * particles[*pinned].position.as_dvec3();
+            }
+
+            let solutions: Vec<_> = (0..3)
+                .into_par_iter()
+                .map(|axis| {
+                    let mut b: Vec<_> = rhs.iter().map(|r| r[axis]).collect();
+                    self.factor.solve(&mut b);
+                    b
+                })
+                .collect();
+
+            for (p, i) in particles.iter_mut().zip(&self.free_idx) {
+                if let Some(i) = i {
+                    p.position = Vec3::new(
+                        solutions[0][*i] as f32,
+                        solutions[1][*i] as f32,
+                        solutions[2][*i] as f32,
+                    );
+                }
+            }
+        }
+    }
+}
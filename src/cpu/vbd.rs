@@ -0,0 +1,190 @@
+use glam::{Mat3, Vec3};
+use rayon::prelude::*;
+
+use crate::{DistanceC, Particle, TetrahedralVolumeC};
+
+use super::Constraint;
+
+/// Compliances below this are clamped, since VBD works with stiffness = 1 / compliance
+const MIN_COMPLIANCE: f32 = 1e-9;
+
+/// For each particle, the constraints it takes part in as (constraint index, position in constraint)
+struct Incidence(Vec<Vec<(u32, u32)>>);
+
+impl Incidence {
+    fn new<T: Constraint>(particles_n: usize, constraints: &[T]) -> Self {
+        let mut incidence = vec![Vec::new(); particles_n];
+        for (c_idx, c) in constraints.iter().enumerate() {
+            for (local, p_idx) in c.particles_idx().into_iter().enumerate() {
+                incidence[p_idx as usize].push((c_idx as u32, local as u32));
+            }
+        }
+        Self(incidence)
+    }
+
+    /// Adds the force and Gauss-Newton hessian of the constraint energies acting on `p_idx`,
+    /// including the constraint damping `beta * grad(C) grad(C)^T` of compliant constraints
+    fn accumulate<T: Constraint>(
+        &self,
+        p_idx: usize,
+        particles: &[Particle],
+        constraints: &[T],
+        delta: f32,
+        force: &mut Vec3,
+        hessian: &mut Mat3,
+    ) {
+        let p = &particles[p_idx];
+        for (c_idx, local) in &self.0[p_idx] {
+            let c = &constraints[*c_idx as usize];
+            if !c.is_active(particles) {
+                continue;
+            }
+            let compliance = c.solve_compliance(particles);
+            let stiffness = 1. / compliance.max(MIN_COMPLIANCE);
+            let value = c.value(particles);
+            let grad = c.gradients(particles)[*local as usize];
+            let grad_outer = outer(grad, grad);
+            // The force of the XPBD damping gamma = compliance * beta / delta, which vanishes on
+            // hard constraints
+            let damping = if compliance > 0. {
+                c.damping() / delta
+            } else {
+                0.
+            };
+
+            *force -=
+                stiffness * value * grad + damping * (grad_outer * (p.position - p.prev_position));
+            *hessian += (stiffness + damping) * grad_outer;
+        }
+    }
+}
+
+fn outer(a: Vec3, b: Vec3) -> Mat3 {
+    Mat3::from_cols(a * b.x, a * b.y, a * b.z)
+}
+
+/// Vertex Block Descent solver state: constraint incidence and a vertex coloring such that
+/// particles of the same color never share a constraint.
+pub struct Vbd {
+    distance: Incidence,
+    volume: Incidence,
+    colors: Vec<Vec<u32>>,
+    inertial_positions: Vec<Vec3>,
+}
+
+impl Vbd {
+    pub fn new(
+        particles: &[Particle],
+        distance_constraints: &[DistanceC],
+        volume_constraints: &[TetrahedralVolumeC],
+    ) -> Self {
+        let particles_n = particles.len();
+        let distance = Incidence::new(particles_n, distance_constraints);
+        let volume = Incidence::new(particles_n, volume_constraints);
+
+        // Greedy coloring of the graph where particles sharing a constraint are adjacent
+        let mut particle_colors: Vec<Option<usize>> = vec![None; particles_n];
+        let mut colors: Vec<Vec<u32>> = Vec::new();
+        let mut used = Vec::new();
+        for p_idx in 0..particles_n {
+            used.clear();
+            used.resize(colors.len(), false);
+
+            let neighbours = distance.0[p_idx]
+                .iter()
+                .flat_map(|(c, _)| distance_constraints[*c as usize].particles_idx())
+                .chain(
+                    volume.0[p_idx]
+                        .iter()
+                        .flat_map(|(c, _)| volume_constraints[*c as usize].particles_idx()),
+                );
+            for n in neighbours {
+                if let Some(color) = particle_colors[n as usize] {
+                    used[color] = true;
+                }
+            }
+
+            let color = used.iter().position(|u| !u).unwrap_or(colors.len());
+            if color == colors.len() {
+                colors.push(Vec::new());
+            }
+            colors[color].push(p_idx as u32);
+            particle_colors[p_idx] = Some(color);
+        }
+
+        Self {
+            distance,
+            volume,
+            colors,
+            inertial_positions: Vec::new(),
+        }
+    }
+
+    /// Must be called after the particles have been moved to their inertial positions
+    pub fn solve(
+        &mut self,
+        particles: &mut [Particle],
+        distance_constraints: &[DistanceC],
+        volume_constraints: &[TetrahedralVolumeC],
+        delta: f32,
+        iterations: u32,
+    ) {
+        self.inertial_positions.clear();
+        self.inertial_positions
+            .extend(particles.iter().map(|p| p.position));
+
+        let Self {
+            distance,
+            volume,
+            colors,
+            inertial_positions,
+        } = self;
+
+        for _ in 0..iterations {
+            for color in colors.iter() {
+                let deltas: Vec<_> = color
+                    .par_iter()
+                    .map(|p_idx| {
+                        let p_idx = *p_idx as usize;
+                        let p = &particles[p_idx];
+                        if p.inv_mass == 0. {
+                            return Vec3::ZERO;
+                        }
+
+                        let inertia = 1. / p.inv_mass / delta / delta;
+                        let mut force = -inertia * (p.position - inertial_positions[p_idx]);
+                        let mut hessian = Mat3::from_diagonal(Vec3::splat(inertia));
+
+                        distance.accumulate(
+                            p_idx,
+                            particles,
+                            distance_constraints,
+                            delta,
+                            &mut force,
+                            &mut hessian,
+                        );
+                        volume.accumulate(
+                            p_idx,
+                            particles,
+                            volume_constraints,
+                            delta,
+                            &mut force,
+                            &mut hessian,
+                        );
+
+                        let x_delta = hessian.inverse() * force;
+                        if x_delta.is_finite() {
+                            x_delta
+                        } else {
+                            Vec3::ZERO
+                        }
+                    })
+                    .collect();
+
+                color.iter().zip(deltas).for_each(|(p_idx, x_delta)| {
+                    particles[*p_idx as usize].position += x_delta;
+                });
+            }
+        }
+    }
+}
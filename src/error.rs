@@ -0,0 +1,112 @@
+use std::fmt;
+
+use crate::{BodyHandle, RigidBodyHandle};
+
+#[derive(Debug)]
+pub enum Error {
+    Index(IndexError),
+    /// A particle or constraint was given a negative or non finite parameter
+    InvalidParameter {
+        name: &'static str,
+        value: f32,
+    },
+    /// The Projective Dynamics system could not be factorized
+    NotPositiveDefinite,
+    /// Data does not fit the layout of a GPU buffer
+    Encoding(encase::internal::Error),
+    /// A GPU buffer could not be mapped for reading
+    BufferAsync(wgpu::BufferAsyncError),
+    /// A wgpu validation or out of memory error caught by an error scope
+    Wgpu(wgpu::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(e) => e.fmt(f),
+            Self::InvalidParameter { name, value } => write!(f, "invalid {name}: {value}"),
+            Self::NotPositiveDefinite => {
+                write!(f, "Projective Dynamics system is not positive definite")
+            }
+            Self::Encoding(e) => e.fmt(f),
+            Self::BufferAsync(e) => e.fmt(f),
+            Self::Wgpu(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Index(e) => Some(e),
+            Self::Encoding(e) => Some(e),
+            Self::BufferAsync(e) => Some(e),
+            Self::Wgpu(e) => Some(e),
+            Self::InvalidParameter { .. } | Self::NotPositiveDefinite => None,
+        }
+    }
+}
+
+impl From<IndexError> for Error {
+    fn from(e: IndexError) -> Self {
+        Self::Index(e)
+    }
+}
+
+impl From<encase::internal::Error> for Error {
+    fn from(e: encase::internal::Error) -> Self {
+        Self::Encoding(e)
+    }
+}
+
+impl From<wgpu::BufferAsyncError> for Error {
+    fn from(e: wgpu::BufferAsyncError) -> Self {
+        Self::BufferAsync(e)
+    }
+}
+
+impl From<wgpu::Error> for Error {
+    fn from(e: wgpu::Error) -> Self {
+        Self::Wgpu(e)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexError {
+    /// The body was never added to the simulation or has been removed
+    InvalidBody(BodyHandle),
+    /// The rigid body was never added to the simulation
+    InvalidRigidBody(RigidBodyHandle),
+    /// A particle index is not smaller than the number of particles of its body
+    ParticleOutOfRange { particle_idx: u32, particles_n: u32 },
+    /// A constraint index is not smaller than the number of constraints of its type in the body
+    ConstraintOutOfRange {
+        constraint_idx: u32,
+        constraints_n: u32,
+    },
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidBody(handle) => write!(f, "{handle:?} is not in the simulation"),
+            Self::InvalidRigidBody(handle) => write!(f, "{handle:?} is not in the simulation"),
+            Self::ParticleOutOfRange {
+                particle_idx,
+                particles_n,
+            } => write!(
+                f,
+                "particle index {particle_idx} is out of range for a body of {particles_n} particles"
+            ),
+            Self::ConstraintOutOfRange {
+                constraint_idx,
+                constraints_n,
+            } => write!(
+                f,
+                "constraint index {constraint_idx} is out of range for a body of {constraints_n} constraints"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IndexError {}
@@ -0,0 +1,126 @@
+use encase::ShaderType;
+use glam::{Mat3, Vec3};
+
+use crate::{lerp, non_negative, signed_volume, validate_indices, Error, Particle};
+
+/// Fibers running through a tetrahedron along a rest direction, which resist stretching and
+/// contract to `1 - activation` of their rest length when activated, a simple active stress
+/// along the fiber for muscles. The compliance is per unit of rest volume.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, ShaderType)]
+pub struct FiberC {
+    pub(crate) particles_idx: [u32; 4],
+    /// Weights of the edges from the first particle giving the deformed fiber, the rest fiber
+    /// direction transformed by the inverse of the rest edge vectors
+    pub(crate) weights: Vec3,
+    pub(crate) rest_volume: f32,
+    pub(crate) activation: f32,
+    pub(crate) compliance: f32,
+}
+
+impl FiberC {
+    /// Constraint with `rest` as the rest positions of the particles and `fiber` as the fiber
+    /// direction in the rest shape
+    pub fn new(
+        particles_idx: [u32; 4],
+        rest: [Vec3; 4],
+        fiber: Vec3,
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        let Some(fiber) = fiber.try_normalize() else {
+            return Err(Error::InvalidParameter {
+                name: "fiber direction",
+                value: fiber.length(),
+            });
+        };
+        let rest_volume = signed_volume(rest).abs();
+        let edges = Mat3::from_cols(rest[1] - rest[0], rest[2] - rest[0], rest[3] - rest[0]);
+        let scale = edges
+            .to_cols_array()
+            .iter()
+            .fold(0f32, |m, x| m.max(x.abs()));
+        if !(rest_volume > f32::EPSILON * scale.powi(3) && rest_volume.is_finite()) {
+            return Err(Error::InvalidParameter {
+                name: "rest volume",
+                value: rest_volume,
+            });
+        }
+        Ok(Self {
+            particles_idx,
+            weights: edges.inverse() * fiber,
+            rest_volume,
+            activation: 0.,
+            compliance: non_negative("compliance", compliance)?,
+        })
+    }
+
+    /// Constraint with the current positions of the particles as the rest shape
+    pub fn from_particles(
+        particles_idx: [u32; 4],
+        particles: &[Particle],
+        fiber: Vec3,
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        validate_indices([&particles_idx[..]], particles.len() as u32)?;
+        let rest = particles_idx.map(|i| particles[i as usize].position);
+        Self::new(particles_idx, rest, fiber, compliance)
+    }
+
+    /// Sets the fraction of its rest length the fiber contracts by, from 0 (passive) up to but
+    /// excluding 1
+    pub fn set_activation(&mut self, activation: f32) -> Result<(), Error> {
+        self.activation = validate_activation(activation)?;
+        Ok(())
+    }
+
+    pub fn activation(&self) -> f32 {
+        self.activation
+    }
+
+    /// Deformed fiber, whose length is the stretch along the fiber
+    pub fn fiber(&self, particles: &[Particle]) -> Vec3 {
+        let [x0, x1, x2, x3] = self.particles_idx.map(|i| particles[i as usize].position);
+        Mat3::from_cols(x1 - x0, x2 - x0, x3 - x0) * self.weights
+    }
+
+    /// Sum of the squared norms of the gradients, the same in every configuration
+    pub(crate) fn gradient_norm_squared(&self) -> f32 {
+        self.weights.length_squared() + (self.weights.x + self.weights.y + self.weights.z).powi(2)
+    }
+
+    pub(crate) fn offset(mut self, offset: u32) -> Self {
+        self.particles_idx = self.particles_idx.map(|i| i + offset);
+        self
+    }
+
+    /// Constraint with the `rest` positions of its particles as the rest shape, the fiber
+    /// following the deformation from the previous rest shape
+    pub(crate) fn with_rest_shape(&self, rest: &[Vec3]) -> Result<Self, Error> {
+        let rest = self.particles_idx.map(|i| rest[i as usize]);
+        let edges = Mat3::from_cols(rest[1] - rest[0], rest[2] - rest[0], rest[3] - rest[0]);
+        let mut c = Self::new(
+            self.particles_idx,
+            rest,
+            edges * self.weights,
+            self.compliance,
+        )?;
+        c.activation = self.activation;
+        Ok(c)
+    }
+
+    pub(crate) fn blend_rest(&mut self, from: &Self, to: &Self, t: f32) {
+        self.weights = from.weights.lerp(to.weights, t);
+        self.rest_volume = lerp(from.rest_volume, to.rest_volume, t);
+    }
+}
+
+pub(crate) fn validate_activation(activation: f32) -> Result<f32, Error> {
+    if (0. ..1.).contains(&activation) {
+        Ok(activation)
+    } else {
+        Err(Error::InvalidParameter {
+            name: "activation",
+            value: activation,
+        })
+    }
+}
@@ -0,0 +1,104 @@
+use glam::Vec3;
+
+use crate::{non_negative, Error, Particle};
+
+/// Parameters of a Position Based Fluid (Macklin and Müller 2013, "Position Based Fluids")
+#[derive(Clone, Copy, Debug)]
+pub struct FluidParams {
+    /// Density the fluid keeps when compressed
+    pub rest_density: f32,
+    /// Radius of the SPH kernels, about twice the particle spacing
+    pub kernel_radius: f32,
+    /// Softens the density constraint (epsilon), in the units of its squared gradient, which
+    /// scale with the inverse of the squared particle spacing
+    pub relaxation: f32,
+    /// Strength of the artificial pressure keeping particles from clumping at the surface (k)
+    pub artificial_pressure: f32,
+    /// Exponent of the artificial pressure (n)
+    pub artificial_pressure_exponent: i32,
+    /// Distance the artificial pressure is measured against, as a fraction of the kernel
+    /// radius (delta q)
+    pub artificial_pressure_distance: f32,
+    /// XSPH viscosity, from 0 to 1 blending the velocity of every particle with its neighbours'
+    pub viscosity: f32,
+    /// Strength of the vorticity confinement restoring the swirls damped by the solver
+    pub vorticity: f32,
+}
+
+impl Default for FluidParams {
+    fn default() -> Self {
+        Self {
+            rest_density: 1000.,
+            kernel_radius: 0.1,
+            relaxation: 10.,
+            artificial_pressure: 1e-4,
+            artificial_pressure_exponent: 4,
+            artificial_pressure_distance: 0.2,
+            viscosity: 0.01,
+            vorticity: 0.,
+        }
+    }
+}
+
+impl FluidParams {
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        for (name, value) in [
+            ("rest density", self.rest_density),
+            ("kernel radius", self.kernel_radius),
+        ] {
+            if !(value > 0. && value.is_finite()) {
+                return Err(Error::InvalidParameter { name, value });
+            }
+        }
+        for (name, value) in [
+            ("relaxation", self.relaxation),
+            ("artificial pressure", self.artificial_pressure),
+            (
+                "artificial pressure distance",
+                self.artificial_pressure_distance,
+            ),
+            ("viscosity", self.viscosity),
+            ("vorticity", self.vorticity),
+        ] {
+            non_negative(name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fluid particles, which interact with every other particle closer than the kernel radius
+#[derive(Clone, Default)]
+pub struct Fluid {
+    pub particles: Vec<Particle>,
+    pub params: FluidParams,
+}
+
+impl Fluid {
+    /// Fluid filling the box from `min` to `max` with particles `spacing` apart, each with
+    /// the mass of its share of the box at the rest density
+    pub fn block(min: Vec3, max: Vec3, spacing: f32, params: FluidParams) -> Result<Self, Error> {
+        params.validate()?;
+        if !(spacing > 0. && spacing.is_finite()) {
+            return Err(Error::InvalidParameter {
+                name: "spacing",
+                value: spacing,
+            });
+        }
+        let counts = ((max - min) / spacing).floor().as_uvec3() + 1;
+        let inv_mass = 1. / (params.rest_density * spacing.powi(3));
+        let mut particles = Vec::new();
+        for z in 0..counts.z {
+            for y in 0..counts.y {
+                for x in 0..counts.x {
+                    let offset = Vec3::new(x as f32, y as f32, z as f32) * spacing;
+                    particles.push(Particle::new(min + offset, inv_mass));
+                }
+            }
+        }
+        Ok(Self { particles, params })
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        self.params.validate()
+    }
+}
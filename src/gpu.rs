@@ -1,145 +1,1055 @@
 use std::{
     mem,
-    sync::{Arc, Mutex},
+    ops::{Range, RangeBounds},
+    sync::{Arc, Mutex, PoisonError},
 };
 
 use bytemuck::{Pod, Zeroable};
 use encase::{private::WriteInto, ShaderSize, ShaderType, StorageBuffer};
+use glam::{Vec3, Vec4};
 use wgpu::{
     util::{DeviceExt, DownloadBuffer},
-    Buffer, BufferUsages, CommandEncoder, Device, Queue,
+    Buffer, BufferUsages, CommandEncoder, Device, ErrorFilter, Queue,
 };
 
 use crate::{
+    fiber,
     gpu::{distance_solver::DistanceSolver, tet_solver::TetSolver},
-    DistanceC, Particle, TetrahedralVolumeC,
+    mass,
+    morph::{self, RestMorph},
+    AeroTriangle, Body, BodyHandle, DistanceC, Error, FiberC, IndexError, Particle, ParticleHandle,
+    Plane, ShapeMatchingC, SurfaceVolumeC, TetherC, TetrahedralVolumeC, TriangleStrainC,
+    Turbulence, Wind, WorldParams,
 };
 
-use self::{add_deltas::AddDeltas, postsolve::Postsolve, presolve::Presolve};
+use self::{
+    add_deltas::AddDeltas,
+    aero_solver::AeroSolver,
+    buffer::GrowableBuffer,
+    fiber_solver::FiberSolver,
+    postsolve::Postsolve,
+    presolve::Presolve,
+    shape_matching_solver::{flatten_clusters, ShapeCluster, ShapeMatchingSolver, ShapeParticle},
+    strain_solver::StrainSolver,
+    surface_volume_solver::{flatten_surfaces, SurfaceCorner, SurfaceVolume, SurfaceVolumeSolver},
+    tether_solver::TetherSolver,
+};
 
 mod add_deltas;
+mod aero_solver;
+mod buffer;
 mod distance_solver;
+mod fiber_solver;
+mod lambdas;
 mod postsolve;
 mod presolve;
 mod shaders;
+mod shape_matching_solver;
+mod strain_solver;
+mod surface_volume_solver;
 mod tet_solver;
+mod tether_solver;
 
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 struct SimParams {
+    gravity: [f32; 3],
     delta: f32,
+    ground_normal: [f32; 3],
+    ground_offset: f32,
     jacobi_w: f32,
+    damping: f32,
+    has_ground: u32,
+    air_density: f32,
+    wind_velocity: [f32; 3],
+    wind_mode: u32,
+    wind_amplitude: f32,
+    wind_scale: f32,
+    wind_frequency: f32,
+    wind_seed: u32,
+    time: f32,
+    /// Iteration within the substep, the constraints start their multipliers over on the first
+    iteration: u32,
+    _padding: [u32; 2],
+}
+
+impl SimParams {
+    fn new(params: &WorldParams, delta: f32, wind: &Wind, time: f32) -> Self {
+        let ground = params.ground.unwrap_or(Plane::new(Vec3::Z, 0.));
+        let (wind_mode, turbulence) = match wind {
+            Wind::Constant(velocity) => (
+                0,
+                Turbulence {
+                    velocity: *velocity,
+                    ..Default::default()
+                },
+            ),
+            Wind::Turbulent(turbulence) => (1, *turbulence),
+            Wind::Field(_) => (2, Default::default()),
+        };
+        Self {
+            gravity: params.gravity.into(),
+            delta,
+            ground_normal: ground.normal.into(),
+            ground_offset: ground.offset,
+            jacobi_w: params.jacobi_weight,
+            damping: params.damping,
+            has_ground: params.ground.is_some() as u32,
+            air_density: params.air_density,
+            wind_velocity: turbulence.velocity.into(),
+            wind_mode,
+            wind_amplitude: turbulence.amplitude,
+            wind_scale: turbulence.scale,
+            wind_frequency: turbulence.frequency,
+            wind_seed: turbulence.seed,
+            time,
+            iteration: 0,
+            _padding: [0; 2],
+        }
+    }
+}
+
+/// Where a body lives in the particle buffer. Its constraints are kept with local indices so
+/// the constraint buffers can be rebuilt when bodies before it are removed.
+struct GpuBody {
+    particles: Range<u32>,
+    constraints: Body,
+    slots: ConstraintSlots,
+    /// Rest shape morph in progress, written to the buffers on the next step
+    morph: Option<RestMorph>,
+}
+
+/// Where the constraints of a body with rest values are in their buffers, one range per batch
+/// of constraints added, so they can be updated in place
+#[derive(Default)]
+struct ConstraintSlots {
+    distance: Vec<Range<u64>>,
+    tet: Vec<Range<u64>>,
+    fiber: Vec<Range<u64>>,
+    strain: Vec<Range<u64>>,
+    shape_clusters: Vec<Range<u64>>,
+    shape_particles: Vec<Range<u64>>,
+    surfaces: Vec<Range<u64>>,
+    surface_corners: Vec<Range<u64>>,
+    aero: Vec<Range<u64>>,
+}
+
+impl ConstraintSlots {
+    fn extend(&mut self, other: ConstraintSlots) {
+        self.distance.extend(other.distance);
+        self.tet.extend(other.tet);
+        self.fiber.extend(other.fiber);
+        self.strain.extend(other.strain);
+        self.shape_clusters.extend(other.shape_clusters);
+        self.shape_particles.extend(other.shape_particles);
+        self.surfaces.extend(other.surfaces);
+        self.surface_corners.extend(other.surface_corners);
+        self.aero.extend(other.aero);
+    }
+}
+
+/// Result of the last particle readback, written from the map callback
+#[derive(Default)]
+struct Download {
+    particles: Vec<Particle>,
+    inverted_tets: u32,
+    error: Option<Error>,
 }
 
 pub struct GpuSimulation {
     presolve: Presolve,
     distance_solver: DistanceSolver,
     tet_solver: TetSolver,
+    shape_matching_solver: ShapeMatchingSolver,
+    fiber_solver: FiberSolver,
+    strain_solver: StrainSolver,
+    surface_volume_solver: SurfaceVolumeSolver,
+    tether_solver: TetherSolver,
+    aero_solver: AeroSolver,
     add_deltas_dist: AddDeltas,
     add_deltas_tet: AddDeltas,
+    add_deltas_shape: AddDeltas,
+    add_deltas_fiber: AddDeltas,
+    add_deltas_strain: AddDeltas,
+    add_deltas_surface_volume: AddDeltas,
+    add_deltas_tether: AddDeltas,
     postsolve: Postsolve,
-    particles: Buffer,
-    distance_constraints: Buffer,
-    tet_constraints: Buffer,
+    particles: GrowableBuffer<Particle>,
+    distance_constraints: GrowableBuffer<DistanceC>,
+    tet_constraints: GrowableBuffer<TetrahedralVolumeC>,
+    shape_clusters: GrowableBuffer<ShapeCluster>,
+    shape_particles: GrowableBuffer<ShapeParticle>,
+    fiber_constraints: GrowableBuffer<FiberC>,
+    strain_constraints: GrowableBuffer<TriangleStrainC>,
+    surfaces: GrowableBuffer<SurfaceVolume>,
+    surface_corners: GrowableBuffer<SurfaceCorner>,
+    tether_constraints: GrowableBuffer<TetherC>,
+    aero_triangles: GrowableBuffer<AeroTriangle>,
+    /// Wind at the center of each aerodynamic triangle for [`Wind::Field`], padded to the
+    /// array stride of `vec3f`
+    aero_winds: GrowableBuffer<Vec4>,
+    bodies: Vec<Option<GpuBody>>,
     sim_params: Buffer,
-    downloaded_particles: Arc<Mutex<Vec<Particle>>>,
+    /// The params of every pass of a step, copied to `sim_params` before the pass
+    pass_params: Buffer,
+    /// Generations of the buffers the bind groups were last built with, see
+    /// [`GrowableBuffer::generation`]
+    bound_generations: Option<[u64; 12]>,
+    params: WorldParams,
+    wind: Wind,
+    /// Simulated time, at which the wind is sampled
+    time: f64,
+    downloaded: Arc<Mutex<Download>>,
 }
 
 impl GpuSimulation {
-    pub fn new(
-        device: &Device,
-        particles: &[Particle],
-        distance_constraints: &[DistanceC],
-        tet_constraints: &[TetrahedralVolumeC],
-    ) -> Self {
-        pub fn create_buffer<T: ShaderType + WriteInto + ShaderSize>(
-            device: &Device,
-            els: &[T],
-            usage: BufferUsages,
-            label: &str,
-        ) -> Buffer {
-            let mut buffer = StorageBuffer::new(Vec::new());
-            buffer.write(&els).unwrap();
-            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(label),
-                contents: &buffer.into_inner(),
-                usage,
-            })
-        }
+    pub async fn new(device: &Device, params: WorldParams) -> Result<Self, Error> {
+        params.validate()?;
 
-        let distance_solver = DistanceSolver::new(device, particles.len() as u64);
+        device.push_error_scope(ErrorFilter::OutOfMemory);
+        device.push_error_scope(ErrorFilter::Validation);
 
-        let tet_solver = TetSolver::new(device, particles.len() as u64);
+        let distance_solver = DistanceSolver::new(device);
 
-        let particles = create_buffer(
-            device,
-            particles,
-            BufferUsages::COPY_SRC | BufferUsages::COPY_DST | BufferUsages::STORAGE,
-            "Particles",
-        );
+        let tet_solver = TetSolver::new(device);
+
+        let shape_matching_solver = ShapeMatchingSolver::new(device);
+
+        let fiber_solver = FiberSolver::new(device);
 
-        let distance_constraints = create_buffer(
+        let strain_solver = StrainSolver::new(device);
+
+        let surface_volume_solver = SurfaceVolumeSolver::new(device);
+
+        let tether_solver = TetherSolver::new(device);
+
+        let aero_solver = AeroSolver::new(device);
+
+        let particles = GrowableBuffer::new(device, "Particles", BufferUsages::STORAGE);
+
+        let distance_constraints =
+            GrowableBuffer::new(device, "Distance constraints", BufferUsages::STORAGE);
+
+        let tet_constraints = GrowableBuffer::new(
             device,
-            distance_constraints,
-            BufferUsages::COPY_DST | BufferUsages::STORAGE,
-            "Distance constraints",
+            "Tetrahedral volume constraints",
+            BufferUsages::STORAGE,
         );
 
-        let tet_constraints = create_buffer(
+        let shape_clusters =
+            GrowableBuffer::new(device, "Shape matching clusters", BufferUsages::STORAGE);
+        let shape_particles = GrowableBuffer::new(
             device,
-            tet_constraints,
-            BufferUsages::COPY_DST | BufferUsages::STORAGE,
-            "Tetrahedral volume constraints",
+            "Shape matching cluster particles",
+            BufferUsages::STORAGE,
         );
 
+        let fiber_constraints =
+            GrowableBuffer::new(device, "Fiber constraints", BufferUsages::STORAGE);
+
+        let strain_constraints =
+            GrowableBuffer::new(device, "Triangle strain constraints", BufferUsages::STORAGE);
+
+        let surfaces =
+            GrowableBuffer::new(device, "Surface volume constraints", BufferUsages::STORAGE);
+        let surface_corners =
+            GrowableBuffer::new(device, "Surface triangle corners", BufferUsages::STORAGE);
+
+        let tether_constraints =
+            GrowableBuffer::new(device, "Tether constraints", BufferUsages::STORAGE);
+
+        let aero_triangles =
+            GrowableBuffer::new(device, "Aerodynamic triangles", BufferUsages::STORAGE);
+        let aero_winds =
+            GrowableBuffer::new(device, "Aerodynamic triangle winds", BufferUsages::STORAGE);
+
         let sim_params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Sim params"),
             contents: &[0u8; mem::size_of::<SimParams>()],
-            usage: BufferUsages::UNIFORM,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
+        let pass_params = Self::create_pass_params(device, 1);
 
         let presolve = Presolve::new(device);
 
         let add_deltas_dist = AddDeltas::new(device);
         let add_deltas_tet = AddDeltas::new(device);
+        let add_deltas_shape = AddDeltas::new(device);
+        let add_deltas_fiber = AddDeltas::new(device);
+        let add_deltas_strain = AddDeltas::new(device);
+        let add_deltas_surface_volume = AddDeltas::new(device);
+        let add_deltas_tether = AddDeltas::new(device);
 
         let postsolve = Postsolve::new(device);
 
-        Self {
+        let validation_error = device.pop_error_scope().await;
+        let out_of_memory_error = device.pop_error_scope().await;
+        if let Some(e) = validation_error.or(out_of_memory_error) {
+            return Err(e.into());
+        }
+
+        Ok(Self {
             presolve,
             distance_solver,
             tet_solver,
+            shape_matching_solver,
+            fiber_solver,
+            strain_solver,
+            surface_volume_solver,
+            tether_solver,
+            aero_solver,
             add_deltas_dist,
             add_deltas_tet,
+            add_deltas_shape,
+            add_deltas_fiber,
+            add_deltas_strain,
+            add_deltas_surface_volume,
+            add_deltas_tether,
             postsolve,
             particles,
             distance_constraints,
             tet_constraints,
+            shape_clusters,
+            shape_particles,
+            fiber_constraints,
+            strain_constraints,
+            surfaces,
+            surface_corners,
+            tether_constraints,
+            aero_triangles,
+            aero_winds,
+            bodies: Vec::new(),
             sim_params,
-            downloaded_particles: Default::default(),
+            pass_params,
+            bound_generations: None,
+            params,
+            wind: Wind::default(),
+            time: 0.,
+            downloaded: Default::default(),
+        })
+    }
+
+    fn create_pass_params(device: &Device, passes: u64) -> Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pass sim params"),
+            size: passes * mem::size_of::<SimParams>() as u64,
+            usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn world_params(&self) -> &WorldParams {
+        &self.params
+    }
+
+    pub fn set_world_params(&mut self, params: WorldParams) -> Result<(), Error> {
+        params.validate()?;
+        self.params = params;
+        Ok(())
+    }
+
+    /// Uploads a body, growing the buffers if needed. Buffers may be reallocated, so this must
+    /// not be called between recording a step and submitting it.
+    pub fn add_body(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        mut body: Body,
+    ) -> Result<BodyHandle, Error> {
+        body.validate()?;
+
+        let offset = self.particles.len() as u32;
+        let slots = self.append_constraints(device, queue, &body.offset_constraints(offset))?;
+        self.particles.extend(device, queue, &body.particles)?;
+
+        let particles = offset..offset + body.particles.len() as u32;
+        body.particles = Vec::new();
+        self.bodies.push(Some(GpuBody {
+            particles,
+            constraints: body,
+            slots,
+            morph: None,
+        }));
+        Ok(BodyHandle(self.bodies.len() as u32 - 1))
+    }
+
+    /// Removes a body, moving the particles after it down, in the downloaded particles too
+    pub fn remove_body(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        handle: BodyHandle,
+    ) -> Result<(), Error> {
+        let body = self
+            .bodies
+            .get_mut(handle.0 as usize)
+            .and_then(Option::take)
+            .ok_or(IndexError::InvalidBody(handle))?;
+
+        let removed = body.particles;
+        self.particles
+            .remove(device, queue, removed.start as u64..removed.end as u64);
+
+        // Keep the downloaded particles of the other bodies at their new indices
+        let mut downloaded = self
+            .downloaded
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let downloaded_n = downloaded.particles.len();
+        downloaded.particles.drain(
+            (removed.start as usize).min(downloaded_n)..(removed.end as usize).min(downloaded_n),
+        );
+        drop(downloaded);
+
+        let removed_n = removed.end - removed.start;
+        self.bodies
+            .iter_mut()
+            .flatten()
+            .filter(|b| b.particles.start >= removed.end)
+            .for_each(|b| b.particles = b.particles.start - removed_n..b.particles.end - removed_n);
+
+        self.upload_constraints(device, queue)
+    }
+
+    /// Adds constraints between particles of an existing body, indexed locally to the body
+    pub fn add_distance_constraints(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        handle: BodyHandle,
+        constraints: Vec<DistanceC>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            device,
+            queue,
+            handle,
+            Body {
+                distance_constraints: constraints,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Adds constraints between particles of an existing body, indexed locally to the body
+    pub fn add_tet_constraints(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        handle: BodyHandle,
+        constraints: Vec<TetrahedralVolumeC>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            device,
+            queue,
+            handle,
+            Body {
+                tet_constraints: constraints,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Adds shape matching clusters of particles of an existing body, indexed locally to the body
+    pub fn add_shape_matching_constraints(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        handle: BodyHandle,
+        constraints: Vec<ShapeMatchingC>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            device,
+            queue,
+            handle,
+            Body {
+                shape_matching_constraints: constraints,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Adds triangle strain constraints between particles of an existing body, indexed locally
+    /// to the body
+    pub fn add_triangle_strain_constraints(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        handle: BodyHandle,
+        constraints: Vec<TriangleStrainC>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            device,
+            queue,
+            handle,
+            Body {
+                triangle_strain_constraints: constraints,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Adds enclosed volume constraints over surfaces of an existing body, indexed locally to
+    /// the body
+    pub fn add_surface_volume_constraints(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        handle: BodyHandle,
+        constraints: Vec<SurfaceVolumeC>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            device,
+            queue,
+            handle,
+            Body {
+                surface_volume_constraints: constraints,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Adds tethers between particles of an existing body, indexed locally to the body
+    pub fn add_tether_constraints(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        handle: BodyHandle,
+        constraints: Vec<TetherC>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            device,
+            queue,
+            handle,
+            Body {
+                tether_constraints: constraints,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Adds fiber constraints to tetrahedra of an existing body, indexed locally to the body
+    pub fn add_fiber_constraints(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        handle: BodyHandle,
+        constraints: Vec<FiberC>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            device,
+            queue,
+            handle,
+            Body {
+                fiber_constraints: constraints,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Adds aerodynamic triangles to an existing body, indexed locally to the body
+    pub fn add_aero_triangles(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        handle: BodyHandle,
+        triangles: Vec<AeroTriangle>,
+    ) -> Result<(), Error> {
+        self.add_constraints(
+            device,
+            queue,
+            handle,
+            Body {
+                aero_triangles: triangles,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn wind(&self) -> &Wind {
+        &self.wind
+    }
+
+    /// Sets the wind blowing on the aerodynamic triangles of every body
+    pub fn set_wind(&mut self, wind: Wind) -> Result<(), Error> {
+        wind.validate()?;
+        self.wind = wind;
+        Ok(())
+    }
+
+    /// Sets the activation of every fiber of the body, see [`FiberC::set_activation`]. Only the
+    /// fibers of the body are written to the buffer.
+    pub fn set_fiber_activation(
+        &mut self,
+        queue: &Queue,
+        handle: BodyHandle,
+        activation: f32,
+    ) -> Result<(), Error> {
+        let activation = fiber::validate_activation(activation)?;
+        let body = self
+            .bodies
+            .get_mut(handle.0 as usize)
+            .and_then(Option::as_mut)
+            .ok_or(IndexError::InvalidBody(handle))?;
+        let offset = body.particles.start;
+        update_body_constraints(
+            queue,
+            &self.fiber_constraints,
+            &body.slots.fiber,
+            &mut body.constraints.fiber_constraints,
+            ..,
+            |c| {
+                c.activation = activation;
+                Ok(())
+            },
+            |c| c.offset(offset),
+        )
+    }
+
+    /// Updates the distance constraints of the body in `range`, indexed locally to the body in
+    /// the order they were added. Nothing is changed if `update` fails for any of them, and only
+    /// the updated constraints are written to the buffer.
+    pub fn update_distance_constraints(
+        &mut self,
+        queue: &Queue,
+        handle: BodyHandle,
+        range: impl RangeBounds<u32>,
+        update: impl FnMut(&mut DistanceC) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let body = self
+            .bodies
+            .get_mut(handle.0 as usize)
+            .and_then(Option::as_mut)
+            .ok_or(IndexError::InvalidBody(handle))?;
+        let offset = body.particles.start;
+        update_body_constraints(
+            queue,
+            &self.distance_constraints,
+            &body.slots.distance,
+            &mut body.constraints.distance_constraints,
+            range,
+            update,
+            |c| c.offset(offset),
+        )
+    }
+
+    /// Updates the tetrahedral volume constraints of the body in `range`, indexed locally to the
+    /// body in the order they were added. Nothing is changed if `update` fails for any of them,
+    /// and only the updated constraints are written to the buffer.
+    pub fn update_tet_constraints(
+        &mut self,
+        queue: &Queue,
+        handle: BodyHandle,
+        range: impl RangeBounds<u32>,
+        update: impl FnMut(&mut TetrahedralVolumeC) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let body = self
+            .bodies
+            .get_mut(handle.0 as usize)
+            .and_then(Option::as_mut)
+            .ok_or(IndexError::InvalidBody(handle))?;
+        let offset = body.particles.start;
+        update_body_constraints(
+            queue,
+            &self.tet_constraints,
+            &body.slots.tet,
+            &mut body.constraints.tet_constraints,
+            range,
+            update,
+            |c| c.offset(offset),
+        )
+    }
+
+    /// Moves the rest shape of the body to `positions`, one per particle, see
+    /// [`Body::set_rest_shape`]. The buffers are updated by the next step.
+    pub fn set_rest_shape(&mut self, handle: BodyHandle, positions: &[Vec3]) -> Result<(), Error> {
+        self.morph_rest_shape(handle, positions, 0.)
+    }
+
+    /// Blends the rest values of the constraints of the body from their current values to those
+    /// of `positions` over `duration` seconds of simulated time, see [`Body::set_rest_shape`].
+    /// Replaces any morph of the body in progress.
+    pub fn morph_rest_shape(
+        &mut self,
+        handle: BodyHandle,
+        positions: &[Vec3],
+        duration: f32,
+    ) -> Result<(), Error> {
+        let body = self
+            .bodies
+            .get_mut(handle.0 as usize)
+            .and_then(Option::as_mut)
+            .ok_or(IndexError::InvalidBody(handle))?;
+        morph::validate_rest_positions(positions, body.particles.len())?;
+        let to = body.constraints.rest_shape_constraints(positions)?;
+        let mut morph = RestMorph::new(body.constraints.clone(), to, duration)?;
+        morph.advance(0., &mut body.constraints);
+        body.morph = Some(morph);
+        Ok(())
+    }
+
+    /// Advances the rest shape morphs by `delta` and records writes of the blended rest values,
+    /// dropping the finished morphs. Errors are returned by [`Self::download_particles`].
+    fn advance_rest_morphs(&mut self, device: &Device, encoder: &mut CommandEncoder, delta: f32) {
+        let mut morphed = Vec::new();
+        for (i, body) in self.bodies.iter_mut().enumerate() {
+            let Some(body) = body else {
+                continue;
+            };
+            let Some(morph) = &mut body.morph else {
+                continue;
+            };
+            if morph.advance(delta, &mut body.constraints) {
+                body.morph = None;
+            }
+            morphed.push(i);
+        }
+        for body in morphed.into_iter().filter_map(|i| self.bodies[i].as_ref()) {
+            if let Err(e) = self.record_rest_values(device, encoder, body) {
+                self.downloaded
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .error = Some(e);
+            }
         }
     }
 
+    /// Records writes of the rest values of the constraints of the body to their slots
+    fn record_rest_values(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        body: &GpuBody,
+    ) -> Result<(), Error> {
+        let constraints = body.constraints.offset_constraints(body.particles.start);
+        let slots = &body.slots;
+        for (slot, batch) in batches(&slots.distance, &constraints.distance_constraints) {
+            self.distance_constraints
+                .record_write(device, encoder, slot.start, batch)?;
+        }
+        for (slot, batch) in batches(&slots.tet, &constraints.tet_constraints) {
+            self.tet_constraints
+                .record_write(device, encoder, slot.start, batch)?;
+        }
+        for (slot, batch) in batches(&slots.fiber, &constraints.fiber_constraints) {
+            self.fiber_constraints
+                .record_write(device, encoder, slot.start, batch)?;
+        }
+        for (slot, batch) in batches(&slots.strain, &constraints.triangle_strain_constraints) {
+            self.strain_constraints
+                .record_write(device, encoder, slot.start, batch)?;
+        }
+        let shape_batches = batches(
+            &slots.shape_clusters,
+            &constraints.shape_matching_constraints,
+        );
+        for ((slot, batch), particles_slot) in shape_batches.zip(&slots.shape_particles) {
+            let (clusters, particles) = flatten_clusters(batch, particles_slot.start as u32);
+            self.shape_clusters
+                .record_write(device, encoder, slot.start, &clusters)?;
+            self.shape_particles
+                .record_write(device, encoder, particles_slot.start, &particles)?;
+        }
+        let surface_batches = batches(&slots.surfaces, &constraints.surface_volume_constraints);
+        for ((slot, batch), corners_slot) in surface_batches.zip(&slots.surface_corners) {
+            // The corners only depend on the triangles, which are unchanged
+            let (surfaces, _) = flatten_surfaces(batch, corners_slot.start as u32);
+            self.surfaces
+                .record_write(device, encoder, slot.start, &surfaces)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the constraints of `constraints`, indexed locally to the body
+    fn add_constraints(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        handle: BodyHandle,
+        constraints: Body,
+    ) -> Result<(), Error> {
+        let particles = self
+            .bodies
+            .get(handle.0 as usize)
+            .and_then(Option::as_ref)
+            .ok_or(IndexError::InvalidBody(handle))?
+            .particles
+            .clone();
+        constraints.validate_constraints(particles.len() as u32)?;
+        let slots = self.append_constraints(
+            device,
+            queue,
+            &constraints.offset_constraints(particles.start),
+        )?;
+        if let Some(Some(body)) = self.bodies.get_mut(handle.0 as usize) {
+            body.constraints.extend_constraints(constraints);
+            body.slots.extend(slots);
+        }
+        Ok(())
+    }
+
+    /// Appends constraints with global indices to the constraint buffers, returning where they
+    /// were placed
+    fn append_constraints(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        constraints: &Body,
+    ) -> Result<ConstraintSlots, Error> {
+        let mut slots = ConstraintSlots::default();
+        slots.distance.push(self.distance_constraints.extend(
+            device,
+            queue,
+            &constraints.distance_constraints,
+        )?);
+        slots.tet.push(
+            self.tet_constraints
+                .extend(device, queue, &constraints.tet_constraints)?,
+        );
+        slots.fiber.push(self.fiber_constraints.extend(
+            device,
+            queue,
+            &constraints.fiber_constraints,
+        )?);
+        slots.strain.push(self.strain_constraints.extend(
+            device,
+            queue,
+            &constraints.triangle_strain_constraints,
+        )?);
+
+        let (clusters, cluster_particles) = flatten_clusters(
+            &constraints.shape_matching_constraints,
+            self.shape_particles.len() as u32,
+        );
+        slots.shape_particles.push(self.shape_particles.extend(
+            device,
+            queue,
+            &cluster_particles,
+        )?);
+        slots
+            .shape_clusters
+            .push(self.shape_clusters.extend(device, queue, &clusters)?);
+
+        let (surfaces, corners) = flatten_surfaces(
+            &constraints.surface_volume_constraints,
+            self.surface_corners.len() as u32,
+        );
+        slots
+            .surface_corners
+            .push(self.surface_corners.extend(device, queue, &corners)?);
+        slots
+            .surfaces
+            .push(self.surfaces.extend(device, queue, &surfaces)?);
+
+        self.tether_constraints
+            .extend(device, queue, &constraints.tether_constraints)?;
+
+        slots.aero.push(
+            self.aero_triangles
+                .extend(device, queue, &constraints.aero_triangles)?,
+        );
+        let winds = vec![Vec4::ZERO; constraints.aero_triangles.len()];
+        self.aero_winds.extend(device, queue, &winds)?;
+        Ok(slots)
+    }
+
+    /// Rewrites the constraint buffers from the constraints of every body
+    fn upload_constraints(&mut self, device: &Device, queue: &Queue) -> Result<(), Error> {
+        self.distance_constraints.clear();
+        self.tet_constraints.clear();
+        self.fiber_constraints.clear();
+        self.strain_constraints.clear();
+        self.shape_particles.clear();
+        self.shape_clusters.clear();
+        self.surface_corners.clear();
+        self.surfaces.clear();
+        self.tether_constraints.clear();
+        self.aero_triangles.clear();
+        self.aero_winds.clear();
+
+        let mut bodies = mem::take(&mut self.bodies);
+        let result = bodies.iter_mut().flatten().try_for_each(|body| {
+            body.slots = self.append_constraints(
+                device,
+                queue,
+                &body.constraints.offset_constraints(body.particles.start),
+            )?;
+            Ok(())
+        });
+        self.bodies = bodies;
+        result
+    }
+
+    /// Range of the body's particles in the downloaded particles
+    pub fn body_particles(&self, handle: BodyHandle) -> Result<Range<u32>, Error> {
+        Ok(self
+            .bodies
+            .get(handle.0 as usize)
+            .and_then(Option::as_ref)
+            .map(|b| b.particles.clone())
+            .ok_or(IndexError::InvalidBody(handle))?)
+    }
+
+    /// Index of the particle in the downloaded particles
+    pub fn particle_index(&self, handle: ParticleHandle) -> Result<u32, Error> {
+        Ok(crate::particle_index(
+            self.body_particles(handle.body)?,
+            handle.index,
+        )?)
+    }
+
+    /// Records a step of `delta` seconds. Unlike [`crate::cpu::CpuSimulation::simulate`], the
+    /// step isn't checked for instability nor retried, see [`crate::StabilityParams`]. The
+    /// params of the step are written with `queue`, so it must be submitted before the next
+    /// step is recorded.
     pub fn simulate(
         &mut self,
         device: &Device,
+        queue: &Queue,
         encoder: &mut CommandEncoder,
-        substeps: u32,
         delta: f32,
     ) {
+        let particles_n = self.particles.len();
+        if particles_n == 0 {
+            return;
+        }
+
+        self.advance_rest_morphs(device, encoder, delta);
+        self.record_field_winds(device, encoder);
+
+        let substeps = self.params.substeps;
         let sub_delta = delta / substeps as f32;
 
-        let params = SimParams {
-            delta: sub_delta,
-            jacobi_w: 1.5,
-        };
+        let params = SimParams::new(&self.params, sub_delta, &self.wind, self.time as f32);
+        let iterations = self.params.iterations;
+        // The params of every pass, copied to the uniform before it. The wind is sampled at the
+        // start of each substep.
+        let pass_params: Vec<_> = (0..substeps)
+            .flat_map(|i| {
+                let time = (self.time + i as f64 * sub_delta as f64) as f32;
+                (0..iterations).map(move |iteration| SimParams {
+                    time,
+                    iteration,
+                    ..params
+                })
+            })
+            .collect();
+        let params_size = mem::size_of::<SimParams>() as u64;
+        if self.pass_params.size() < pass_params.len() as u64 * params_size {
+            self.pass_params = Self::create_pass_params(device, pass_params.len() as u64);
+        }
+        queue.write_buffer(&self.pass_params, 0, bytemuck::cast_slice(&pass_params));
 
-        self.sim_params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Sim params"),
-            contents: bytemuck::cast_slice(&[params]),
-            usage: BufferUsages::UNIFORM,
-        });
+        let capacity = self.particles.capacity();
+        let reallocated = [
+            self.distance_solver
+                .reserve(device, capacity, self.distance_constraints.len()),
+            self.tet_solver
+                .reserve(device, capacity, self.tet_constraints.len()),
+            self.shape_matching_solver.reserve(device, capacity),
+            self.fiber_solver
+                .reserve(device, capacity, self.fiber_constraints.len()),
+            self.strain_solver
+                .reserve(device, capacity, self.strain_constraints.len()),
+            self.surface_volume_solver.reserve(device, capacity),
+            self.tether_solver
+                .reserve(device, capacity, self.tether_constraints.len()),
+            self.aero_solver.reserve(device, capacity),
+        ];
+        let generations = self.generations();
+        if reallocated.contains(&true) || self.bound_generations != Some(generations) {
+            self.update_bind_groups(device);
+            self.bound_generations = Some(generations);
+        }
+
+        let distance_n = self.distance_constraints.len();
+        let tet_n = self.tet_constraints.len();
+        let clusters_n = self.shape_clusters.len();
+        let fibers_n = self.fiber_constraints.len();
+        let strain_n = self.strain_constraints.len();
+        let surfaces_n = self.surfaces.len();
+        let tethers_n = self.tether_constraints.len();
+        let aero_n = self.aero_triangles.len();
+        for i in 0..substeps {
+            for j in 0..iterations {
+                let pass = i as u64 * iterations as u64 + j as u64;
+                encoder.copy_buffer_to_buffer(
+                    &self.pass_params,
+                    pass * params_size,
+                    &self.sim_params,
+                    0,
+                    params_size,
+                );
+                // Results are cleared outside of the pass, so every iteration needs its own
+                self.distance_solver.prerun(encoder);
+                self.tet_solver.prerun(encoder);
+                self.shape_matching_solver.prerun(encoder);
+                self.fiber_solver.prerun(encoder);
+                self.strain_solver.prerun(encoder);
+                self.surface_volume_solver.prerun(encoder);
+                self.tether_solver.prerun(encoder);
+                if j == 0 {
+                    self.aero_solver.prerun(encoder);
+                    self.distance_solver.clear_lambdas(encoder);
+                    self.tet_solver.clear_lambdas(encoder);
+                    self.fiber_solver.clear_lambdas(encoder);
+                    self.strain_solver.clear_lambdas(encoder);
+                    self.tether_solver.clear_lambdas(encoder);
+                }
+                let cpass_name = format!("substep {i} iteration {j}");
+                let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some(&cpass_name),
+                });
+                if j == 0 {
+                    self.aero_solver.run(&mut cpass, aero_n, particles_n);
+                    self.presolve.run(&mut cpass, particles_n);
+                }
+                self.distance_solver.run(&mut cpass, distance_n);
+                self.tet_solver.run(&mut cpass, tet_n);
+                self.shape_matching_solver.run(&mut cpass, clusters_n);
+                self.add_deltas_dist.run(&mut cpass, particles_n);
+                self.add_deltas_tet.run(&mut cpass, particles_n);
+                self.add_deltas_shape.run(&mut cpass, particles_n);
+                // Fibers share their particles with the tetrahedra they reinforce
+                self.fiber_solver.run(&mut cpass, fibers_n);
+                self.add_deltas_fiber.run(&mut cpass, particles_n);
+                // Cloth bending constraints share particles with the strain and surface volume
+                // constraints, whose over-relaxed deltas would add up with theirs if solved on
+                // the same positions
+                self.strain_solver.run(&mut cpass, strain_n);
+                self.add_deltas_strain.run(&mut cpass, particles_n);
+                self.surface_volume_solver.run(&mut cpass, surfaces_n);
+                self.add_deltas_surface_volume.run(&mut cpass, particles_n);
+                // Tethers only limit the stretch left by the other constraints
+                self.tether_solver.run(&mut cpass, tethers_n);
+                self.add_deltas_tether.run(&mut cpass, particles_n);
+                if j == iterations - 1 {
+                    self.postsolve.run(&mut cpass, particles_n);
+                }
+            }
+        }
+        self.tet_solver.count_inverted(encoder, tet_n);
+        self.time += delta as f64;
+    }
+
+    /// Generations of the buffers bound by the solvers
+    fn generations(&self) -> [u64; 12] {
+        [
+            self.particles.generation(),
+            self.distance_constraints.generation(),
+            self.tet_constraints.generation(),
+            self.shape_clusters.generation(),
+            self.shape_particles.generation(),
+            self.fiber_constraints.generation(),
+            self.strain_constraints.generation(),
+            self.surfaces.generation(),
+            self.surface_corners.generation(),
+            self.tether_constraints.generation(),
+            self.aero_triangles.generation(),
+            self.aero_winds.generation(),
+        ]
+    }
 
+    fn update_bind_groups(&mut self, device: &Device) {
         self.presolve
             .update_bind_group(device, &self.sim_params, &self.particles);
         self.postsolve
@@ -156,6 +1066,45 @@ impl GpuSimulation {
             &self.particles,
             &self.tet_constraints,
         );
+        self.shape_matching_solver.update_bind_group(
+            device,
+            &self.sim_params,
+            &self.particles,
+            &self.shape_clusters,
+            &self.shape_particles,
+        );
+        self.fiber_solver.update_bind_group(
+            device,
+            &self.sim_params,
+            &self.particles,
+            &self.fiber_constraints,
+        );
+        self.strain_solver.update_bind_group(
+            device,
+            &self.sim_params,
+            &self.particles,
+            &self.strain_constraints,
+        );
+        self.surface_volume_solver.update_bind_group(
+            device,
+            &self.sim_params,
+            &self.particles,
+            &self.surfaces,
+            &self.surface_corners,
+        );
+        self.tether_solver.update_bind_group(
+            device,
+            &self.sim_params,
+            &self.particles,
+            &self.tether_constraints,
+        );
+        self.aero_solver.update_bind_group(
+            device,
+            &self.sim_params,
+            &self.particles,
+            &self.aero_triangles,
+            &self.aero_winds,
+        );
         self.add_deltas_dist.update_bind_group(
             device,
             &self.sim_params,
@@ -168,33 +1117,205 @@ impl GpuSimulation {
             &self.particles,
             self.tet_solver.results(),
         );
+        self.add_deltas_shape.update_bind_group(
+            device,
+            &self.sim_params,
+            &self.particles,
+            self.shape_matching_solver.results(),
+        );
+        self.add_deltas_fiber.update_bind_group(
+            device,
+            &self.sim_params,
+            &self.particles,
+            self.fiber_solver.results(),
+        );
+        self.add_deltas_strain.update_bind_group(
+            device,
+            &self.sim_params,
+            &self.particles,
+            self.strain_solver.results(),
+        );
+        self.add_deltas_surface_volume.update_bind_group(
+            device,
+            &self.sim_params,
+            &self.particles,
+            self.surface_volume_solver.results(),
+        );
+        self.add_deltas_tether.update_bind_group(
+            device,
+            &self.sim_params,
+            &self.particles,
+            self.tether_solver.results(),
+        );
+    }
 
-        for i in 0..substeps {
-            self.distance_solver.prerun(encoder);
-            self.tet_solver.prerun(encoder);
-            let cpass_name = format!("substep {i}");
-            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some(&cpass_name),
+    /// Records writes of the wind of a [`Wind::Field`] at the centers of the aerodynamic
+    /// triangles, from the last downloaded particles. Until particles were downloaded the wind
+    /// is left as it was. Errors are returned by [`Self::download_particles`].
+    fn record_field_winds(&self, device: &Device, encoder: &mut CommandEncoder) {
+        let Wind::Field(field) = &self.wind else {
+            return;
+        };
+        let mut downloaded = self
+            .downloaded
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if downloaded.particles.len() as u64 != self.particles.len() {
+            return;
+        }
+        let time = self.time as f32;
+        for body in self.bodies.iter().flatten() {
+            let triangles: Vec<_> = body
+                .constraints
+                .aero_triangles
+                .iter()
+                .map(|t| t.offset(body.particles.start))
+                .collect();
+            for (slot, batch) in batches(&body.slots.aero, &triangles) {
+                let winds: Vec<_> = batch
+                    .iter()
+                    .map(|t| {
+                        let center = t
+                            .particles_idx
+                            .iter()
+                            .map(|&i| downloaded.particles[i as usize].position)
+                            .sum::<Vec3>()
+                            / 3.;
+                        field(center, time).extend(0.)
+                    })
+                    .collect();
+                if let Err(e) = self
+                    .aero_winds
+                    .record_write(device, encoder, slot.start, &winds)
+                {
+                    downloaded.error = Some(e);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Mass of the body in the downloaded particles
+    pub fn body_mass(&self, handle: BodyHandle) -> Result<f32, Error> {
+        self.with_downloaded_body(handle, mass::total_mass)
+    }
+
+    /// Center of mass of the particles of the body that can move, as of the last download
+    pub fn body_center_of_mass(&self, handle: BodyHandle) -> Result<Option<Vec3>, Error> {
+        self.with_downloaded_body(handle, mass::center_of_mass)
+    }
+
+    fn with_downloaded_body<T>(
+        &self,
+        handle: BodyHandle,
+        f: impl FnOnce(&[Particle]) -> T,
+    ) -> Result<T, Error> {
+        let range = self.body_particles(handle)?;
+        let downloaded = self
+            .downloaded
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let particles = downloaded
+            .particles
+            .get(range.start as usize..range.end as usize)
+            .unwrap_or_default();
+        Ok(f(particles))
+    }
+
+    /// Tetrahedra with negative volume at the end of the last step, like
+    /// [`StepReport::inverted_tets`](crate::StepReport::inverted_tets), read back together with
+    /// the particles by [`Self::download_particles`]
+    pub fn inverted_tets(&self) -> u32 {
+        self.downloaded
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .inverted_tets
+    }
+
+    /// Starts reading the particles back, returning the ones read by the previous call once
+    /// the device has been polled. Errors of the previous readback and steps are returned once.
+    pub fn download_particles(
+        &self,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<Vec<Particle>, Error> {
+        if self.particles.len() > 0 {
+            let downloaded = self.downloaded.clone();
+            let particles = self.particles.buffer().slice(..self.particles.size());
+            DownloadBuffer::read_buffer(device, queue, &particles, move |buff| {
+                let mut downloaded = downloaded.lock().unwrap_or_else(PoisonError::into_inner);
+                let result = buff.map_err(Error::from).and_then(|buff| {
+                    let buffer = StorageBuffer::new(&buff[..]);
+                    Ok(buffer.read(&mut downloaded.particles)?)
+                });
+                if let Err(e) = result {
+                    downloaded.error = Some(e);
+                }
+            });
+
+            let downloaded = self.downloaded.clone();
+            let inverted = self.tet_solver.inverted().slice(..);
+            DownloadBuffer::read_buffer(device, queue, &inverted, move |buff| {
+                let mut downloaded = downloaded.lock().unwrap_or_else(PoisonError::into_inner);
+                match buff {
+                    Ok(buff) => downloaded.inverted_tets = bytemuck::pod_read_unaligned(&buff),
+                    Err(e) => downloaded.error = Some(e.into()),
+                }
             });
-            self.presolve.run(&mut cpass, &self.particles);
-            self.distance_solver
-                .run(&mut cpass, &self.distance_constraints);
-            self.tet_solver.run(&mut cpass, &self.tet_constraints);
-            self.add_deltas_dist.run(&mut cpass, &self.particles);
-            self.add_deltas_tet.run(&mut cpass, &self.particles);
-            self.postsolve.run(&mut cpass, &self.particles);
+        }
+
+        let mut downloaded = self
+            .downloaded
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        match downloaded.error.take() {
+            Some(e) => Err(e),
+            None if self.particles.len() == 0 => Ok(Vec::new()),
+            None => Ok(downloaded.particles.clone()),
         }
     }
+}
 
-    pub fn download_particles(&self, device: &Device, queue: &Queue) -> Vec<Particle> {
-        let downloaded_particles = self.downloaded_particles.clone();
-        DownloadBuffer::read_buffer(device, queue, &self.particles.slice(..), move |buff| {
-            let buff = buff.unwrap();
-            let buffer = StorageBuffer::new(&buff[..]);
-            let mut particles = downloaded_particles.lock().unwrap();
-            buffer.read(&mut *particles).unwrap();
-        });
+/// Splits `constraints` into the batches placed at `slots`
+fn batches<'a, T>(
+    slots: &'a [Range<u64>],
+    mut constraints: &'a [T],
+) -> impl Iterator<Item = (&'a Range<u64>, &'a [T])> {
+    slots.iter().map(move |slot| {
+        let (batch, rest) = constraints.split_at((slot.end - slot.start) as usize);
+        constraints = rest;
+        (slot, batch)
+    })
+}
 
-        self.downloaded_particles.lock().unwrap().clone()
+/// Applies `update` to copies of the local `constraints` of a body in `range`, and once every
+/// update succeeded keeps them and writes them with global indices to their `slots` of `buffer`
+fn update_body_constraints<T: Copy + ShaderType + ShaderSize + WriteInto>(
+    queue: &Queue,
+    buffer: &GrowableBuffer<T>,
+    slots: &[Range<u64>],
+    constraints: &mut [T],
+    range: impl RangeBounds<u32>,
+    mut update: impl FnMut(&mut T) -> Result<(), Error>,
+    offset: impl Fn(&T) -> T,
+) -> Result<(), Error> {
+    let range = crate::constraint_range(range, constraints.len() as u32)?;
+    let mut updated = constraints[range.clone()].to_vec();
+    for c in &mut updated {
+        update(c)?;
+    }
+    constraints[range.clone()].copy_from_slice(&updated);
+
+    let mut local = 0;
+    for slot in slots {
+        let len = (slot.end - slot.start) as usize;
+        let start = range.start.max(local);
+        let end = range.end.min(local + len);
+        if start < end {
+            let els: Vec<_> = constraints[start..end].iter().map(&offset).collect();
+            buffer.write(queue, slot.start + (start - local) as u64, &els)?;
+        }
+        local += len;
     }
+    Ok(())
 }
@@ -0,0 +1,162 @@
+use encase::CalculateSizeFor;
+use glam::Vec4;
+use wgpu::{
+    BindGroup, Buffer, BufferDescriptor, BufferUsages, CommandEncoder, ComputePass,
+    ComputePipeline, Device,
+};
+
+use crate::{AeroTriangle, Particle};
+
+use super::{buffer::GrowableBuffer, shaders::BufferDesc};
+
+/// Computes the aerodynamic forces on the triangles as velocity changes of their particles, and
+/// adds them up into the particle velocities
+pub struct AeroSolver {
+    pipeline: ComputePipeline,
+    add_pipeline: ComputePipeline,
+    bind_group: Option<BindGroup>,
+    add_bind_group: Option<BindGroup>,
+    results: Buffer,
+}
+
+impl AeroSolver {
+    pub fn new(device: &Device) -> Self {
+        let pipeline = super::shaders::create_pipeline(
+            device,
+            "aero_solver",
+            [
+                BufferDesc { read_only: true },
+                BufferDesc { read_only: true },
+                BufferDesc { read_only: true },
+                BufferDesc { read_only: false },
+            ]
+            .into_iter(),
+            super::shaders::COMMON_SRC.to_string() + super::shaders::AERO_SRC,
+        );
+        let add_pipeline = super::shaders::create_pipeline(
+            device,
+            "add_aero",
+            [
+                BufferDesc { read_only: false },
+                BufferDesc { read_only: true },
+            ]
+            .into_iter(),
+            super::shaders::COMMON_SRC.to_string() + super::shaders::ADD_AERO_SRC,
+        );
+
+        let results = Self::create_results(device, 1);
+
+        Self {
+            pipeline,
+            add_pipeline,
+            bind_group: None,
+            add_bind_group: None,
+            results,
+        }
+    }
+
+    fn create_results(device: &Device, particles_n: u64) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Aerodynamic triangles results"),
+            size: Vec::<crate::ParticleConstraintDeltas>::calculate_size_for(particles_n).into(),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Makes sure there is room for the results of `particles_n` particles,
+    /// returning whether a buffer was reallocated, after which the bind group must be updated
+    pub fn reserve(&mut self, device: &Device, particles_n: u64) -> bool {
+        let size: u64 =
+            Vec::<crate::ParticleConstraintDeltas>::calculate_size_for(particles_n).into();
+        let reallocate = self.results.size() < size;
+        if reallocate {
+            self.results = Self::create_results(device, particles_n);
+        }
+        reallocate
+    }
+
+    pub fn update_bind_group(
+        &mut self,
+        device: &Device,
+        sim_params: &Buffer,
+        particles: &GrowableBuffer<Particle>,
+        triangles: &GrowableBuffer<AeroTriangle>,
+        winds: &GrowableBuffer<Vec4>,
+    ) {
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: sim_params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particles.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: triangles.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: winds.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.results.as_entire_binding(),
+                },
+            ],
+        }));
+        self.add_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.add_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: sim_params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particles.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.results.as_entire_binding(),
+                },
+            ],
+        }));
+    }
+
+    pub fn prerun(&self, encoder: &mut CommandEncoder) {
+        encoder.clear_buffer(&self.results, 0, None);
+    }
+
+    pub fn run<'a: 'b, 'b>(
+        &'a self,
+        compute_pass: &'b mut ComputePass<'a>,
+        triangles_n: u64,
+        particles_n: u64,
+    ) {
+        const WORKGROUP_SIZE: u64 = 64;
+        let (Some(bind_group), Some(add_bind_group)) = (&self.bind_group, &self.add_bind_group)
+        else {
+            return;
+        };
+        if triangles_n == 0 {
+            return;
+        }
+        let work_groups = ((triangles_n / WORKGROUP_SIZE) + 1) as u32;
+        let particle_work_groups = ((particles_n / WORKGROUP_SIZE) + 1) as u32;
+
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, bind_group, &[]);
+        compute_pass.dispatch_workgroups(work_groups, 1, 1);
+
+        compute_pass.set_pipeline(&self.add_pipeline);
+        compute_pass.set_bind_group(0, add_bind_group, &[]);
+        compute_pass.dispatch_workgroups(particle_work_groups, 1, 1);
+    }
+}
@@ -0,0 +1,203 @@
+use std::{marker::PhantomData, ops::Range};
+
+use encase::{private::WriteInto, ShaderSize, ShaderType, StorageBuffer};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindingResource, Buffer, BufferBinding, BufferDescriptor, BufferSize, BufferUsages,
+    CommandEncoder, Device, Queue,
+};
+
+use crate::Error;
+
+const MIN_CAPACITY: u64 = 64;
+
+/// Storage buffer holding `len` elements of `T`, reallocated with twice the capacity when full.
+/// Only the first `len` elements are bound, so `arrayLength` in the shaders is `len`.
+pub struct GrowableBuffer<T> {
+    buffer: Buffer,
+    len: u64,
+    capacity: u64,
+    usage: BufferUsages,
+    label: &'static str,
+    /// Incremented whenever the binding changes, when the buffer is reallocated or `len`
+    /// changes, so bind groups are only rebuilt then
+    generation: u64,
+    _marker: PhantomData<T>,
+}
+
+impl<T: ShaderType + ShaderSize + WriteInto> GrowableBuffer<T> {
+    const STRIDE: u64 = T::SHADER_SIZE.get();
+
+    pub fn new(device: &Device, label: &'static str, usage: BufferUsages) -> Self {
+        let usage = usage | BufferUsages::COPY_SRC | BufferUsages::COPY_DST;
+        Self {
+            buffer: Self::create_buffer(device, label, usage, MIN_CAPACITY),
+            len: 0,
+            capacity: MIN_CAPACITY,
+            usage,
+            label,
+            generation: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    fn create_buffer(device: &Device, label: &str, usage: BufferUsages, capacity: u64) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some(label),
+            size: capacity * Self::STRIDE,
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Size in bytes of the elements in use
+    pub fn size(&self) -> u64 {
+        self.len * Self::STRIDE
+    }
+
+    /// Binds the elements in use. Empty buffers bind their first element, which must not be
+    /// read by the shaders.
+    pub fn binding(&self) -> BindingResource<'_> {
+        BindingResource::Buffer(BufferBinding {
+            buffer: &self.buffer,
+            offset: 0,
+            size: BufferSize::new(self.len.max(1) * Self::STRIDE),
+        })
+    }
+
+    /// Grows the buffer so it can hold `additional` more elements, keeping the current ones
+    pub fn reserve(&mut self, device: &Device, queue: &Queue, additional: u64) {
+        let required = self.len + additional;
+        if required <= self.capacity {
+            return;
+        }
+
+        let capacity = required.next_power_of_two().max(MIN_CAPACITY);
+        let buffer = Self::create_buffer(device, self.label, self.usage, capacity);
+
+        if self.len > 0 {
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Grow buffer"),
+            });
+            encoder.copy_buffer_to_buffer(&self.buffer, 0, &buffer, 0, self.size());
+            queue.submit(Some(encoder.finish()));
+        }
+
+        self.buffer = buffer;
+        self.capacity = capacity;
+        self.generation += 1;
+    }
+
+    /// Appends `els`, returning the range of indices they were placed at
+    pub fn extend(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        els: &[T],
+    ) -> Result<Range<u64>, Error> {
+        self.reserve(device, queue, els.len() as u64);
+        let start = self.len;
+        self.len += els.len() as u64;
+        if let Err(e) = self.write(queue, start, els) {
+            self.len = start;
+            return Err(e);
+        }
+        if !els.is_empty() {
+            self.generation += 1;
+        }
+        Ok(start..self.len)
+    }
+
+    /// Overwrites the elements starting at `offset`, which must already be in use
+    pub fn write(&self, queue: &Queue, offset: u64, els: &[T]) -> Result<(), Error> {
+        debug_assert!(offset + els.len() as u64 <= self.len);
+        if els.is_empty() {
+            return Ok(());
+        }
+        let mut buffer = StorageBuffer::new(Vec::new());
+        buffer.write(&els)?;
+        queue.write_buffer(&self.buffer, offset * Self::STRIDE, &buffer.into_inner());
+        Ok(())
+    }
+
+    /// Records a copy of `els` over the elements starting at `offset`, which must already be in
+    /// use, so it is ordered with the other commands of `encoder`
+    pub fn record_write(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        offset: u64,
+        els: &[T],
+    ) -> Result<(), Error> {
+        debug_assert!(offset + els.len() as u64 <= self.len);
+        if els.is_empty() {
+            return Ok(());
+        }
+        let mut buffer = StorageBuffer::new(Vec::new());
+        buffer.write(&els)?;
+        let contents = buffer.into_inner();
+        let staging = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Staging buffer"),
+            contents: &contents,
+            usage: BufferUsages::COPY_SRC,
+        });
+        encoder.copy_buffer_to_buffer(
+            &staging,
+            0,
+            &self.buffer,
+            offset * Self::STRIDE,
+            contents.len() as u64,
+        );
+        Ok(())
+    }
+
+    /// Removes every element, keeping the capacity
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.generation += 1;
+    }
+
+    /// Removes the elements in `range`, moving the following ones down to fill the gap
+    pub fn remove(&mut self, device: &Device, queue: &Queue, range: Range<u64>) {
+        debug_assert!(range.end <= self.len);
+        // Copies inside of a single buffer are not allowed, so compact into a new one
+        let buffer = Self::create_buffer(device, self.label, self.usage, self.capacity);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Compact buffer"),
+        });
+        if range.start > 0 {
+            encoder.copy_buffer_to_buffer(&self.buffer, 0, &buffer, 0, range.start * Self::STRIDE);
+        }
+        if range.end < self.len {
+            encoder.copy_buffer_to_buffer(
+                &self.buffer,
+                range.end * Self::STRIDE,
+                &buffer,
+                range.start * Self::STRIDE,
+                (self.len - range.end) * Self::STRIDE,
+            );
+        }
+        queue.submit(Some(encoder.finish()));
+
+        self.buffer = buffer;
+        self.len -= range.end - range.start;
+        self.generation += 1;
+    }
+}
@@ -1,23 +1,22 @@
-use std::mem;
-
 use encase::CalculateSizeFor;
 use wgpu::{
     BindGroup, Buffer, BufferDescriptor, BufferUsages, CommandEncoder, ComputePass,
     ComputePipeline, Device,
 };
 
-use crate::DistanceC;
+use crate::{DistanceC, Particle};
 
-use super::shaders::BufferDesc;
+use super::{buffer::GrowableBuffer, lambdas::Lambdas, shaders::BufferDesc};
 
 pub struct DistanceSolver {
     pipeline: ComputePipeline,
     bind_group: Option<BindGroup>,
     distance_constraints_res: Buffer,
+    lambdas: Lambdas,
 }
 
 impl DistanceSolver {
-    pub fn new(device: &Device, particles_n: u64) -> Self {
+    pub fn new(device: &Device) -> Self {
         let pipeline = super::shaders::create_pipeline(
             device,
             "distance_solver",
@@ -25,31 +24,51 @@ impl DistanceSolver {
                 BufferDesc { read_only: true },
                 BufferDesc { read_only: true },
                 BufferDesc { read_only: false },
+                BufferDesc { read_only: false },
             ]
             .into_iter(),
             super::shaders::COMMON_SRC.to_string() + super::shaders::SOLVE_DIST_SRC,
         );
 
-        let distance_constraints_res = device.create_buffer(&BufferDescriptor {
-            label: Some("Distance constraints results"),
-            size: Vec::<crate::ParticleConstraintDeltas>::calculate_size_for(particles_n).into(),
-            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let distance_constraints_res = Self::create_results(device, 1);
 
         Self {
             pipeline,
             bind_group: None,
             distance_constraints_res,
+            lambdas: Lambdas::new(device, "Distance constraints multipliers"),
         }
     }
 
+    fn create_results(device: &Device, particles_n: u64) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Distance constraints results"),
+            size: Vec::<crate::ParticleConstraintDeltas>::calculate_size_for(particles_n).into(),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Makes sure there is room for the results of `particles_n` particles and the multipliers
+    /// of `constraints_n` constraints,
+    /// returning whether a buffer was reallocated, after which the bind group must be updated
+    pub fn reserve(&mut self, device: &Device, particles_n: u64, constraints_n: u64) -> bool {
+        let mut reallocated = self.lambdas.reserve(device, constraints_n);
+        let size: u64 =
+            Vec::<crate::ParticleConstraintDeltas>::calculate_size_for(particles_n).into();
+        if self.distance_constraints_res.size() < size {
+            self.distance_constraints_res = Self::create_results(device, particles_n);
+            reallocated = true;
+        }
+        reallocated
+    }
+
     pub fn update_bind_group(
         &mut self,
         device: &Device,
         sim_params: &Buffer,
-        particles: &Buffer,
-        distance_constraints: &Buffer,
+        particles: &GrowableBuffer<Particle>,
+        distance_constraints: &GrowableBuffer<DistanceC>,
     ) {
         self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: None,
@@ -61,16 +80,20 @@ impl DistanceSolver {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: particles.as_entire_binding(),
+                    resource: particles.binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: distance_constraints.as_entire_binding(),
+                    resource: distance_constraints.binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 3,
                     resource: self.distance_constraints_res.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.lambdas.binding(),
+                },
             ],
         }))
     }
@@ -78,17 +101,22 @@ impl DistanceSolver {
         encoder.clear_buffer(&self.distance_constraints_res, 0, None);
     }
 
-    pub fn run<'a: 'b, 'b>(
-        &'a self,
-        compute_pass: &'b mut ComputePass<'a>,
-        distance_constraints: &Buffer,
-    ) {
+    pub fn clear_lambdas(&self, encoder: &mut CommandEncoder) {
+        self.lambdas.clear(encoder);
+    }
+
+    pub fn run<'a: 'b, 'b>(&'a self, compute_pass: &'b mut ComputePass<'a>, constraints_n: u64) {
         const WORKGROUP_SIZE: u64 = 64;
-        let constraints_n = distance_constraints.size() / mem::size_of::<DistanceC>() as u64;
+        let Some(bind_group) = &self.bind_group else {
+            return;
+        };
+        if constraints_n == 0 {
+            return;
+        }
         let work_groups = ((constraints_n / WORKGROUP_SIZE) + 1) as u32;
 
         compute_pass.set_pipeline(&self.pipeline);
-        compute_pass.set_bind_group(0, self.bind_group.as_ref().unwrap(), &[]);
+        compute_pass.set_bind_group(0, bind_group, &[]);
         compute_pass.dispatch_workgroups(work_groups, 1, 1);
     }
 
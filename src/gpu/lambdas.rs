@@ -0,0 +1,47 @@
+use std::mem;
+
+use wgpu::{BindingResource, Buffer, BufferDescriptor, BufferUsages, CommandEncoder, Device};
+
+/// Lagrange multipliers of the constraints of a solver, accumulated over the iterations of a
+/// substep so that the compliance does not depend on the number of iterations
+pub struct Lambdas {
+    buffer: Buffer,
+    label: &'static str,
+}
+
+impl Lambdas {
+    pub fn new(device: &Device, label: &'static str) -> Self {
+        Self {
+            buffer: Self::create_buffer(device, label, 1),
+            label,
+        }
+    }
+
+    fn create_buffer(device: &Device, label: &str, n: u64) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some(label),
+            size: n.max(1) * mem::size_of::<f32>() as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Makes sure there is room for `n` multipliers, returning whether the buffer was
+    /// reallocated
+    pub fn reserve(&mut self, device: &Device, n: u64) -> bool {
+        let reallocate = self.buffer.size() < n * mem::size_of::<f32>() as u64;
+        if reallocate {
+            self.buffer = Self::create_buffer(device, self.label, n);
+        }
+        reallocate
+    }
+
+    pub fn binding(&self) -> BindingResource<'_> {
+        self.buffer.as_entire_binding()
+    }
+
+    /// Resets the multipliers for a new substep
+    pub fn clear(&self, encoder: &mut CommandEncoder) {
+        encoder.clear_buffer(&self.buffer, 0, None);
+    }
+}
@@ -1,10 +1,8 @@
-use std::mem;
-
 use wgpu::{BindGroup, Buffer, ComputePass, ComputePipeline, Device};
 
 use crate::Particle;
 
-use super::shaders::BufferDesc;
+use super::{buffer::GrowableBuffer, shaders::BufferDesc};
 
 pub struct Presolve {
     pipeline: ComputePipeline,
@@ -30,7 +28,7 @@ impl Presolve {
         &self,
         device: &Device,
         sim_params: &Buffer,
-        particles: &Buffer,
+        particles: &GrowableBuffer<Particle>,
     ) -> BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: None,
@@ -42,23 +40,30 @@ impl Presolve {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: particles.as_entire_binding(),
+                    resource: particles.binding(),
                 },
             ],
         })
     }
 
-    pub fn update_bind_group(&mut self, device: &Device, sim_params: &Buffer, particles: &Buffer) {
+    pub fn update_bind_group(
+        &mut self,
+        device: &Device,
+        sim_params: &Buffer,
+        particles: &GrowableBuffer<Particle>,
+    ) {
         self.bind_group = Some(self.create_bind_group(device, sim_params, particles));
     }
 
-    pub fn run<'a: 'b, 'b>(&'a self, compute_pass: &'b mut ComputePass<'a>, particles: &Buffer) {
+    pub fn run<'a: 'b, 'b>(&'a self, compute_pass: &'b mut ComputePass<'a>, particles_n: u64) {
         const WORKGROUP_SIZE: u64 = 64;
-        let particles_n = particles.size() / mem::size_of::<Particle>() as u64;
+        let Some(bind_group) = &self.bind_group else {
+            return;
+        };
         let particle_work_groups = ((particles_n / WORKGROUP_SIZE) + 1) as u32;
 
         compute_pass.set_pipeline(&self.pipeline);
-        compute_pass.set_bind_group(0, self.bind_group.as_ref().unwrap(), &[]);
+        compute_pass.set_bind_group(0, bind_group, &[]);
         compute_pass.dispatch_workgroups(particle_work_groups, 1, 1);
     }
 }
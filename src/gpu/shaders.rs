@@ -4,6 +4,14 @@ pub const COMMON_SRC: &str = include_str!("shaders/common.wgsl");
 pub const PRESOLVE_SRC: &str = include_str!("shaders/presolve.wgsl");
 pub const SOLVE_DIST_SRC: &str = include_str!("shaders/solve_dist.wgsl");
 pub const SOLVE_TET_SRC: &str = include_str!("shaders/solve_tet_vol.wgsl");
+pub const COUNT_INVERTED_SRC: &str = include_str!("shaders/count_inverted.wgsl");
+pub const SOLVE_SHAPE_MATCHING_SRC: &str = include_str!("shaders/solve_shape_matching.wgsl");
+pub const SOLVE_FIBER_SRC: &str = include_str!("shaders/solve_fiber.wgsl");
+pub const SOLVE_STRAIN_SRC: &str = include_str!("shaders/solve_strain.wgsl");
+pub const SOLVE_SURFACE_VOLUME_SRC: &str = include_str!("shaders/solve_surface_volume.wgsl");
+pub const SOLVE_TETHER_SRC: &str = include_str!("shaders/solve_tether.wgsl");
+pub const AERO_SRC: &str = include_str!("shaders/aero.wgsl");
+pub const ADD_AERO_SRC: &str = include_str!("shaders/add_aero.wgsl");
 pub const ADD_DELTAS_SRC: &str = include_str!("shaders/add_deltas.wgsl");
 pub const POSTSOLVE_SRC: &str = include_str!("shaders/postsolve.wgsl");
 
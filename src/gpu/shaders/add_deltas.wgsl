@@ -16,6 +16,9 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
   }
 
   let n = results[index].n;
+  if n == 0u {
+      return;
+  }
 
   var total = vec3(0.0);
   for (var i = 0u; i < n && i < DELTAS_SIZE; i++) {
@@ -0,0 +1,111 @@
+struct ParticleConstraintDeltas {
+ n: atomic<u32>,
+ deltas: array<vec3f, DELTAS_SIZE>,
+};
+
+struct AeroTriangle {
+ particles_idx: array<u32, 3>,
+ drag: f32,
+ lift: f32,
+};
+
+@binding(0) @group(0) var<uniform> params: SimParams;
+@binding(1) @group(0) var<storage, read> particles: array<Particle>;
+@binding(2) @group(0) var<storage, read> triangles: array<AeroTriangle>;
+@binding(3) @group(0) var<storage, read> winds: array<vec4f>;
+@binding(4) @group(0) var<storage, read_write> results: array<ParticleConstraintDeltas>;
+
+const WIND_TURBULENT = 1u;
+const WIND_FIELD = 2u;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
+  let t_idx = GlobalInvocationID.x;
+
+  if t_idx >= arrayLength(&triangles) {
+      return;
+  }
+
+  let t = triangles[t_idx];
+  let p0 = particles[t.particles_idx[0]];
+  let p1 = particles[t.particles_idx[1]];
+  let p2 = particles[t.particles_idx[2]];
+
+  var wind = params.wind_velocity;
+  if params.wind_mode == WIND_TURBULENT {
+    wind = turbulent_wind((p0.position + p1.position + p2.position) / 3.0);
+  } else if params.wind_mode == WIND_FIELD {
+    wind = winds[t_idx].xyz;
+  }
+
+  let relative = wind - (p0.velocity + p1.velocity + p2.velocity) / 3.0;
+  let cross_edges = cross(p1.position - p0.position, p2.position - p0.position);
+  let area = length(cross_edges) / 2.0;
+  if area == 0.0 || length2(relative) == 0.0 {
+      return;
+  }
+  let flow = normalize(relative);
+  var normal = normalize(cross_edges);
+  // Facing downstream, so the lift points away from the side the air hits
+  if dot(normal, flow) < 0.0 {
+      normal = -normal;
+  }
+  let cos_flow = dot(normal, flow);
+  let pressure = 0.5 * params.air_density * length2(relative);
+  let force = pressure * area * cos_flow * (t.drag * flow + t.lift * (normal - cos_flow * flow));
+
+  for (var i = 0u; i < 3u; i++) {
+    let idx = triangles[t_idx].particles_idx[i];
+    add_delta_to_list(force / 3.0 * particles[idx].inv_mass * params.delta, idx);
+  }
+}
+
+fn turbulent_wind(position: vec3f) -> vec3f {
+  let p = vec4((position - params.wind_velocity * params.time) / params.wind_scale, params.time * params.wind_frequency);
+  let seed = params.wind_seed * 3u;
+  let gust = vec3(value_noise(p, seed), value_noise(p, seed + 1u), value_noise(p, seed + 2u));
+  return params.wind_velocity + params.wind_amplitude * gust;
+}
+
+// Mirrors `value_noise` in aero.rs
+fn value_noise(p: vec4f, seed: u32) -> f32 {
+  let cell = floor(p);
+  let f = p - cell;
+  var s = f * f * (3.0 - 2.0 * f);
+  var c = vec4<i32>(cell);
+  var total = 0.0;
+  for (var corner = 0u; corner < 16u; corner++) {
+    var h = seed;
+    var weight = 1.0;
+    for (var axis = 0u; axis < 4u; axis++) {
+      let bit = (corner >> axis) & 1u;
+      h = hash(h ^ bitcast<u32>(c[axis] + i32(bit)));
+      if bit == 1u {
+        weight *= s[axis];
+      } else {
+        weight *= 1.0 - s[axis];
+      }
+    }
+    total += weight * (f32(h >> 8u) / 8388607.5 - 1.0);
+  }
+  return total;
+}
+
+fn hash(x: u32) -> u32 {
+  var h = x;
+  h ^= h >> 16u;
+  h *= 0x7feb352du;
+  h ^= h >> 15u;
+  h *= 0x846ca68bu;
+  return h ^ (h >> 16u);
+}
+
+fn add_delta_to_list(delta: vec3<f32>, idx: u32) {
+  let n = &results[idx].n;
+  let index = atomicAdd(n, 1u);
+
+  if index >= DELTAS_SIZE {
+      return;
+    }
+  results[idx].deltas[index] = delta;
+}
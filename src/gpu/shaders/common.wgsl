@@ -8,19 +8,60 @@ struct Particle {
 
 struct DistanceC {
  particles_idx: array<u32, 2>,
- rest_distance: f32,
+ min_distance: f32,
+ max_distance: f32,
  compliance: f32,
+ damping: f32,
+ enabled: u32,
 };
 
 struct TetrahedralVolumeC {
  particles_idx: array<u32, 4>,
  rest_volume: f32,
+ rest_gradient_norm_squared: f32,
+ compliance: f32,
+ damping: f32,
+ enabled: u32,
+};
+
+struct TriangleStrainC {
+ particles_idx: array<u32, 3>,
+ inv_rest: mat2x2<f32>,
+ rest_area: f32,
+ compliance: vec3f,
+};
+
+struct TetherC {
+ particles_idx: array<u32, 2>,
+ max_distance: f32,
+ compliance: f32,
+};
+
+struct FiberC {
+ particles_idx: array<u32, 4>,
+ weights: vec3f,
+ rest_volume: f32,
+ activation: f32,
  compliance: f32,
 };
 
 struct SimParams {
+ gravity: vec3f,
  delta: f32,
- jacobi_w: f32
+ ground_normal: vec3f,
+ ground_offset: f32,
+ jacobi_w: f32,
+ damping: f32,
+ has_ground: u32,
+ air_density: f32,
+ wind_velocity: vec3f,
+ wind_mode: u32,
+ wind_amplitude: f32,
+ wind_scale: f32,
+ wind_frequency: f32,
+ wind_seed: u32,
+ time: f32,
+ iteration: u32,
 };
 
 const DELTAS_SIZE = 64u;
@@ -0,0 +1,21 @@
+@binding(0) @group(0) var<uniform> params: SimParams;
+@binding(1) @group(0) var<storage, read> particles: array<Particle>;
+@binding(2) @group(0) var<storage, read> constraints: array<TetrahedralVolumeC>;
+@binding(3) @group(0) var<storage, read_write> inverted: atomic<u32>;
+
+// Counts the enabled tetrahedra with negative volume, once the step is done
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
+  let c_idx = GlobalInvocationID.x;
+
+  if c_idx >= arrayLength(&constraints) || constraints[c_idx].enabled == 0u {
+      return;
+  }
+
+  let idx = constraints[c_idx].particles_idx;
+  let p0 = particles[idx[0]].position;
+  let vol = dot(cross(particles[idx[1]].position - p0, particles[idx[2]].position - p0), particles[idx[3]].position - p0);
+  if vol < 0.0 {
+    atomicAdd(&inverted, 1u);
+  }
+}
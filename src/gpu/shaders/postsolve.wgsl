@@ -9,5 +9,6 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
       return;
   }
 
-  particles[index].velocity = (particles[index].position - particles[index].prev_position) / params.delta;
+  let velocity_scale = exp(-params.damping * params.delta);
+  particles[index].velocity = velocity_scale * (particles[index].position - particles[index].prev_position) / params.delta;
 }
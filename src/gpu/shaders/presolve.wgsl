@@ -11,14 +11,17 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
 
   var p = particles[index];
 
+  if p.inv_mass != 0.0 {
+      p.velocity += params.gravity * params.delta;
+  }
   p.velocity += p.ext_acc * params.delta;
   p.prev_position = p.position;
   p.position += p.velocity * params.delta;
 
   // Ground plane clipping
-  if p.position.z < 0.0 {
-      p.position = p.prev_position;
-      p.position.z = 0.;
+  if params.has_ground != 0u && dot(params.ground_normal, p.position) < params.ground_offset {
+      let prev_height = dot(params.ground_normal, p.prev_position) - params.ground_offset;
+      p.position = p.prev_position - prev_height * params.ground_normal;
   }
 
   particles[index] = p;
@@ -7,6 +7,8 @@ struct ParticleConstraintDeltas {
 @binding(1) @group(0) var<storage, read> particles: array<Particle>;
 @binding(2) @group(0) var<storage, read> distance_constraints: array<DistanceC>;
 @binding(3) @group(0) var<storage, read_write> results: array<ParticleConstraintDeltas>;
+// Accumulated over the iterations of a substep
+@binding(4) @group(0) var<storage, read_write> lambdas: array<f32>;
 
 @compute @workgroup_size(64)
 fn main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
@@ -17,10 +19,18 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
   }
 
   let c = distance_constraints[index];
+  if c.enabled == 0u {
+      return;
+  }
   let ps_idx = array(c.particles_idx[0], c.particles_idx[1]);
   let ps = array(particles[ps_idx[0]], particles[ps_idx[1]]);
 
-  let value = distance(ps[0].position, ps[1].position) - c.rest_distance;
+  // Slack strictly inside the range, without adding deltas that would count when averaging
+  let dist = distance(ps[0].position, ps[1].position);
+  if c.min_distance < dist && dist < c.max_distance {
+      return;
+  }
+  let value = dist - clamp(dist, c.min_distance, c.max_distance);
 
   let dir = normalize(ps[0].position - ps[1].position);
 
@@ -28,11 +38,19 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
   let grad_2 = -dir;
 
   let xpbd_stiff = c.compliance / params.delta / params.delta;
+  let gamma = c.compliance * c.damping / params.delta;
 
-  let lambda = -value/((ps[0].inv_mass * length2(grad_1) + ps[1].inv_mass * length2(grad_2)) + xpbd_stiff);
+  let damping = gamma * (dot(grad_1, ps[0].position - ps[0].prev_position) + dot(grad_2, ps[1].position - ps[1].prev_position));
+
+  let denominator = (1.0 + gamma) * (ps[0].inv_mass * length2(grad_1) + ps[1].inv_mass * length2(grad_2)) + xpbd_stiff;
+  if denominator == 0.0 {
+      return;
+  }
+  let delta_lambda = -(value + xpbd_stiff * lambdas[index] + damping) / denominator;
+  lambdas[index] += delta_lambda;
 
-  let x1_delta = lambda * ps[0].inv_mass * grad_1;
-  let x2_delta = lambda * ps[1].inv_mass * grad_2;
+  let x1_delta = delta_lambda * ps[0].inv_mass * grad_1;
+  let x2_delta = delta_lambda * ps[1].inv_mass * grad_2;
 
   add_delta_to_list(x1_delta, ps_idx[0]);
   add_delta_to_list(x2_delta, ps_idx[1]);
@@ -0,0 +1,62 @@
+struct ParticleConstraintDeltas {
+ n: atomic<u32>,
+ deltas: array<vec3f, DELTAS_SIZE>,
+};
+
+@binding(0) @group(0) var<uniform> params: SimParams;
+@binding(1) @group(0) var<storage, read> particles: array<Particle>;
+@binding(2) @group(0) var<storage, read> constraints: array<FiberC>;
+@binding(3) @group(0) var<storage, read_write> results: array<ParticleConstraintDeltas>;
+// Accumulated over the iterations of a substep
+@binding(4) @group(0) var<storage, read_write> lambdas: array<f32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
+  let c_idx = GlobalInvocationID.x;
+
+  if c_idx >= arrayLength(&constraints) {
+      return;
+  }
+
+  let c = constraints[c_idx];
+  let w = c.weights;
+  var weights: array<f32, 4>;
+  weights[0] = -(w.x + w.y + w.z);
+  weights[1] = w.x;
+  weights[2] = w.y;
+  weights[3] = w.z;
+
+  var fiber = vec3(0.0);
+  for (var i = 0u; i < 4u; i++) {
+    fiber += particles[constraints[c_idx].particles_idx[i]].position * weights[i];
+  }
+  let value = length(fiber) - (1.0 - c.activation);
+  let dir = normalize(fiber);
+
+  var grad_sum = 0.0;
+  for (var i = 0u; i < 4u; i++) {
+    grad_sum += weights[i] * weights[i] * particles[constraints[c_idx].particles_idx[i]].inv_mass;
+  }
+  let xpbd_stiff = c.compliance / (c.rest_volume * params.delta * params.delta);
+  let denominator = grad_sum + xpbd_stiff;
+  if denominator == 0.0 {
+      return;
+  }
+  let delta_lambda = -(value + xpbd_stiff * lambdas[c_idx]) / denominator;
+  lambdas[c_idx] += delta_lambda;
+
+  for (var i = 0u; i < 4u; i++) {
+    let idx = constraints[c_idx].particles_idx[i];
+    add_delta_to_list(delta_lambda * particles[idx].inv_mass * weights[i] * dir, idx);
+  }
+}
+
+fn add_delta_to_list(delta: vec3<f32>, idx: u32) {
+  let n = &results[idx].n;
+  let index = atomicAdd(n, 1u);
+
+  if index >= DELTAS_SIZE {
+      return;
+    }
+  results[idx].deltas[index] = delta;
+}
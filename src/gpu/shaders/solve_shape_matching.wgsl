@@ -0,0 +1,268 @@
+struct ParticleConstraintDeltas {
+ n: atomic<u32>,
+ deltas: array<vec3f, DELTAS_SIZE>,
+};
+
+struct ShapeCluster {
+ start: u32,
+ len: u32,
+ mode: u32,
+ stiffness: f32,
+ beta: f32,
+ aqq_inv: array<f32, 81>,
+};
+
+struct ShapeParticle {
+ idx: u32,
+ mass: f32,
+ rest: vec3f,
+};
+
+@binding(0) @group(0) var<uniform> params: SimParams;
+@binding(1) @group(0) var<storage, read> particles: array<Particle>;
+@binding(2) @group(0) var<storage, read> clusters: array<ShapeCluster>;
+@binding(3) @group(0) var<storage, read> cluster_particles: array<ShapeParticle>;
+@binding(4) @group(0) var<storage, read_write> results: array<ParticleConstraintDeltas>;
+
+const WORKGROUP_SIZE = 64u;
+const MODE_LINEAR = 1u;
+const MODE_QUADRATIC = 2u;
+
+// Per invocation partial sums, at most the 3x9 quadratic covariance
+var<workgroup> sums: array<array<f32, 27>, WORKGROUP_SIZE>;
+var<workgroup> transform: array<vec3f, 9>;
+
+// One workgroup per cluster
+@compute @workgroup_size(64)
+fn main(@builtin(workgroup_id) workgroup_id: vec3<u32>, @builtin(local_invocation_index) lid: u32) {
+  let c_idx = workgroup_id.x;
+
+  if c_idx >= arrayLength(&clusters) {
+      return;
+  }
+
+  let start = clusters[c_idx].start;
+  let len = clusters[c_idx].len;
+
+  // Center of mass
+  var partial = vec4(0.0);
+  for (var i = lid; i < len; i += WORKGROUP_SIZE) {
+    let sp = cluster_particles[start + i];
+    partial += vec4(sp.mass * particles[sp.idx].position, sp.mass);
+  }
+  for (var k = 0u; k < 4u; k++) {
+    sums[lid][k] = partial[k];
+  }
+  workgroupBarrier();
+  reduce(lid, 4u);
+  let center = vec3(sums[0][0], sums[0][1], sums[0][2]) / sums[0][3];
+  workgroupBarrier();
+
+  // Covariance of the current and rest positions, a column per term of the rest position
+  var terms = 3u;
+  if clusters[c_idx].mode == MODE_QUADRATIC {
+    terms = 9u;
+  }
+  var apq: array<vec3f, 9>;
+  for (var i = lid; i < len; i += WORKGROUP_SIZE) {
+    let sp = cluster_particles[start + i];
+    let p = sp.mass * (particles[sp.idx].position - center);
+    for (var k = 0u; k < terms; k++) {
+      apq[k] += quadratic_term(sp.rest, k) * p;
+    }
+  }
+  for (var k = 0u; k < terms; k++) {
+    sums[lid][3u * k] = apq[k].x;
+    sums[lid][3u * k + 1u] = apq[k].y;
+    sums[lid][3u * k + 2u] = apq[k].z;
+  }
+  workgroupBarrier();
+  reduce(lid, 3u * terms);
+
+  if lid == 0u {
+    compute_transform(c_idx);
+  }
+  workgroupBarrier();
+
+  let stiffness = clusters[c_idx].stiffness;
+  for (var i = lid; i < len; i += WORKGROUP_SIZE) {
+    let sp = cluster_particles[start + i];
+    if particles[sp.idx].inv_mass == 0.0 {
+      continue;
+    }
+    var goal = center;
+    for (var k = 0u; k < 9u; k++) {
+      goal += transform[k] * quadratic_term(sp.rest, k);
+    }
+    add_delta_to_list(stiffness * (goal - particles[sp.idx].position), sp.idx);
+  }
+}
+
+// Sums the first `n` partial sums of every invocation into sums[0]
+fn reduce(lid: u32, n: u32) {
+  for (var s = WORKGROUP_SIZE / 2u; s > 0u; s >>= 1u) {
+    if lid < s {
+      for (var k = 0u; k < n; k++) {
+	sums[lid][k] += sums[lid + s][k];
+      }
+    }
+    workgroupBarrier();
+  }
+}
+
+fn sum_column(k: u32) -> vec3f {
+  return vec3(sums[0][3u * k], sums[0][3u * k + 1u], sums[0][3u * k + 2u]);
+}
+
+fn compute_transform(c_idx: u32) {
+  let apq = mat3x3(sum_column(0u), sum_column(1u), sum_column(2u));
+  var rotation = extract_rotation(apq);
+  let mode = clusters[c_idx].mode;
+  let beta = clusters[c_idx].beta;
+
+  for (var k = 0u; k < 9u; k++) {
+    transform[k] = vec3(0.0);
+  }
+
+  if mode == MODE_LINEAR {
+    var aqq_inv: mat3x3<f32>;
+    for (var k = 0u; k < 3u; k++) {
+      aqq_inv[k] = vec3(clusters[c_idx].aqq_inv[3u * k], clusters[c_idx].aqq_inv[3u * k + 1u], clusters[c_idx].aqq_inv[3u * k + 2u]);
+    }
+    var a = apq * aqq_inv;
+    let det = determinant(a);
+    if det > 0.0 {
+      a = a * (1.0 / pow(det, 1.0 / 3.0));
+    }
+    for (var k = 0u; k < 3u; k++) {
+      transform[k] = beta * a[k] + (1.0 - beta) * rotation[k];
+    }
+  } else if mode == MODE_QUADRATIC {
+    for (var col = 0u; col < 9u; col++) {
+      var a = vec3(0.0);
+      for (var k = 0u; k < 9u; k++) {
+	a += sum_column(k) * clusters[c_idx].aqq_inv[col * 9u + k];
+      }
+      var r = vec3(0.0);
+      if col < 3u {
+	r = rotation[col];
+      }
+      transform[col] = beta * a + (1.0 - beta) * r;
+    }
+  } else {
+    for (var k = 0u; k < 3u; k++) {
+      transform[k] = rotation[k];
+    }
+  }
+}
+
+// Term `k` of [x, y, z, x^2, y^2, z^2, xy, yz, zx]
+fn quadratic_term(q: vec3f, k: u32) -> f32 {
+  if k < 3u {
+    return q[k];
+  } else if k < 6u {
+    return q[k - 3u] * q[k - 3u];
+  }
+  return q[k - 6u] * q[(k - 5u) % 3u];
+}
+
+fn normalize_above(v: vec3f, eps: f32) -> vec3f {
+  let l = length(v);
+  if l > eps {
+    return v / l;
+  }
+  return vec3(0.0);
+}
+
+// Rotational part of `a`, see `extract_rotation` in shape_matching.rs
+fn extract_rotation(a: mat3x3<f32>) -> mat3x3<f32> {
+  let eps = 1e-4 * max(length(a[0]), max(length(a[1]), length(a[2])));
+  let x = normalize_above(a[0], eps);
+  let y = normalize_above(a[1] - x * dot(x, a[1]), eps);
+  let guess = mat3x3(x, y, cross(x, y));
+  var q = vec4(0.0, 0.0, 0.0, 1.0);
+  if determinant(guess) > 0.5 {
+    q = normalize(quat_from_mat(guess));
+  }
+
+  for (var i = 0; i < 20; i++) {
+    let r = quat_to_mat(q);
+    let omega = (cross(r[0], a[0]) + cross(r[1], a[1]) + cross(r[2], a[2]))
+      / (abs(dot(r[0], a[0]) + dot(r[1], a[1]) + dot(r[2], a[2])) + 1e-9);
+    if dot(omega, omega) < 1e-18 {
+      break;
+    }
+    q = normalize(quat_mul(quat_from_scaled_axis(omega), q));
+  }
+  return quat_to_mat(q);
+}
+
+fn quat_mul(a: vec4f, b: vec4f) -> vec4f {
+  return vec4(a.w * b.xyz + b.w * a.xyz + cross(a.xyz, b.xyz), a.w * b.w - dot(a.xyz, b.xyz));
+}
+
+fn quat_from_scaled_axis(v: vec3f) -> vec4f {
+  let angle = length(v);
+  if angle == 0.0 {
+    return vec4(0.0, 0.0, 0.0, 1.0);
+  }
+  return vec4(sin(angle / 2.0) * v / angle, cos(angle / 2.0));
+}
+
+fn quat_to_mat(q: vec4f) -> mat3x3<f32> {
+  let x2 = q.x + q.x;
+  let y2 = q.y + q.y;
+  let z2 = q.z + q.z;
+  let xx = q.x * x2;
+  let xy = q.x * y2;
+  let xz = q.x * z2;
+  let yy = q.y * y2;
+  let yz = q.y * z2;
+  let zz = q.z * z2;
+  let wx = q.w * x2;
+  let wy = q.w * y2;
+  let wz = q.w * z2;
+  return mat3x3(
+    vec3(1.0 - (yy + zz), xy + wz, xz - wy),
+    vec3(xy - wz, 1.0 - (xx + zz), yz + wx),
+    vec3(xz + wy, yz - wx, 1.0 - (xx + yy)),
+  );
+}
+
+fn quat_from_mat(m: mat3x3<f32>) -> vec4f {
+  if m[2].z <= 0.0 {
+    let dif10 = m[1].y - m[0].x;
+    let omm22 = 1.0 - m[2].z;
+    if dif10 <= 0.0 {
+      let four_xsq = omm22 - dif10;
+      let inv4x = 0.5 / sqrt(four_xsq);
+      return vec4(four_xsq * inv4x, (m[0].y + m[1].x) * inv4x, (m[0].z + m[2].x) * inv4x, (m[1].z - m[2].y) * inv4x);
+    } else {
+      let four_ysq = omm22 + dif10;
+      let inv4y = 0.5 / sqrt(four_ysq);
+      return vec4((m[0].y + m[1].x) * inv4y, four_ysq * inv4y, (m[1].z + m[2].y) * inv4y, (m[2].x - m[0].z) * inv4y);
+    }
+  } else {
+    let sum10 = m[1].y + m[0].x;
+    let opm22 = 1.0 + m[2].z;
+    if sum10 <= 0.0 {
+      let four_zsq = opm22 - sum10;
+      let inv4z = 0.5 / sqrt(four_zsq);
+      return vec4((m[0].z + m[2].x) * inv4z, (m[1].z + m[2].y) * inv4z, four_zsq * inv4z, (m[0].y - m[1].x) * inv4z);
+    } else {
+      let four_wsq = opm22 + sum10;
+      let inv4w = 0.5 / sqrt(four_wsq);
+      return vec4((m[1].z - m[2].y) * inv4w, (m[2].x - m[0].z) * inv4w, (m[0].y - m[1].x) * inv4w, four_wsq * inv4w);
+    }
+  }
+}
+
+fn add_delta_to_list(delta: vec3<f32>, idx: u32) {
+  let n = &results[idx].n;
+  let index = atomicAdd(n, 1u);
+
+  if index >= DELTAS_SIZE {
+      return;
+    }
+  results[idx].deltas[index] = delta;
+}
@@ -0,0 +1,101 @@
+struct ParticleConstraintDeltas {
+ n: atomic<u32>,
+ deltas: array<vec3f, DELTAS_SIZE>,
+};
+
+@binding(0) @group(0) var<uniform> params: SimParams;
+@binding(1) @group(0) var<storage, read> particles: array<Particle>;
+@binding(2) @group(0) var<storage, read> constraints: array<TriangleStrainC>;
+@binding(3) @group(0) var<storage, read_write> results: array<ParticleConstraintDeltas>;
+// One per strain, accumulated over the iterations of a substep
+@binding(4) @group(0) var<storage, read_write> lambdas: array<f32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
+  let c_idx = GlobalInvocationID.x;
+
+  if c_idx >= arrayLength(&constraints) {
+      return;
+  }
+
+  // Weights of each particle in the deformed warp and weft axes
+  let inv_rest = constraints[c_idx].inv_rest;
+  let a = vec2(inv_rest[0].x, inv_rest[1].x);
+  let b = vec2(inv_rest[0].y, inv_rest[1].y);
+  var weights: array<vec2f, 3>;
+  weights[0] = -(a + b);
+  weights[1] = a;
+  weights[2] = b;
+
+  var start: array<vec3f, 3>;
+  var pos: array<vec3f, 3>;
+  for (var i = 0u; i < 3u; i++) {
+    start[i] = particles[constraints[c_idx].particles_idx[i]].position;
+    pos[i] = start[i];
+  }
+
+  // Warp stretch, weft stretch and shear, solved one after the other
+  var compliance = constraints[c_idx].compliance;
+  for (var row = 0u; row < 3u; row++) {
+    var f1 = vec3(0.0);
+    var f2 = vec3(0.0);
+    for (var i = 0u; i < 3u; i++) {
+      f1 += pos[i] * weights[i].x;
+      f2 += pos[i] * weights[i].y;
+    }
+
+    var value = 0.0;
+    var grad: array<vec3f, 3>;
+    for (var i = 0u; i < 3u; i++) {
+      let w = weights[i];
+      if row == 0u {
+        grad[i] = f1 * w.x;
+      } else if row == 1u {
+        grad[i] = f2 * w.y;
+      } else {
+        grad[i] = (f2 * w.x + f1 * w.y) / 2.0;
+      }
+    }
+    if row == 0u {
+      value = (length2(f1) - 1.0) / 2.0;
+    } else if row == 1u {
+      value = (length2(f2) - 1.0) / 2.0;
+    } else {
+      value = dot(f1, f2) / 2.0;
+    }
+
+    var grad_sum = 0.0;
+    for (var i = 0u; i < 3u; i++) {
+      grad_sum += length2(grad[i]) * inv_mass(c_idx, i);
+    }
+    let xpbd_stiff = compliance[row] / (constraints[c_idx].rest_area * params.delta * params.delta);
+    let denominator = grad_sum + xpbd_stiff;
+    if denominator == 0.0 {
+      continue;
+    }
+    let l_idx = 3u * c_idx + row;
+    let delta_lambda = -(value + xpbd_stiff * lambdas[l_idx]) / denominator;
+    lambdas[l_idx] += delta_lambda;
+    for (var i = 0u; i < 3u; i++) {
+      pos[i] += delta_lambda * inv_mass(c_idx, i) * grad[i];
+    }
+  }
+
+  for (var i = 0u; i < 3u; i++) {
+    add_delta_to_list(pos[i] - start[i], constraints[c_idx].particles_idx[i]);
+  }
+}
+
+fn inv_mass(c_idx: u32, num: u32) -> f32 {
+  return particles[constraints[c_idx].particles_idx[num]].inv_mass;
+}
+
+fn add_delta_to_list(delta: vec3<f32>, idx: u32) {
+  let n = &results[idx].n;
+  let index = atomicAdd(n, 1u);
+
+  if index >= DELTAS_SIZE {
+      return;
+    }
+  results[idx].deltas[index] = delta;
+}
@@ -0,0 +1,110 @@
+struct ParticleConstraintDeltas {
+ n: atomic<u32>,
+ deltas: array<vec3f, DELTAS_SIZE>,
+};
+
+struct SurfaceVolume {
+ start: u32,
+ len: u32,
+ target_volume: f32,
+ compliance: f32,
+ // Accumulated over the iterations of a substep
+ lambda: f32,
+};
+
+struct SurfaceCorner {
+ particles_idx: array<u32, 3>,
+};
+
+@binding(0) @group(0) var<uniform> params: SimParams;
+@binding(1) @group(0) var<storage, read> particles: array<Particle>;
+@binding(2) @group(0) var<storage, read_write> surfaces: array<SurfaceVolume>;
+@binding(3) @group(0) var<storage, read> corners: array<SurfaceCorner>;
+@binding(4) @group(0) var<storage, read_write> results: array<ParticleConstraintDeltas>;
+
+const WORKGROUP_SIZE = 64u;
+
+// Per invocation partial sums of the volume and of the gradient norms
+var<workgroup> sums: array<vec2f, WORKGROUP_SIZE>;
+
+// One workgroup per surface, whose corners are sorted by particle
+@compute @workgroup_size(64)
+fn main(@builtin(workgroup_id) workgroup_id: vec3<u32>, @builtin(local_invocation_index) lid: u32) {
+  let c_idx = workgroup_id.x;
+
+  if c_idx >= arrayLength(&surfaces) {
+      return;
+  }
+
+  // Read by every invocation before the barriers below, past which the first one updates it.
+  // The first iteration of a substep starts over from zero
+  let lambda = select(surfaces[c_idx].lambda, 0.0, params.iteration == 0u);
+  let start = surfaces[c_idx].start;
+  let end = start + surfaces[c_idx].len;
+
+  var partial = vec2(0.0);
+  for (var i = start + lid; i < end; i += WORKGROUP_SIZE) {
+    // Every triangle has three corners
+    partial.x += dot(cross(pos(i, 0u), pos(i, 1u)), pos(i, 2u)) / 18.0;
+    if is_first_corner(i, start) {
+      partial.y += inv_mass(i) * length2(gradient(i, end));
+    }
+  }
+  sums[lid] = partial;
+  workgroupBarrier();
+  for (var s = WORKGROUP_SIZE / 2u; s > 0u; s >>= 1u) {
+    if lid < s {
+      sums[lid] += sums[lid + s];
+    }
+    workgroupBarrier();
+  }
+
+  let value = sums[0].x - surfaces[c_idx].target_volume;
+  let xpbd_stiff = surfaces[c_idx].compliance / params.delta / params.delta;
+  let denominator = sums[0].y + xpbd_stiff;
+  if denominator == 0.0 {
+      return;
+  }
+  let delta_lambda = -(value + xpbd_stiff * lambda) / denominator;
+  if lid == 0u {
+    surfaces[c_idx].lambda = lambda + delta_lambda;
+  }
+
+  for (var i = start + lid; i < end; i += WORKGROUP_SIZE) {
+    if is_first_corner(i, start) {
+      add_delta_to_list(delta_lambda * inv_mass(i) * gradient(i, end), corners[i].particles_idx[0]);
+    }
+  }
+}
+
+fn is_first_corner(i: u32, start: u32) -> bool {
+  return i == start || corners[i - 1u].particles_idx[0] != corners[i].particles_idx[0];
+}
+
+// Gradient of the volume with respect to the particle of corner `i`, summed over its corners
+fn gradient(i: u32, end: u32) -> vec3f {
+  let particle = corners[i].particles_idx[0];
+  var grad = vec3(0.0);
+  for (var j = i; j < end && corners[j].particles_idx[0] == particle; j++) {
+    grad += cross(pos(j, 1u), pos(j, 2u)) / 6.0;
+  }
+  return grad;
+}
+
+fn inv_mass(i: u32) -> f32 {
+  return particles[corners[i].particles_idx[0]].inv_mass;
+}
+
+fn pos(i: u32, num: u32) -> vec3<f32> {
+  return particles[corners[i].particles_idx[num]].position;
+}
+
+fn add_delta_to_list(delta: vec3<f32>, idx: u32) {
+  let n = &results[idx].n;
+  let index = atomicAdd(n, 1u);
+
+  if index >= DELTAS_SIZE {
+      return;
+    }
+  results[idx].deltas[index] = delta;
+}
@@ -7,12 +7,14 @@ struct ParticleConstraintDeltas {
 @binding(1) @group(0) var<storage, read> particles: array<Particle>;
 @binding(2) @group(0) var<storage, read> constraints: array<TetrahedralVolumeC>;
 @binding(3) @group(0) var<storage, read_write> results: array<ParticleConstraintDeltas>;
+// Accumulated over the iterations of a substep
+@binding(4) @group(0) var<storage, read_write> lambdas: array<f32>;
 
 @compute @workgroup_size(64)
 fn main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
   let c_idx = GlobalInvocationID.x;
 
-  if c_idx >= arrayLength(&constraints) {
+  if c_idx >= arrayLength(&constraints) || constraints[c_idx].enabled == 0u {
       return;
   }
 
@@ -23,6 +25,7 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
   var grad: array<vec3<f32>, 4>;
 
   var grad_sum = 0.0;
+  var grad_dot_v = 0.0;
 
   var orderN: array<vec4u, 4> = array<vec4u, 4>(vec4u(3u, 1u, 2u, 1u),
 			   vec4u(2u, 0u, 3u, 0u),
@@ -49,14 +52,27 @@ fn main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
     
     grad[i] = cross(pos(c_idx, order.x) - pos(c_idx, order.y), pos(c_idx, order.z) - pos(c_idx, order.w));
     grad_sum += length2(grad[i])*inv_mass(c_idx, i);
+    grad_dot_v += dot(grad[i], pos(c_idx, i) - prev_pos(c_idx, i));
   }
 
-  let xpbd_stiff = constraints[c_idx].compliance / params.delta / params.delta;
-  
-  let lambda = -value / (grad_sum + xpbd_stiff);
+  // Inverted tetrahedra are solved as hard constraints until they recover
+  var compliance = constraints[c_idx].compliance;
+  if vol < 0.0 {
+    compliance = 0.0;
+  }
+
+  let xpbd_stiff = compliance / params.delta / params.delta;
+  let gamma = compliance * constraints[c_idx].damping / params.delta;
+
+  let denominator = (1.0 + gamma) * grad_sum + xpbd_stiff;
+  if denominator == 0.0 {
+      return;
+  }
+  let delta_lambda = -(value + xpbd_stiff * lambdas[c_idx] + gamma * grad_dot_v) / denominator;
+  lambdas[c_idx] += delta_lambda;
 
   for (var i = 0u; i < 4u; i++) {
-    let delta = lambda * inv_mass(c_idx, i) * grad[i];
+    let delta = delta_lambda * inv_mass(c_idx, i) * grad[i];
     add_delta_to_list(delta, constraints[c_idx].particles_idx[i]);
   }
 }
@@ -69,6 +85,10 @@ fn pos(c_idx: u32, num: u32) -> vec3<f32> {
   return particles[constraints[c_idx].particles_idx[num]].position;
 }
 
+fn prev_pos(c_idx: u32, num: u32) -> vec3<f32> {
+  return particles[constraints[c_idx].particles_idx[num]].prev_position;
+}
+
 fn add_delta_to_list(delta: vec3<f32>, idx: u32) {  
   let n = &results[idx].n;
   let index = atomicAdd(n, 1u);
@@ -0,0 +1,53 @@
+struct ParticleConstraintDeltas {
+ n: atomic<u32>,
+ deltas: array<vec3f, DELTAS_SIZE>,
+};
+
+@binding(0) @group(0) var<uniform> params: SimParams;
+@binding(1) @group(0) var<storage, read> particles: array<Particle>;
+@binding(2) @group(0) var<storage, read> constraints: array<TetherC>;
+@binding(3) @group(0) var<storage, read_write> results: array<ParticleConstraintDeltas>;
+// Accumulated over the iterations of a substep
+@binding(4) @group(0) var<storage, read_write> lambdas: array<f32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) GlobalInvocationID : vec3<u32>) {
+  let index = GlobalInvocationID.x;
+
+  if index >= arrayLength(&constraints) {
+      return;
+  }
+
+  let c = constraints[index];
+  let anchor = particles[c.particles_idx[0]];
+  let particle = particles[c.particles_idx[1]];
+
+  // Slack tethers add no deltas, so they don't count when the deltas are averaged
+  let value = distance(anchor.position, particle.position) - c.max_distance;
+  if value <= 0.0 {
+      return;
+  }
+
+  let dir = normalize(particle.position - anchor.position);
+
+  let xpbd_stiff = c.compliance / params.delta / params.delta;
+  let denominator = anchor.inv_mass + particle.inv_mass + xpbd_stiff;
+  if denominator == 0.0 {
+      return;
+  }
+  let delta_lambda = -(value + xpbd_stiff * lambdas[index]) / denominator;
+  lambdas[index] += delta_lambda;
+
+  add_delta_to_list(-delta_lambda * anchor.inv_mass * dir, c.particles_idx[0]);
+  add_delta_to_list(delta_lambda * particle.inv_mass * dir, c.particles_idx[1]);
+}
+
+fn add_delta_to_list(delta: vec3<f32>, idx: u32) {
+  let n = &results[idx].n;
+  let index = atomicAdd(n, 1u);
+
+  if index >= DELTAS_SIZE {
+      return;
+    }
+  results[idx].deltas[index] = delta;
+}
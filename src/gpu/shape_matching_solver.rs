@@ -0,0 +1,176 @@
+use encase::{CalculateSizeFor, ShaderType};
+use glam::Vec3;
+use wgpu::{
+    BindGroup, Buffer, BufferDescriptor, BufferUsages, CommandEncoder, ComputePass,
+    ComputePipeline, Device,
+};
+
+use crate::{Particle, ShapeMatchingC, ShapeMatchingMode};
+
+use super::{buffer::GrowableBuffer, shaders::BufferDesc};
+
+/// A shape matching constraint, its particles are `len` consecutive [`ShapeParticle`]s
+#[derive(Clone, Copy, ShaderType)]
+pub struct ShapeCluster {
+    start: u32,
+    len: u32,
+    mode: u32,
+    stiffness: f32,
+    beta: f32,
+    aqq_inv: [f32; 81],
+}
+
+#[derive(Clone, Copy, ShaderType)]
+pub struct ShapeParticle {
+    idx: u32,
+    mass: f32,
+    rest: Vec3,
+}
+
+/// Flattens `constraints` into clusters whose particles start at `first_particle`
+pub fn flatten_clusters<'a>(
+    constraints: impl IntoIterator<Item = &'a ShapeMatchingC>,
+    first_particle: u32,
+) -> (Vec<ShapeCluster>, Vec<ShapeParticle>) {
+    let mut clusters = Vec::new();
+    let mut particles = Vec::new();
+    for c in constraints {
+        let (mode, beta) = match c.mode {
+            ShapeMatchingMode::Rigid => (0, 0.),
+            ShapeMatchingMode::Linear { beta } => (1, beta),
+            ShapeMatchingMode::Quadratic { beta } => (2, beta),
+        };
+        let mut aqq_inv = [0.; 81];
+        aqq_inv[..c.aqq_inv.len()].copy_from_slice(&c.aqq_inv);
+        clusters.push(ShapeCluster {
+            start: first_particle + particles.len() as u32,
+            len: c.particles_idx.len() as u32,
+            mode,
+            stiffness: c.stiffness,
+            beta,
+            aqq_inv,
+        });
+        particles.extend(
+            c.particles_idx
+                .iter()
+                .zip(&c.masses)
+                .zip(&c.rest_offsets)
+                .map(|((idx, mass), rest)| ShapeParticle {
+                    idx: *idx,
+                    mass: *mass,
+                    rest: *rest,
+                }),
+        );
+    }
+    (clusters, particles)
+}
+
+pub struct ShapeMatchingSolver {
+    pipeline: ComputePipeline,
+    bind_group: Option<BindGroup>,
+    results: Buffer,
+}
+
+impl ShapeMatchingSolver {
+    pub fn new(device: &Device) -> Self {
+        let pipeline = super::shaders::create_pipeline(
+            device,
+            "shape_matching_solver",
+            [
+                BufferDesc { read_only: true },
+                BufferDesc { read_only: true },
+                BufferDesc { read_only: true },
+                BufferDesc { read_only: false },
+            ]
+            .into_iter(),
+            super::shaders::COMMON_SRC.to_string() + super::shaders::SOLVE_SHAPE_MATCHING_SRC,
+        );
+
+        let results = Self::create_results(device, 1);
+
+        Self {
+            pipeline,
+            bind_group: None,
+            results,
+        }
+    }
+
+    fn create_results(device: &Device, particles_n: u64) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Shape matching constraints results"),
+            size: Vec::<crate::ParticleConstraintDeltas>::calculate_size_for(particles_n).into(),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Makes sure there is room for the results of `particles_n` particles,
+    /// returning whether a buffer was reallocated, after which the bind group must be updated
+    pub fn reserve(&mut self, device: &Device, particles_n: u64) -> bool {
+        let size: u64 =
+            Vec::<crate::ParticleConstraintDeltas>::calculate_size_for(particles_n).into();
+        let reallocate = self.results.size() < size;
+        if reallocate {
+            self.results = Self::create_results(device, particles_n);
+        }
+        reallocate
+    }
+
+    pub fn update_bind_group(
+        &mut self,
+        device: &Device,
+        sim_params: &Buffer,
+        particles: &GrowableBuffer<Particle>,
+        clusters: &GrowableBuffer<ShapeCluster>,
+        cluster_particles: &GrowableBuffer<ShapeParticle>,
+    ) {
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: sim_params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particles.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: clusters.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: cluster_particles.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.results.as_entire_binding(),
+                },
+            ],
+        }))
+    }
+
+    pub fn prerun(&self, encoder: &mut CommandEncoder) {
+        encoder.clear_buffer(&self.results, 0, None);
+    }
+
+    /// Dispatches a workgroup per cluster
+    pub fn run<'a: 'b, 'b>(&'a self, compute_pass: &'b mut ComputePass<'a>, clusters_n: u64) {
+        let Some(bind_group) = &self.bind_group else {
+            return;
+        };
+        if clusters_n == 0 {
+            return;
+        }
+
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, bind_group, &[]);
+        compute_pass.dispatch_workgroups(clusters_n as u32, 1, 1);
+    }
+
+    pub fn results(&self) -> &Buffer {
+        &self.results
+    }
+}
@@ -0,0 +1,171 @@
+use encase::{CalculateSizeFor, ShaderType};
+use wgpu::{
+    BindGroup, Buffer, BufferDescriptor, BufferUsages, CommandEncoder, ComputePass,
+    ComputePipeline, Device,
+};
+
+use crate::{Particle, SurfaceVolumeC};
+
+use super::{buffer::GrowableBuffer, shaders::BufferDesc};
+
+/// A surface volume constraint, its triangles' corners are `len` consecutive
+/// [`SurfaceCorner`]s
+#[derive(Clone, Copy, ShaderType)]
+pub struct SurfaceVolume {
+    start: u32,
+    len: u32,
+    target_volume: f32,
+    compliance: f32,
+    /// Multiplier accumulated over the iterations of a substep, kept with the surface rather
+    /// than in a buffer of its own to stay within the downlevel storage buffer limit
+    lambda: f32,
+}
+
+/// The particles of a triangle, starting from the corner's one
+#[derive(Clone, Copy, ShaderType)]
+pub struct SurfaceCorner {
+    particles_idx: [u32; 3],
+}
+
+/// Flattens `constraints` into surfaces whose corners start at `first_corner`, the corners of
+/// each surface sorted by particle so the gradient of a particle sums consecutive corners
+pub fn flatten_surfaces<'a>(
+    constraints: impl IntoIterator<Item = &'a SurfaceVolumeC>,
+    first_corner: u32,
+) -> (Vec<SurfaceVolume>, Vec<SurfaceCorner>) {
+    let mut surfaces = Vec::new();
+    let mut corners = Vec::new();
+    for c in constraints {
+        let mut surface_corners: Vec<_> = c
+            .triangles
+            .iter()
+            .flat_map(|t| [[t[0], t[1], t[2]], [t[1], t[2], t[0]], [t[2], t[0], t[1]]])
+            .map(|t| t.map(|i| c.particles_idx[i as usize]))
+            .collect();
+        surface_corners.sort_by_key(|t| t[0]);
+        surfaces.push(SurfaceVolume {
+            start: first_corner + corners.len() as u32,
+            len: surface_corners.len() as u32,
+            target_volume: c.pressure * c.rest_volume,
+            compliance: c.compliance,
+            lambda: 0.,
+        });
+        corners.extend(
+            surface_corners
+                .into_iter()
+                .map(|particles_idx| SurfaceCorner { particles_idx }),
+        );
+    }
+    (surfaces, corners)
+}
+
+pub struct SurfaceVolumeSolver {
+    pipeline: ComputePipeline,
+    bind_group: Option<BindGroup>,
+    results: Buffer,
+}
+
+impl SurfaceVolumeSolver {
+    pub fn new(device: &Device) -> Self {
+        let pipeline = super::shaders::create_pipeline(
+            device,
+            "surface_volume_solver",
+            [
+                BufferDesc { read_only: true },
+                BufferDesc { read_only: false },
+                BufferDesc { read_only: true },
+                BufferDesc { read_only: false },
+            ]
+            .into_iter(),
+            super::shaders::COMMON_SRC.to_string() + super::shaders::SOLVE_SURFACE_VOLUME_SRC,
+        );
+
+        let results = Self::create_results(device, 1);
+
+        Self {
+            pipeline,
+            bind_group: None,
+            results,
+        }
+    }
+
+    fn create_results(device: &Device, particles_n: u64) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Surface volume constraints results"),
+            size: Vec::<crate::ParticleConstraintDeltas>::calculate_size_for(particles_n).into(),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Makes sure there is room for the results of `particles_n` particles,
+    /// returning whether a buffer was reallocated, after which the bind group must be updated
+    pub fn reserve(&mut self, device: &Device, particles_n: u64) -> bool {
+        let size: u64 =
+            Vec::<crate::ParticleConstraintDeltas>::calculate_size_for(particles_n).into();
+        let reallocate = self.results.size() < size;
+        if reallocate {
+            self.results = Self::create_results(device, particles_n);
+        }
+        reallocate
+    }
+
+    pub fn update_bind_group(
+        &mut self,
+        device: &Device,
+        sim_params: &Buffer,
+        particles: &GrowableBuffer<Particle>,
+        surfaces: &GrowableBuffer<SurfaceVolume>,
+        corners: &GrowableBuffer<SurfaceCorner>,
+    ) {
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: sim_params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particles.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: surfaces.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: corners.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.results.as_entire_binding(),
+                },
+            ],
+        }))
+    }
+
+    pub fn prerun(&self, encoder: &mut CommandEncoder) {
+        encoder.clear_buffer(&self.results, 0, None);
+    }
+
+    /// Dispatches a workgroup per surface, which sums the volume and the gradient norms before
+    /// applying the correction
+    pub fn run<'a: 'b, 'b>(&'a self, compute_pass: &'b mut ComputePass<'a>, surfaces_n: u64) {
+        let Some(bind_group) = &self.bind_group else {
+            return;
+        };
+        if surfaces_n == 0 {
+            return;
+        }
+
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, bind_group, &[]);
+        compute_pass.dispatch_workgroups(surfaces_n as u32, 1, 1);
+    }
+
+    pub fn results(&self) -> &Buffer {
+        &self.results
+    }
+}
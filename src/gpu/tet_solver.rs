@@ -6,18 +6,23 @@ use wgpu::{
     ComputePipeline, Device,
 };
 
-use crate::TetrahedralVolumeC;
+use crate::{Particle, TetrahedralVolumeC};
 
-use super::shaders::BufferDesc;
+use super::{buffer::GrowableBuffer, lambdas::Lambdas, shaders::BufferDesc};
 
 pub struct TetSolver {
     pipeline: ComputePipeline,
+    count_pipeline: ComputePipeline,
     bind_group: Option<BindGroup>,
+    count_bind_group: Option<BindGroup>,
     results: Buffer,
+    /// Number of inverted tetrahedra found by the last count
+    inverted: Buffer,
+    lambdas: Lambdas,
 }
 
 impl TetSolver {
-    pub fn new(device: &Device, particles_n: u64) -> Self {
+    pub fn new(device: &Device) -> Self {
         let pipeline = super::shaders::create_pipeline(
             device,
             "tet_solver",
@@ -25,31 +30,72 @@ impl TetSolver {
                 BufferDesc { read_only: true },
                 BufferDesc { read_only: true },
                 BufferDesc { read_only: false },
+                BufferDesc { read_only: false },
             ]
             .into_iter(),
             super::shaders::COMMON_SRC.to_string() + super::shaders::SOLVE_TET_SRC,
         );
+        let count_pipeline = super::shaders::create_pipeline(
+            device,
+            "count_inverted",
+            [
+                BufferDesc { read_only: true },
+                BufferDesc { read_only: true },
+                BufferDesc { read_only: false },
+            ]
+            .into_iter(),
+            super::shaders::COMMON_SRC.to_string() + super::shaders::COUNT_INVERTED_SRC,
+        );
 
-        let results = device.create_buffer(&BufferDescriptor {
-            label: Some("Tet constraints results"),
-            size: Vec::<crate::ParticleConstraintDeltas>::calculate_size_for(particles_n).into(),
-            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        let results = Self::create_results(device, 1);
+
+        let inverted = device.create_buffer(&BufferDescriptor {
+            label: Some("Inverted tets"),
+            size: mem::size_of::<u32>() as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
         Self {
             pipeline,
+            count_pipeline,
             bind_group: None,
+            count_bind_group: None,
             results,
+            inverted,
+            lambdas: Lambdas::new(device, "Tet constraints multipliers"),
         }
     }
 
+    fn create_results(device: &Device, particles_n: u64) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Tet constraints results"),
+            size: Vec::<crate::ParticleConstraintDeltas>::calculate_size_for(particles_n).into(),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Makes sure there is room for the results of `particles_n` particles and the multipliers
+    /// of `constraints_n` constraints,
+    /// returning whether a buffer was reallocated, after which the bind group must be updated
+    pub fn reserve(&mut self, device: &Device, particles_n: u64, constraints_n: u64) -> bool {
+        let mut reallocated = self.lambdas.reserve(device, constraints_n);
+        let size: u64 =
+            Vec::<crate::ParticleConstraintDeltas>::calculate_size_for(particles_n).into();
+        if self.results.size() < size {
+            self.results = Self::create_results(device, particles_n);
+            reallocated = true;
+        }
+        reallocated
+    }
+
     pub fn update_bind_group(
         &mut self,
         device: &Device,
         sim_params: &Buffer,
-        particles: &Buffer,
-        tet_constraints: &Buffer,
+        particles: &GrowableBuffer<Particle>,
+        tet_constraints: &GrowableBuffer<TetrahedralVolumeC>,
     ) {
         self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: None,
@@ -61,38 +107,93 @@ impl TetSolver {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: particles.as_entire_binding(),
+                    resource: particles.binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: tet_constraints.as_entire_binding(),
+                    resource: tet_constraints.binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 3,
                     resource: self.results.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.lambdas.binding(),
+                },
             ],
-        }))
+        }));
+        self.count_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.count_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: sim_params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particles.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: tet_constraints.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.inverted.as_entire_binding(),
+                },
+            ],
+        }));
     }
     pub fn prerun(&self, encoder: &mut CommandEncoder) {
         encoder.clear_buffer(&self.results, 0, None);
     }
 
-    pub fn run<'a: 'b, 'b>(
-        &'a self,
-        compute_pass: &'b mut ComputePass<'a>,
-        tet_constraints: &Buffer,
-    ) {
+    /// Counts the tetrahedra left inverted, in a pass of its own once the step is done
+    pub fn count_inverted(&self, encoder: &mut CommandEncoder, constraints_n: u64) {
         const WORKGROUP_SIZE: u64 = 64;
-        let constraints_n = tet_constraints.size() / mem::size_of::<TetrahedralVolumeC>() as u64;
+        encoder.clear_buffer(&self.inverted, 0, None);
+        let Some(bind_group) = &self.count_bind_group else {
+            return;
+        };
+        if constraints_n == 0 {
+            return;
+        }
+        let work_groups = ((constraints_n / WORKGROUP_SIZE) + 1) as u32;
+
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("count inverted tets"),
+        });
+        cpass.set_pipeline(&self.count_pipeline);
+        cpass.set_bind_group(0, bind_group, &[]);
+        cpass.dispatch_workgroups(work_groups, 1, 1);
+    }
+
+    pub fn clear_lambdas(&self, encoder: &mut CommandEncoder) {
+        self.lambdas.clear(encoder);
+    }
+
+    pub fn run<'a: 'b, 'b>(&'a self, compute_pass: &'b mut ComputePass<'a>, constraints_n: u64) {
+        const WORKGROUP_SIZE: u64 = 64;
+        let Some(bind_group) = &self.bind_group else {
+            return;
+        };
+        if constraints_n == 0 {
+            return;
+        }
         let work_groups = ((constraints_n / WORKGROUP_SIZE) + 1) as u32;
 
         compute_pass.set_pipeline(&self.pipeline);
-        compute_pass.set_bind_group(0, self.bind_group.as_ref().unwrap(), &[]);
+        compute_pass.set_bind_group(0, bind_group, &[]);
         compute_pass.dispatch_workgroups(work_groups, 1, 1);
     }
 
     pub fn results(&self) -> &Buffer {
         &self.results
     }
+
+    pub fn inverted(&self) -> &Buffer {
+        &self.inverted
+    }
 }
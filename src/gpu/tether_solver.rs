@@ -0,0 +1,126 @@
+use encase::CalculateSizeFor;
+use wgpu::{
+    BindGroup, Buffer, BufferDescriptor, BufferUsages, CommandEncoder, ComputePass,
+    ComputePipeline, Device,
+};
+
+use crate::{Particle, TetherC};
+
+use super::{buffer::GrowableBuffer, lambdas::Lambdas, shaders::BufferDesc};
+
+pub struct TetherSolver {
+    pipeline: ComputePipeline,
+    bind_group: Option<BindGroup>,
+    results: Buffer,
+    lambdas: Lambdas,
+}
+
+impl TetherSolver {
+    pub fn new(device: &Device) -> Self {
+        let pipeline = super::shaders::create_pipeline(
+            device,
+            "tether_solver",
+            [
+                BufferDesc { read_only: true },
+                BufferDesc { read_only: true },
+                BufferDesc { read_only: false },
+                BufferDesc { read_only: false },
+            ]
+            .into_iter(),
+            super::shaders::COMMON_SRC.to_string() + super::shaders::SOLVE_TETHER_SRC,
+        );
+
+        let results = Self::create_results(device, 1);
+
+        Self {
+            pipeline,
+            bind_group: None,
+            results,
+            lambdas: Lambdas::new(device, "Tether constraints multipliers"),
+        }
+    }
+
+    fn create_results(device: &Device, particles_n: u64) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Tether constraints results"),
+            size: Vec::<crate::ParticleConstraintDeltas>::calculate_size_for(particles_n).into(),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Makes sure there is room for the results of `particles_n` particles and the multipliers
+    /// of `constraints_n` constraints,
+    /// returning whether a buffer was reallocated, after which the bind group must be updated
+    pub fn reserve(&mut self, device: &Device, particles_n: u64, constraints_n: u64) -> bool {
+        let mut reallocated = self.lambdas.reserve(device, constraints_n);
+        let size: u64 =
+            Vec::<crate::ParticleConstraintDeltas>::calculate_size_for(particles_n).into();
+        if self.results.size() < size {
+            self.results = Self::create_results(device, particles_n);
+            reallocated = true;
+        }
+        reallocated
+    }
+
+    pub fn update_bind_group(
+        &mut self,
+        device: &Device,
+        sim_params: &Buffer,
+        particles: &GrowableBuffer<Particle>,
+        tether_constraints: &GrowableBuffer<TetherC>,
+    ) {
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: sim_params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particles.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: tether_constraints.binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.results.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.lambdas.binding(),
+                },
+            ],
+        }))
+    }
+    pub fn prerun(&self, encoder: &mut CommandEncoder) {
+        encoder.clear_buffer(&self.results, 0, None);
+    }
+
+    pub fn clear_lambdas(&self, encoder: &mut CommandEncoder) {
+        self.lambdas.clear(encoder);
+    }
+
+    pub fn run<'a: 'b, 'b>(&'a self, compute_pass: &'b mut ComputePass<'a>, constraints_n: u64) {
+        const WORKGROUP_SIZE: u64 = 64;
+        let Some(bind_group) = &self.bind_group else {
+            return;
+        };
+        if constraints_n == 0 {
+            return;
+        }
+        let work_groups = ((constraints_n / WORKGROUP_SIZE) + 1) as u32;
+
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, bind_group, &[]);
+        compute_pass.dispatch_workgroups(work_groups, 1, 1);
+    }
+
+    pub fn results(&self) -> &Buffer {
+        &self.results
+    }
+}
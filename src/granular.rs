@@ -0,0 +1,106 @@
+use glam::Vec3;
+
+use crate::{non_negative, Error, Particle};
+
+/// Parameters of a granular material made of spherical grains (Macklin et al. 2014, "Unified
+/// Particle Physics for Real-Time Applications")
+#[derive(Clone, Copy, Debug)]
+pub struct GranularParams {
+    /// Radius of every grain, particles of other bodies collide with grains as if they had the
+    /// same radius
+    pub radius: f32,
+    /// Ratio of tangential to normal motion below which grains in contact stick together
+    pub static_friction: f32,
+    /// Ratio of tangential to normal correction applied to sliding grains
+    pub kinetic_friction: f32,
+    /// Ratio of the normal correction removed from the tangential motion left by friction.
+    /// Grains carry no rotation, so this damping stands in for rolling friction.
+    pub tangential_damping: f32,
+    /// Fraction of the gap closed per iteration between grains closer than the cohesion
+    /// distance, 0 for dry grains
+    pub cohesion: f32,
+    /// Gap across which grains attract each other, as a fraction of the radius
+    pub cohesion_distance: f32,
+}
+
+impl Default for GranularParams {
+    fn default() -> Self {
+        Self {
+            radius: 0.025,
+            static_friction: 0.6,
+            kinetic_friction: 0.4,
+            tangential_damping: 0.1,
+            cohesion: 0.,
+            cohesion_distance: 0.25,
+        }
+    }
+}
+
+impl GranularParams {
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        if !(self.radius > 0. && self.radius.is_finite()) {
+            return Err(Error::InvalidParameter {
+                name: "radius",
+                value: self.radius,
+            });
+        }
+        for (name, value) in [
+            ("static friction", self.static_friction),
+            ("kinetic friction", self.kinetic_friction),
+            ("tangential damping", self.tangential_damping),
+            ("cohesion", self.cohesion),
+            ("cohesion distance", self.cohesion_distance),
+        ] {
+            non_negative(name, value)?;
+        }
+        Ok(())
+    }
+
+    /// Distance between grain centers below which they interact
+    pub(crate) fn search_radius(&self) -> f32 {
+        2. * self.radius + self.cohesion_distance * self.radius
+    }
+}
+
+/// Grains, which collide with each other and with every other particle
+#[derive(Clone, Default)]
+pub struct Granular {
+    pub particles: Vec<Particle>,
+    pub params: GranularParams,
+}
+
+impl Granular {
+    /// Grains filling the box from `min` to `max`, packed one diameter apart, each with the
+    /// mass of its share of the box at the bulk density `density`
+    pub fn block(
+        min: Vec3,
+        max: Vec3,
+        density: f32,
+        params: GranularParams,
+    ) -> Result<Self, Error> {
+        params.validate()?;
+        if !(density > 0. && density.is_finite()) {
+            return Err(Error::InvalidParameter {
+                name: "density",
+                value: density,
+            });
+        }
+        let spacing = 2. * params.radius;
+        let counts = ((max - min) / spacing).floor().as_uvec3() + 1;
+        let inv_mass = 1. / (density * spacing.powi(3));
+        let mut particles = Vec::new();
+        for z in 0..counts.z {
+            for y in 0..counts.y {
+                for x in 0..counts.x {
+                    let offset = Vec3::new(x as f32, y as f32, z as f32) * spacing;
+                    particles.push(Particle::new(min + offset, inv_mass));
+                }
+            }
+        }
+        Ok(Self { particles, params })
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        self.params.validate()
+    }
+}
@@ -0,0 +1,283 @@
+use glam::{Quat, Vec3};
+
+use crate::{
+    non_negative,
+    rigid::{body_or_world, rotation_vector, solve_angular, solve_positional},
+    Error, RigidBody, RigidBodyHandle,
+};
+
+/// Anchor point and orientation of a joint in the space of one of its bodies. The joint axis
+/// is the frame's x axis and the reference for hinge angles its y axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JointFrame {
+    /// Relative to the center of mass of the body
+    pub point: Vec3,
+    pub rotation: Quat,
+}
+
+impl JointFrame {
+    /// Frame at `point` with its x axis along `axis`, both in body space
+    pub fn new(point: Vec3, axis: Vec3) -> Self {
+        Self {
+            point,
+            rotation: Quat::from_rotation_arc(Vec3::X, axis.normalize()),
+        }
+    }
+
+    /// Frame at `point` with its x axis along `axis`, both in world space, for `body` as it is
+    /// now or for the world when `None`
+    pub fn from_world(body: Option<&RigidBody>, point: Vec3, axis: Vec3) -> Self {
+        match body {
+            Some(body) => Self::new(body.world_to_local(point), body.rotation.inverse() * axis),
+            None => Self::new(point, axis),
+        }
+    }
+}
+
+/// Drives the angle of a hinge
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Motor {
+    /// Towards `target` radians
+    Angle { target: f32, compliance: f32 },
+    /// At `speed` radians per second
+    Velocity { speed: f32, compliance: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JointKind {
+    /// Anchor points kept together, free rotation
+    Ball,
+    /// Rotation about the joint axis only, with the angle of the second frame's y axis from the
+    /// first one's kept within `limits`
+    Hinge {
+        limits: Option<[f32; 2]>,
+        motor: Option<Motor>,
+    },
+    /// Sliding along the joint axis only, with the offset of the second anchor from the first
+    /// one along the axis kept within `limits`
+    Prismatic { limits: Option<[f32; 2]> },
+    /// No relative motion
+    Fixed,
+    /// Frames kept at the same orientation, free translation
+    Angular,
+}
+
+/// Joint between two rigid bodies, or between a rigid body and the world when one of the
+/// bodies is `None` (Müller et al. 2020, "Detailed Rigid Body Simulation with Extended Position
+/// Based Dynamics"). Limits are hard, `compliance` applies to the other parts of the joint.
+#[derive(Clone, Copy, Debug)]
+pub struct JointC {
+    pub(crate) bodies: [Option<RigidBodyHandle>; 2],
+    pub(crate) frames: [JointFrame; 2],
+    pub(crate) kind: JointKind,
+    pub(crate) compliance: f32,
+}
+
+impl JointC {
+    pub fn new(
+        bodies: [Option<RigidBodyHandle>; 2],
+        frames: [JointFrame; 2],
+        kind: JointKind,
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        match kind {
+            JointKind::Hinge { limits, motor } => {
+                validate_limits(limits)?;
+                if let Some(Motor::Angle { compliance, .. } | Motor::Velocity { compliance, .. }) =
+                    motor
+                {
+                    non_negative("motor compliance", compliance)?;
+                }
+            }
+            JointKind::Prismatic { limits } => validate_limits(limits)?,
+            JointKind::Ball | JointKind::Fixed | JointKind::Angular => {}
+        }
+        Ok(Self {
+            bodies,
+            frames,
+            kind,
+            compliance: non_negative("compliance", compliance)?,
+        })
+    }
+
+    pub fn ball(
+        bodies: [Option<RigidBodyHandle>; 2],
+        frames: [JointFrame; 2],
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        Self::new(bodies, frames, JointKind::Ball, compliance)
+    }
+
+    pub fn hinge(
+        bodies: [Option<RigidBodyHandle>; 2],
+        frames: [JointFrame; 2],
+        limits: Option<[f32; 2]>,
+        motor: Option<Motor>,
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        Self::new(
+            bodies,
+            frames,
+            JointKind::Hinge { limits, motor },
+            compliance,
+        )
+    }
+
+    pub fn prismatic(
+        bodies: [Option<RigidBodyHandle>; 2],
+        frames: [JointFrame; 2],
+        limits: Option<[f32; 2]>,
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        Self::new(bodies, frames, JointKind::Prismatic { limits }, compliance)
+    }
+
+    pub fn fixed(
+        bodies: [Option<RigidBodyHandle>; 2],
+        frames: [JointFrame; 2],
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        Self::new(bodies, frames, JointKind::Fixed, compliance)
+    }
+
+    pub fn angular(
+        bodies: [Option<RigidBodyHandle>; 2],
+        frames: [JointFrame; 2],
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        Self::new(bodies, frames, JointKind::Angular, compliance)
+    }
+
+    /// Angle of the hinge, or the relative rotation of the frames about the first frame's axis
+    /// for other kinds of joints
+    pub fn angle(&self, rigid_bodies: &[RigidBody]) -> f32 {
+        let [a, b] = self.bodies.map(|h| body_or_world(rigid_bodies, h));
+        self.hinge_angle(a.rotation, b.rotation)
+    }
+
+    /// Angle between the y axes of the frames about the first frame's x axis
+    fn hinge_angle(&self, rotation_a: Quat, rotation_b: Quat) -> f32 {
+        let [fa, fb] = [
+            rotation_a * self.frames[0].rotation,
+            rotation_b * self.frames[1].rotation,
+        ];
+        let axis = fa * Vec3::X;
+        let [ya, yb] = [fa * Vec3::Y, fb * Vec3::Y];
+        ya.cross(yb).dot(axis).atan2(ya.dot(yb))
+    }
+
+    pub(crate) fn solve(&self, rigid_bodies: &mut [RigidBody], delta: f32) {
+        match self.kind {
+            JointKind::Ball => {}
+            JointKind::Hinge { limits, motor } => {
+                self.align_axes(rigid_bodies, delta);
+
+                let [a, b] = self.bodies.map(|h| body_or_world(rigid_bodies, h));
+                let axis = a.rotation * self.frames[0].rotation * Vec3::X;
+                let angle = self.hinge_angle(a.rotation, b.rotation);
+                if let Some(motor) = motor {
+                    let (target, compliance) = match motor {
+                        Motor::Angle { target, compliance } => (target, compliance),
+                        Motor::Velocity { speed, compliance } => (
+                            self.hinge_angle(a.prev_rotation, b.prev_rotation) + speed * delta,
+                            compliance,
+                        ),
+                    };
+                    let error = wrap_angle(angle - target);
+                    solve_angular(rigid_bodies, self.bodies, error * axis, compliance, delta);
+                }
+                if let Some([min, max]) = limits {
+                    let [a, b] = self.bodies.map(|h| body_or_world(rigid_bodies, h));
+                    let angle = self.hinge_angle(a.rotation, b.rotation);
+                    let error = angle - angle.clamp(min, max);
+                    solve_angular(rigid_bodies, self.bodies, error * axis, 0., delta);
+                }
+            }
+            JointKind::Prismatic { .. } | JointKind::Fixed => {
+                self.lock_rotation(rigid_bodies, delta);
+            }
+            JointKind::Angular => {
+                self.lock_rotation(rigid_bodies, delta);
+                return;
+            }
+        }
+
+        let [a, b] = self.bodies.map(|h| body_or_world(rigid_bodies, h));
+        let ra = a.rotation * self.frames[0].point;
+        let rb = b.rotation * self.frames[1].point;
+        let d = (b.position + rb) - (a.position + ra);
+        match self.kind {
+            JointKind::Prismatic { limits } => {
+                let axis = a.rotation * self.frames[0].rotation * Vec3::X;
+                let offset = d.dot(axis);
+                let perpendicular = d - offset * axis;
+                solve_positional(
+                    rigid_bodies,
+                    self.bodies,
+                    [ra, rb],
+                    perpendicular,
+                    self.compliance,
+                    delta,
+                );
+                if let Some([min, max]) = limits {
+                    let error = offset - offset.clamp(min, max);
+                    solve_positional(rigid_bodies, self.bodies, [ra, rb], error * axis, 0., delta);
+                }
+            }
+            _ => solve_positional(
+                rigid_bodies,
+                self.bodies,
+                [ra, rb],
+                d,
+                self.compliance,
+                delta,
+            ),
+        }
+    }
+
+    /// Turns the bodies so the x axes of the frames line up
+    fn align_axes(&self, rigid_bodies: &mut [RigidBody], delta: f32) {
+        let [a, b] = self.bodies.map(|h| body_or_world(rigid_bodies, h));
+        let axis_a = a.rotation * self.frames[0].rotation * Vec3::X;
+        let axis_b = b.rotation * self.frames[1].rotation * Vec3::X;
+        solve_angular(
+            rigid_bodies,
+            self.bodies,
+            axis_a.cross(axis_b),
+            self.compliance,
+            delta,
+        );
+    }
+
+    /// Turns the bodies so the frames have the same orientation
+    fn lock_rotation(&self, rigid_bodies: &mut [RigidBody], delta: f32) {
+        let [a, b] = self.bodies.map(|h| body_or_world(rigid_bodies, h));
+        let fa = a.rotation * self.frames[0].rotation;
+        let fb = b.rotation * self.frames[1].rotation;
+        solve_angular(
+            rigid_bodies,
+            self.bodies,
+            rotation_vector(fb * fa.inverse()),
+            self.compliance,
+            delta,
+        );
+    }
+}
+
+fn validate_limits(limits: Option<[f32; 2]>) -> Result<(), Error> {
+    match limits {
+        Some([min, max]) if min > max || min.is_nan() || max.is_nan() => {
+            Err(Error::InvalidParameter {
+                name: "limits",
+                value: max - min,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// `angle` wrapped to [-pi, pi]
+fn wrap_angle(angle: f32) -> f32 {
+    use std::f32::consts::{PI, TAU};
+    angle - TAU * ((angle + PI) / TAU).floor()
+}
@@ -1,8 +1,39 @@
+use std::ops::{Bound, Range, RangeBounds};
+
 use encase::ShaderType;
 use glam::Vec3;
 
+mod aero;
+mod cloth;
 pub mod cpu;
+mod error;
+mod fiber;
+mod fluid;
 pub mod gpu;
+mod granular;
+mod joint;
+pub mod mass;
+mod morph;
+mod rigid;
+mod rod;
+mod shape_matching;
+mod strain;
+mod surface_volume;
+mod tether;
+
+pub use aero::{AeroTriangle, Turbulence, Wind};
+pub use cloth::ClothParams;
+pub use error::{Error, IndexError};
+pub use fiber::FiberC;
+pub use fluid::{Fluid, FluidParams};
+pub use granular::{Granular, GranularParams};
+pub use joint::{JointC, JointFrame, JointKind, Motor};
+pub use rigid::{AttachmentC, RigidBody, RigidBodyHandle};
+pub use rod::{BendTwistC, QuatParticle, Rod, StretchShearC};
+pub use shape_matching::{ShapeMatchingC, ShapeMatchingMode};
+pub use strain::TriangleStrainC;
+pub use surface_volume::SurfaceVolumeC;
+pub use tether::{TetherC, TetherDistance};
 
 #[repr(C)]
 #[derive(Clone, Copy, ShaderType)]
@@ -26,20 +57,257 @@ impl Particle {
     }
 }
 
+/// Half space `normal . x >= offset` particles are kept in
+#[derive(Clone, Copy, Debug)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub offset: f32,
+}
+
+impl Plane {
+    pub fn new(normal: Vec3, offset: f32) -> Self {
+        Self {
+            normal: normal.normalize(),
+            offset,
+        }
+    }
+}
+
+/// Parameters shared by every particle of a simulation
+#[derive(Clone, Copy, Debug)]
+pub struct WorldParams {
+    /// Acceleration applied to every particle with finite mass, `Particle::ext_acc` is added on
+    /// top of it
+    pub gravity: Vec3,
+    /// Global velocity damping rate, in 1/s
+    pub damping: f32,
+    /// Over-relaxation factor of the Jacobi solvers
+    pub jacobi_weight: f32,
+    pub substeps: u32,
+    /// Solver iterations per substep. The multipliers of compliant constraints are accumulated
+    /// over them, so more iterations converge further without making those constraints
+    /// stiffer, except for joints, attachments and rods which are solved anew each iteration.
+    pub iterations: u32,
+    pub ground: Option<Plane>,
+    /// Only used by [`cpu::CpuSimulation`]
+    pub stability: StabilityParams,
+    /// Density of the air the wind of [`AeroTriangle`]s blows in, in kg/m³
+    pub air_density: f32,
+}
+
+impl Default for WorldParams {
+    fn default() -> Self {
+        Self {
+            gravity: Vec3::new(0., 0., -9.81),
+            damping: 0.,
+            jacobi_weight: 1.5,
+            substeps: 10,
+            iterations: 1,
+            ground: Some(Plane::new(Vec3::Z, 0.)),
+            stability: Default::default(),
+            air_density: 1.2,
+        }
+    }
+}
+
+impl WorldParams {
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        for (name, value) in [("iterations", self.iterations), ("substeps", self.substeps)] {
+            if value == 0 {
+                return Err(Error::InvalidParameter { name, value: 0. });
+            }
+        }
+        if let Some(value) = self.gravity.to_array().into_iter().find(|x| !x.is_finite()) {
+            return Err(Error::InvalidParameter {
+                name: "gravity",
+                value,
+            });
+        }
+        if !(self.jacobi_weight > 0. && self.jacobi_weight.is_finite()) {
+            return Err(Error::InvalidParameter {
+                name: "jacobi weight",
+                value: self.jacobi_weight,
+            });
+        }
+        non_negative("damping", self.damping)?;
+        non_negative("air density", self.air_density)?;
+        Ok(())
+    }
+}
+
+/// When a step is considered unstable and how it is retried. The GPU simulation records its
+/// steps ahead of running them, so it neither checks them nor rolls them back.
+#[derive(Clone, Copy, Debug)]
+pub struct StabilityParams {
+    /// Particles moving faster than this make the step unstable
+    pub max_speed: f32,
+    /// Times a step is rolled back and retried, each time with twice the substeps. Retries stop
+    /// early once the substeps would overflow.
+    pub max_retries: u32,
+}
+
+impl Default for StabilityParams {
+    fn default() -> Self {
+        Self {
+            max_speed: f32::INFINITY,
+            max_retries: 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Instability {
+    NonFinite { particle_idx: u32 },
+    ExcessiveSpeed { particle_idx: u32, speed: f32 },
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct StepReport {
+    /// Substeps used by the last attempt
+    pub substeps: u32,
+    /// Cause of every attempt that was rolled back
+    pub instabilities: Vec<Instability>,
+    /// If false every attempt failed, and the simulation was left as it was before the step
+    pub stable: bool,
+    /// Enabled tetrahedra with negative volume at the end of the step, the same count
+    /// [`GpuSimulation::inverted_tets`](gpu::GpuSimulation::inverted_tets) reads back
+    pub inverted_tets: u32,
+}
+
+/// Keeps the distance between two particles within `[min_distance, max_distance]`, a single
+/// rest distance when both are equal. Inside the range the constraint is slack and applies no
+/// correction.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, ShaderType)]
 pub struct DistanceC {
     particles_idx: [u32; 2],
-    rest_distance: f32,
+    min_distance: f32,
+    max_distance: f32,
     compliance: f32,
+    damping: f32,
+    /// Disabled constraints are skipped by every solver, stored as an integer for the shaders
+    enabled: u32,
 }
 impl DistanceC {
-    pub fn new(particles_idx: [u32; 2], rest_distance: f32, compliance: f32) -> Self {
-        Self {
+    pub fn new(
+        particles_idx: [u32; 2],
+        rest_distance: f32,
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        Self::range(particles_idx, rest_distance, rest_distance, compliance)
+    }
+
+    /// Constraint only acting while the distance is outside `[min_distance, max_distance]`
+    pub fn range(
+        particles_idx: [u32; 2],
+        min_distance: f32,
+        max_distance: f32,
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        validate_distance_range(min_distance, max_distance)?;
+        Ok(Self {
             particles_idx,
-            rest_distance,
-            compliance,
+            min_distance,
+            max_distance,
+            compliance: non_negative("compliance", compliance)?,
+            damping: 0.,
+            enabled: 1,
+        })
+    }
+
+    /// Constraint only acting when stretched beyond `max_distance`, like a slack rope
+    pub fn at_most(
+        particles_idx: [u32; 2],
+        max_distance: f32,
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        Self::range(particles_idx, 0., max_distance, compliance)
+    }
+
+    /// Constraint only acting when compressed below `min_distance`, like a cushion
+    pub fn at_least(
+        particles_idx: [u32; 2],
+        min_distance: f32,
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        Self::range(particles_idx, min_distance, f32::MAX, compliance)
+    }
+
+    /// Distance between the particles
+    pub fn distance(&self, particles: &[Particle]) -> f32 {
+        let [p1, p2] = self.particles_idx.map(|i| particles[i as usize].position);
+        p1.distance(p2)
+    }
+
+    pub fn min_distance(&self) -> f32 {
+        self.min_distance
+    }
+
+    /// Whether the constraint keeps the distance in a range rather than at a rest distance
+    pub(crate) fn is_range(&self) -> bool {
+        self.min_distance < self.max_distance
+    }
+
+    pub fn max_distance(&self) -> f32 {
+        self.max_distance
+    }
+
+    /// Sets a single rest distance, replacing the range
+    pub fn set_rest_distance(&mut self, rest_distance: f32) -> Result<(), Error> {
+        self.set_range(rest_distance, rest_distance)
+    }
+
+    pub fn set_range(&mut self, min_distance: f32, max_distance: f32) -> Result<(), Error> {
+        validate_distance_range(min_distance, max_distance)?;
+        self.min_distance = min_distance;
+        self.max_distance = max_distance;
+        Ok(())
+    }
+
+    pub fn compliance(&self) -> f32 {
+        self.compliance
+    }
+
+    pub fn set_compliance(&mut self, compliance: f32) -> Result<(), Error> {
+        self.compliance = non_negative("compliance", compliance)?;
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled != 0
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled as u32;
+    }
+
+    fn offset(mut self, offset: u32) -> Self {
+        self.particles_idx = self.particles_idx.map(|i| i + offset);
+        self
+    }
+
+    /// Constraint with the distance between the `rest` positions of its particles as the rest
+    /// distance. Ranges keep their bounds.
+    fn with_rest_shape(&self, rest: &[Vec3]) -> Self {
+        let mut c = *self;
+        if self.min_distance == self.max_distance {
+            let [p1, p2] = self.particles_idx.map(|i| rest[i as usize]);
+            c.min_distance = p1.distance(p2);
+            c.max_distance = c.min_distance;
         }
+        c
+    }
+
+    fn blend_rest(&mut self, from: &Self, to: &Self, t: f32) {
+        self.min_distance = lerp(from.min_distance, to.min_distance, t);
+        self.max_distance = lerp(from.max_distance, to.max_distance, t);
+    }
+
+    /// Sets the XPBD constraint damping coefficient (beta), only has an effect on compliant
+    /// constraints. Ignored by [`crate::cpu::SolverType::ProjectiveDynamics`].
+    pub fn with_damping(mut self, damping: f32) -> Result<Self, Error> {
+        self.damping = non_negative("damping", damping)?;
+        Ok(self)
     }
 }
 
@@ -48,20 +316,399 @@ impl DistanceC {
 pub struct TetrahedralVolumeC {
     particles_idx: [u32; 4],
     rest_volume: f32,
+    /// Sum of the squared norms of the gradients in the rest shape
+    rest_gradient_norm_squared: f32,
     compliance: f32,
+    damping: f32,
+    enabled: u32,
 }
 
 impl TetrahedralVolumeC {
-    pub fn new(particles_idx: [u32; 4], rest_volume: f32, compliance: f32) -> Self {
-        assert!(rest_volume >= 0.);
-        Self {
+    /// A negative `rest_volume` is taken as the signed volume of an inverted tetrahedron, and
+    /// its orientation is fixed by swapping the last two particles. The rest shape is taken to be
+    /// a regular tetrahedron, see [`Self::from_particles`] to keep the actual one.
+    pub fn new(
+        mut particles_idx: [u32; 4],
+        mut rest_volume: f32,
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        if rest_volume < 0. {
+            particles_idx.swap(2, 3);
+            rest_volume = -rest_volume;
+        }
+        let rest_volume = non_negative("rest volume", rest_volume)?;
+        Ok(Self {
             particles_idx,
             rest_volume,
-            compliance,
+            rest_gradient_norm_squared: regular_gradient_norm_squared(rest_volume),
+            compliance: non_negative("compliance", compliance)?,
+            damping: 0.,
+            enabled: 1,
+        })
+    }
+
+    /// Constraint keeping the current volume of the particles, oriented so it is positive
+    pub fn from_particles(
+        particles_idx: [u32; 4],
+        particles: &[Particle],
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        validate_indices([&particles_idx[..]], particles.len() as u32)?;
+        let positions = particles_idx.map(|i| particles[i as usize].position);
+        let mut c = Self::new(particles_idx, signed_volume(positions), compliance)?;
+        c.rest_gradient_norm_squared = volume_gradient_norm_squared(positions);
+        Ok(c)
+    }
+
+    /// Signed volume of the tetrahedron, negative when it is inverted
+    pub fn volume(&self, particles: &[Particle]) -> f32 {
+        signed_volume(self.particles_idx.map(|i| particles[i as usize].position))
+    }
+
+    pub fn rest_volume(&self) -> f32 {
+        self.rest_volume
+    }
+
+    /// The orientation is kept from construction, so the rest volume can't be negative. The
+    /// rest shape is taken to be scaled uniformly.
+    pub fn set_rest_volume(&mut self, rest_volume: f32) -> Result<(), Error> {
+        let rest_volume = non_negative("rest volume", rest_volume)?;
+        self.rest_gradient_norm_squared = if self.rest_volume > 0. {
+            self.rest_gradient_norm_squared * (rest_volume / self.rest_volume).powf(4. / 3.)
+        } else {
+            regular_gradient_norm_squared(rest_volume)
+        };
+        self.rest_volume = rest_volume;
+        Ok(())
+    }
+
+    /// Sum of the squared norms of the gradients in the rest shape
+    pub(crate) fn rest_gradient_norm_squared(&self) -> f32 {
+        self.rest_gradient_norm_squared
+    }
+
+    pub fn compliance(&self) -> f32 {
+        self.compliance
+    }
+
+    pub fn set_compliance(&mut self, compliance: f32) -> Result<(), Error> {
+        self.compliance = non_negative("compliance", compliance)?;
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled != 0
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled as u32;
+    }
+
+    fn offset(mut self, offset: u32) -> Self {
+        self.particles_idx = self.particles_idx.map(|i| i + offset);
+        self
+    }
+
+    /// Constraint with the volume of the `rest` positions of its particles as the rest volume,
+    /// which must not be inverted
+    fn with_rest_shape(&self, rest: &[Vec3]) -> Result<Self, Error> {
+        let mut c = *self;
+        let rest = self.particles_idx.map(|i| rest[i as usize]);
+        c.set_rest_volume(signed_volume(rest))?;
+        c.rest_gradient_norm_squared = volume_gradient_norm_squared(rest);
+        Ok(c)
+    }
+
+    fn blend_rest(&mut self, from: &Self, to: &Self, t: f32) {
+        self.rest_volume = lerp(from.rest_volume, to.rest_volume, t);
+        self.rest_gradient_norm_squared = lerp(
+            from.rest_gradient_norm_squared,
+            to.rest_gradient_norm_squared,
+            t,
+        );
+    }
+
+    /// Sets the XPBD constraint damping coefficient (beta), only has an effect on compliant
+    /// constraints. Ignored by [`crate::cpu::SolverType::ProjectiveDynamics`].
+    pub fn with_damping(mut self, damping: f32) -> Result<Self, Error> {
+        self.damping = non_negative("damping", damping)?;
+        Ok(self)
+    }
+}
+
+/// Particles and the constraints between them, constraints index into `particles`
+#[derive(Clone, Default)]
+pub struct Body {
+    pub particles: Vec<Particle>,
+    pub distance_constraints: Vec<DistanceC>,
+    pub tet_constraints: Vec<TetrahedralVolumeC>,
+    pub shape_matching_constraints: Vec<ShapeMatchingC>,
+    pub triangle_strain_constraints: Vec<TriangleStrainC>,
+    pub surface_volume_constraints: Vec<SurfaceVolumeC>,
+    pub tether_constraints: Vec<TetherC>,
+    pub fiber_constraints: Vec<FiberC>,
+    pub aero_triangles: Vec<AeroTriangle>,
+}
+
+impl Body {
+    /// Checks that every constraint references particles of the body, and that masses and
+    /// damping coefficients are valid
+    pub fn validate(&self) -> Result<(), Error> {
+        for p in &self.particles {
+            non_negative("inverse mass", p.inv_mass)?;
+        }
+        self.validate_constraints(self.particles.len() as u32)
+    }
+
+    /// Validates the constraints for a body of `particles_n` particles
+    fn validate_constraints(&self, particles_n: u32) -> Result<(), Error> {
+        for damping in self
+            .distance_constraints
+            .iter()
+            .map(|c| c.damping)
+            .chain(self.tet_constraints.iter().map(|c| c.damping))
+        {
+            non_negative("damping", damping)?;
+        }
+
+        validate_indices(
+            self.distance_constraints
+                .iter()
+                .map(|c| &c.particles_idx[..]),
+            particles_n,
+        )?;
+        validate_indices(
+            self.tet_constraints.iter().map(|c| &c.particles_idx[..]),
+            particles_n,
+        )?;
+        validate_indices(
+            self.shape_matching_constraints
+                .iter()
+                .map(|c| &c.particles_idx[..]),
+            particles_n,
+        )?;
+        validate_indices(
+            self.triangle_strain_constraints
+                .iter()
+                .map(|c| &c.particles_idx[..]),
+            particles_n,
+        )?;
+        validate_indices(
+            self.surface_volume_constraints
+                .iter()
+                .map(|c| &c.particles_idx[..]),
+            particles_n,
+        )?;
+        validate_indices(
+            self.tether_constraints.iter().map(|c| &c.particles_idx[..]),
+            particles_n,
+        )?;
+        validate_indices(
+            self.fiber_constraints.iter().map(|c| &c.particles_idx[..]),
+            particles_n,
+        )?;
+        validate_indices(
+            self.aero_triangles.iter().map(|t| &t.particles_idx[..]),
+            particles_n,
+        )?;
+        Ok(())
+    }
+
+    /// Appends the constraints of `other`
+    fn extend_constraints(&mut self, other: Body) {
+        self.distance_constraints.extend(other.distance_constraints);
+        self.tet_constraints.extend(other.tet_constraints);
+        self.shape_matching_constraints
+            .extend(other.shape_matching_constraints);
+        self.triangle_strain_constraints
+            .extend(other.triangle_strain_constraints);
+        self.surface_volume_constraints
+            .extend(other.surface_volume_constraints);
+        self.tether_constraints.extend(other.tether_constraints);
+        self.fiber_constraints.extend(other.fiber_constraints);
+        self.aero_triangles.extend(other.aero_triangles);
+    }
+
+    /// Sets the particle masses from the volume of the tetrahedra of the body, see
+    /// [`mass::tet_masses`]. Pinned particles are overwritten as well, as by
+    /// [`mass::set_masses`].
+    pub fn set_masses_from_density(&mut self, density: f32) -> Result<(), Error> {
+        let tets: Vec<_> = self
+            .tet_constraints
+            .iter()
+            .map(|c| c.particles_idx)
+            .collect();
+        let masses = mass::tet_masses(&self.particles, &tets, density)?;
+        mass::set_masses(&mut self.particles, &masses);
+        Ok(())
+    }
+
+    /// Constraints with their particle indices moved by `offset`, without the particles
+    fn offset_constraints(&self, offset: u32) -> Body {
+        Body {
+            particles: Vec::new(),
+            distance_constraints: self
+                .distance_constraints
+                .iter()
+                .map(|c| c.offset(offset))
+                .collect(),
+            tet_constraints: self
+                .tet_constraints
+                .iter()
+                .map(|c| c.offset(offset))
+                .collect(),
+            shape_matching_constraints: self
+                .shape_matching_constraints
+                .iter()
+                .map(|c| c.offset(offset))
+                .collect(),
+            triangle_strain_constraints: self
+                .triangle_strain_constraints
+                .iter()
+                .map(|c| c.offset(offset))
+                .collect(),
+            surface_volume_constraints: self
+                .surface_volume_constraints
+                .iter()
+                .map(|c| c.offset(offset))
+                .collect(),
+            tether_constraints: self
+                .tether_constraints
+                .iter()
+                .map(|c| c.offset(offset))
+                .collect(),
+            fiber_constraints: self
+                .fiber_constraints
+                .iter()
+                .map(|c| c.offset(offset))
+                .collect(),
+            aero_triangles: self
+                .aero_triangles
+                .iter()
+                .map(|t| t.offset(offset))
+                .collect(),
         }
     }
 }
 
+/// Identifies a body added to a simulation, never reused after the body is removed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BodyHandle(u32);
+
+impl BodyHandle {
+    /// Handle to the particle at `index` within the body
+    pub fn particle(self, index: u32) -> ParticleHandle {
+        ParticleHandle { body: self, index }
+    }
+}
+
+/// A particle of a body, indexed locally to the body so it stays valid when other bodies are
+/// added or removed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ParticleHandle {
+    pub body: BodyHandle,
+    pub index: u32,
+}
+
+/// Global index of the particle at `index` within a body occupying `particles`
+pub(crate) fn particle_index(particles: Range<u32>, index: u32) -> Result<u32, IndexError> {
+    if index < particles.len() as u32 {
+        Ok(particles.start + index)
+    } else {
+        Err(IndexError::ParticleOutOfRange {
+            particle_idx: index,
+            particles_n: particles.len() as u32,
+        })
+    }
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+fn validate_distance_range(min_distance: f32, max_distance: f32) -> Result<(), Error> {
+    non_negative("min distance", min_distance)?;
+    if max_distance.is_nan() || max_distance < min_distance {
+        return Err(Error::InvalidParameter {
+            name: "max distance",
+            value: max_distance,
+        });
+    }
+    Ok(())
+}
+
+/// Resolves `range` over the `constraints_n` constraints of one type of a body
+pub(crate) fn constraint_range(
+    range: impl RangeBounds<u32>,
+    constraints_n: u32,
+) -> Result<Range<usize>, IndexError> {
+    let start = match range.start_bound() {
+        Bound::Included(i) => *i,
+        Bound::Excluded(i) => i.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(i) => i.saturating_add(1),
+        Bound::Excluded(i) => *i,
+        Bound::Unbounded => constraints_n,
+    };
+    if end > constraints_n {
+        return Err(IndexError::ConstraintOutOfRange {
+            constraint_idx: end - 1,
+            constraints_n,
+        });
+    }
+    Ok(start.min(end) as usize..end as usize)
+}
+
+fn signed_volume([p1, p2, p3, p4]: [Vec3; 4]) -> f32 {
+    (p2 - p1).cross(p3 - p1).dot(p4 - p1) / 6.
+}
+
+/// Sum of the squared norms of the gradients of six times the volume of a tetrahedron
+fn volume_gradient_norm_squared([p1, p2, p3, p4]: [Vec3; 4]) -> f32 {
+    [
+        (p4 - p2).cross(p3 - p2),
+        (p3 - p1).cross(p4 - p1),
+        (p4 - p1).cross(p2 - p1),
+        (p2 - p1).cross(p3 - p1),
+    ]
+    .iter()
+    .map(|g| g.length_squared())
+    .sum()
+}
+
+/// [`volume_gradient_norm_squared`] of a regular tetrahedron of volume `volume`, whose faces
+/// have an area of `sqrt(3) / 4 a^2` for edges of length `a = (6 sqrt(2) volume)^(1 / 3)`
+fn regular_gradient_norm_squared(volume: f32) -> f32 {
+    3. * (6. * 2f32.sqrt() * volume).powf(4. / 3.)
+}
+
+fn non_negative(name: &'static str, value: f32) -> Result<f32, Error> {
+    if value >= 0. && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(Error::InvalidParameter { name, value })
+    }
+}
+
+/// Checks that every index is below `particles_n`
+pub(crate) fn validate_indices<'a>(
+    particles_idx: impl IntoIterator<Item = &'a [u32]>,
+    particles_n: u32,
+) -> Result<(), IndexError> {
+    particles_idx
+        .into_iter()
+        .flatten()
+        .find(|i| **i >= particles_n)
+        .map_or(Ok(()), |particle_idx| {
+            Err(IndexError::ParticleOutOfRange {
+                particle_idx: *particle_idx,
+                particles_n,
+            })
+        })
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, ShaderType)]
 struct ParticleConstraintDeltas<const N: usize = 64> {
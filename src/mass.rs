@@ -0,0 +1,94 @@
+//! Lumped masses computed from element sizes and a density
+
+use glam::Vec3;
+
+use crate::{non_negative, signed_volume, validate_indices, Error, Particle};
+
+/// Mass of every particle, each tetrahedron giving a quarter of `density * volume` to each of
+/// its particles
+pub fn tet_masses(
+    particles: &[Particle],
+    tets: &[[u32; 4]],
+    density: f32,
+) -> Result<Vec<f32>, Error> {
+    let density = non_negative("density", density)?;
+    validate_indices(tets.iter().map(|t| &t[..]), particles.len() as u32)?;
+    let mut masses = vec![0.; particles.len()];
+    for tet in tets {
+        let volume = signed_volume(tet.map(|i| particles[i as usize].position)).abs();
+        for i in tet {
+            masses[*i as usize] += density * volume / 4.;
+        }
+    }
+    Ok(masses)
+}
+
+/// Mass of every particle of a surface of the given `thickness`, each triangle giving a third
+/// of `density * area * thickness` to each of its particles
+pub fn triangle_masses(
+    particles: &[Particle],
+    triangles: &[[u32; 3]],
+    density: f32,
+    thickness: f32,
+) -> Result<Vec<f32>, Error> {
+    let density = non_negative("density", density)?;
+    let thickness = non_negative("thickness", thickness)?;
+    validate_indices(triangles.iter().map(|t| &t[..]), particles.len() as u32)?;
+    let mut masses = vec![0.; particles.len()];
+    for tri in triangles {
+        let [p1, p2, p3] = tri.map(|i| particles[i as usize].position);
+        let area = (p2 - p1).cross(p3 - p1).length() / 2.;
+        for i in tri {
+            masses[*i as usize] += density * area * thickness / 3.;
+        }
+    }
+    Ok(masses)
+}
+
+/// Sets the inverse mass of every particle from `masses`. Particles with zero mass get an
+/// inverse mass of zero, so they don't move. Pinned particles are overwritten as well, see
+/// [`set_masses_keeping_pins`] to keep them.
+pub fn set_masses(particles: &mut [Particle], masses: &[f32]) {
+    for (p, m) in particles.iter_mut().zip(masses) {
+        p.inv_mass = inv_mass(*m);
+    }
+}
+
+/// Like [`set_masses`], but leaves the particles with an inverse mass of zero pinned
+pub fn set_masses_keeping_pins(particles: &mut [Particle], masses: &[f32]) {
+    for (p, m) in particles.iter_mut().zip(masses) {
+        if p.inv_mass > 0. {
+            p.inv_mass = inv_mass(*m);
+        }
+    }
+}
+
+fn inv_mass(mass: f32) -> f32 {
+    if mass > 0. {
+        1. / mass
+    } else {
+        0.
+    }
+}
+
+/// Sum of the masses of the particles that can move
+pub fn total_mass(particles: &[Particle]) -> f32 {
+    particles
+        .iter()
+        .filter(|p| p.inv_mass > 0.)
+        .map(|p| 1. / p.inv_mass)
+        .sum()
+}
+
+/// Center of mass of the particles that can move, `None` if there are none
+pub fn center_of_mass(particles: &[Particle]) -> Option<Vec3> {
+    let total_mass = total_mass(particles);
+    (total_mass > 0.).then(|| {
+        particles
+            .iter()
+            .filter(|p| p.inv_mass > 0.)
+            .map(|p| p.position / p.inv_mass)
+            .sum::<Vec3>()
+            / total_mass
+    })
+}
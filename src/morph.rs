@@ -0,0 +1,167 @@
+use glam::Vec3;
+
+use crate::{non_negative, Body, Error};
+
+impl Body {
+    /// Moves the rest shape of the body to `positions`, one per particle. Rest distances and
+    /// volumes, shape matching clusters and the rest shapes of triangle strain and fiber
+    /// constraints are computed from them, the material axes of the latter following the
+    /// deformation from the previous rest shape. Distance ranges and tethers are left as they
+    /// are.
+    pub fn set_rest_shape(&mut self, positions: &[Vec3]) -> Result<(), Error> {
+        validate_rest_positions(positions, self.particles.len())?;
+        let rest = self.rest_shape_constraints(positions)?;
+        self.blend_rest(&rest, &rest, 1.);
+        Ok(())
+    }
+
+    /// Copies of the constraints with the rest values of the `rest` positions, indexed like the
+    /// particles of the constraints
+    pub(crate) fn rest_shape_constraints(&self, rest: &[Vec3]) -> Result<Body, Error> {
+        Ok(Body {
+            particles: Vec::new(),
+            distance_constraints: self
+                .distance_constraints
+                .iter()
+                .map(|c| c.with_rest_shape(rest))
+                .collect(),
+            tet_constraints: self
+                .tet_constraints
+                .iter()
+                .map(|c| c.with_rest_shape(rest))
+                .collect::<Result<_, _>>()?,
+            shape_matching_constraints: self
+                .shape_matching_constraints
+                .iter()
+                .map(|c| c.with_rest_shape(rest))
+                .collect::<Result<_, _>>()?,
+            triangle_strain_constraints: self
+                .triangle_strain_constraints
+                .iter()
+                .map(|c| c.with_rest_shape(rest))
+                .collect::<Result<_, _>>()?,
+            surface_volume_constraints: self
+                .surface_volume_constraints
+                .iter()
+                .map(|c| c.with_rest_shape(rest))
+                .collect::<Result<_, _>>()?,
+            tether_constraints: self.tether_constraints.clone(),
+            fiber_constraints: self
+                .fiber_constraints
+                .iter()
+                .map(|c| c.with_rest_shape(rest))
+                .collect::<Result<_, _>>()?,
+            aero_triangles: self.aero_triangles.clone(),
+        })
+    }
+
+    /// Sets the rest values of the constraints to those of `from` blended towards those of `to`
+    /// by `t`, keeping their other parameters. Constraints added after `from` are left as they
+    /// are.
+    pub(crate) fn blend_rest(&mut self, from: &Body, to: &Body, t: f32) {
+        blend(
+            &mut self.distance_constraints,
+            &from.distance_constraints,
+            &to.distance_constraints,
+            |c, from, to| c.blend_rest(from, to, t),
+        );
+        blend(
+            &mut self.tet_constraints,
+            &from.tet_constraints,
+            &to.tet_constraints,
+            |c, from, to| c.blend_rest(from, to, t),
+        );
+        blend(
+            &mut self.shape_matching_constraints,
+            &from.shape_matching_constraints,
+            &to.shape_matching_constraints,
+            |c, from, to| c.blend_rest(from, to, t),
+        );
+        blend(
+            &mut self.triangle_strain_constraints,
+            &from.triangle_strain_constraints,
+            &to.triangle_strain_constraints,
+            |c, from, to| c.blend_rest(from, to, t),
+        );
+        blend(
+            &mut self.surface_volume_constraints,
+            &from.surface_volume_constraints,
+            &to.surface_volume_constraints,
+            |c, from, to| c.blend_rest(from, to, t),
+        );
+        blend(
+            &mut self.fiber_constraints,
+            &from.fiber_constraints,
+            &to.fiber_constraints,
+            |c, from, to| c.blend_rest(from, to, t),
+        );
+    }
+}
+
+fn blend<T>(constraints: &mut [T], from: &[T], to: &[T], blend: impl Fn(&mut T, &T, &T)) {
+    for ((c, from), to) in constraints.iter_mut().zip(from).zip(to) {
+        blend(c, from, to);
+    }
+}
+
+/// Linear blend of the rest values of the constraints of a body, from their values when it
+/// started to those of a new rest shape, over simulated time. Blending the values rather than
+/// the positions keeps every intermediate rest shape valid.
+#[derive(Clone)]
+pub(crate) struct RestMorph {
+    from: Body,
+    to: Body,
+    duration: f32,
+    elapsed: f32,
+}
+
+impl RestMorph {
+    pub fn new(from: Body, to: Body, duration: f32) -> Result<Self, Error> {
+        Ok(Self {
+            from,
+            to,
+            duration: non_negative("duration", duration)?,
+            elapsed: 0.,
+        })
+    }
+
+    /// Advances the morph by `delta` and blends the rest values of `constraints`, returning
+    /// whether it is finished
+    pub fn advance(&mut self, delta: f32, constraints: &mut Body) -> bool {
+        self.elapsed += delta;
+        self.blend(constraints) == 1.
+    }
+
+    /// Blends the rest values of `constraints` for the time elapsed so far, returning the
+    /// blend factor
+    pub fn blend(&self, constraints: &mut Body) -> f32 {
+        let t = if self.elapsed < self.duration {
+            self.elapsed / self.duration
+        } else {
+            1.
+        };
+        constraints.blend_rest(&self.from, &self.to, t);
+        t
+    }
+}
+
+/// Checks that there is a finite position for each of the `particles_n` particles of a body
+pub(crate) fn validate_rest_positions(positions: &[Vec3], particles_n: usize) -> Result<(), Error> {
+    if positions.len() != particles_n {
+        return Err(Error::InvalidParameter {
+            name: "rest positions count",
+            value: positions.len() as f32,
+        });
+    }
+    match positions
+        .iter()
+        .flat_map(|p| p.to_array())
+        .find(|x| !x.is_finite())
+    {
+        Some(value) => Err(Error::InvalidParameter {
+            name: "rest position",
+            value,
+        }),
+        None => Ok(()),
+    }
+}
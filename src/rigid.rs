@@ -0,0 +1,311 @@
+use glam::{Quat, Vec3};
+
+use crate::{non_negative, Error, Particle, ParticleHandle};
+
+/// A body that keeps its shape, described by the pose of its center of mass and its principal
+/// axes of inertia
+#[derive(Clone, Copy, Debug)]
+pub struct RigidBody {
+    prev_position: Vec3,
+    pub position: Vec3,
+    pub(crate) prev_rotation: Quat,
+    /// Rotation from the body's principal axes to world space
+    pub rotation: Quat,
+    pub velocity: Vec3,
+    pub angular_velocity: Vec3,
+    pub inv_mass: f32,
+    /// Inverse of the principal moments of inertia, in body space
+    pub inv_inertia: Vec3,
+}
+
+impl RigidBody {
+    /// Body of the given `mass` and principal moments of `inertia`. A mass of zero makes the body
+    /// immovable, like a particle with an inverse mass of zero, and a moment of zero keeps it from
+    /// turning about that axis.
+    pub fn new(position: Vec3, rotation: Quat, mass: f32, inertia: Vec3) -> Result<Self, Error> {
+        let inv = |name, v| non_negative(name, v).map(|v| if v > 0. { 1. / v } else { 0. });
+        let inv_mass = inv("mass", mass)?;
+        let inv_inertia = Vec3::new(
+            inv("inertia", inertia.x)?,
+            inv("inertia", inertia.y)?,
+            inv("inertia", inertia.z)?,
+        );
+        Ok(Self {
+            inv_mass,
+            inv_inertia: if inv_mass == 0. {
+                Vec3::ZERO
+            } else {
+                inv_inertia
+            },
+            ..Self::fixed(position, rotation)
+        })
+    }
+
+    /// Solid box of the given `mass` and `half_extents` along its principal axes
+    pub fn cuboid(
+        position: Vec3,
+        rotation: Quat,
+        mass: f32,
+        half_extents: Vec3,
+    ) -> Result<Self, Error> {
+        let e2 = half_extents * half_extents;
+        let inertia = mass / 3. * Vec3::new(e2.y + e2.z, e2.x + e2.z, e2.x + e2.y);
+        Self::new(position, rotation, mass, inertia)
+    }
+
+    /// Immovable body, used to pin joints and attachments to the world
+    pub fn fixed(position: Vec3, rotation: Quat) -> Self {
+        let rotation = rotation.normalize();
+        Self {
+            prev_position: position,
+            position,
+            prev_rotation: rotation,
+            rotation,
+            velocity: Vec3::ZERO,
+            angular_velocity: Vec3::ZERO,
+            inv_mass: 0.,
+            inv_inertia: Vec3::ZERO,
+        }
+    }
+
+    /// World position of a point given relative to the center of mass in body space
+    pub fn local_to_world(&self, point: Vec3) -> Vec3 {
+        self.position + self.rotation * point
+    }
+
+    /// Position relative to the center of mass in body space of a point in world space
+    pub fn world_to_local(&self, point: Vec3) -> Vec3 {
+        self.rotation.inverse() * (point - self.position)
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        non_negative("inverse mass", self.inv_mass)?;
+        for v in self.inv_inertia.to_array() {
+            non_negative("inverse inertia", v)?;
+        }
+        if !self.position.is_finite() || !self.rotation.is_finite() {
+            return Err(Error::InvalidParameter {
+                name: "pose",
+                value: f32::NAN,
+            });
+        }
+        Ok(())
+    }
+
+    /// Explicit step of the velocities and pose, including the gyroscopic torque
+    pub(crate) fn integrate(&mut self, gravity: Vec3, delta: f32) {
+        self.prev_position = self.position;
+        self.prev_rotation = self.rotation;
+        if self.inv_mass == 0. {
+            return;
+        }
+
+        self.velocity += gravity * delta;
+        self.position += self.velocity * delta;
+
+        let local_w = self.rotation.inverse() * self.angular_velocity;
+        let inertia = Vec3::select(
+            self.inv_inertia.cmpgt(Vec3::ZERO),
+            self.inv_inertia.recip(),
+            Vec3::ZERO,
+        );
+        let torque = -local_w.cross(inertia * local_w);
+        self.angular_velocity += self.rotation * (self.inv_inertia * torque) * delta;
+        self.rotation = rotate(self.rotation, self.angular_velocity * delta);
+    }
+
+    /// Derives the velocities from the pose change of the last substep
+    pub(crate) fn update_velocities(&mut self, delta: f32, velocity_scale: f32) {
+        if self.inv_mass == 0. {
+            return;
+        }
+        self.velocity = velocity_scale * (self.position - self.prev_position) / delta;
+        let dq = self.rotation * self.prev_rotation.inverse();
+        let w = 2. * Vec3::new(dq.x, dq.y, dq.z) / delta;
+        self.angular_velocity = velocity_scale * if dq.w >= 0. { w } else { -w };
+    }
+
+    /// Inverse inertia tensor in world space times `v`
+    fn inv_inertia_mul(&self, v: Vec3) -> Vec3 {
+        self.rotation * (self.inv_inertia * (self.rotation.inverse() * v))
+    }
+
+    /// Inverse mass seen by a positional correction along `n` at `r` from the center of mass
+    fn positional_inv_mass(&self, r: Vec3, n: Vec3) -> f32 {
+        let rn = r.cross(n);
+        self.inv_mass + rn.dot(self.inv_inertia_mul(rn))
+    }
+
+    /// Inverse mass seen by a rotation about `n`
+    fn angular_inv_mass(&self, n: Vec3) -> f32 {
+        n.dot(self.inv_inertia_mul(n))
+    }
+
+    /// Applies the positional impulse `p` at `r` from the center of mass
+    fn apply_positional(&mut self, p: Vec3, r: Vec3) {
+        self.position += p * self.inv_mass;
+        self.rotation = rotate(self.rotation, self.inv_inertia_mul(r.cross(p)));
+    }
+
+    /// Applies the angular impulse `p`
+    fn apply_angular(&mut self, p: Vec3) {
+        self.rotation = rotate(self.rotation, self.inv_inertia_mul(p));
+    }
+}
+
+/// First order update of `rotation` by the rotation vector `phi`
+pub(crate) fn rotate(rotation: Quat, phi: Vec3) -> Quat {
+    let dq = Quat::from_xyzw(phi.x, phi.y, phi.z, 0.) * rotation;
+    (rotation + dq * 0.5).normalize()
+}
+
+/// Rotation vector of the shortest rotation equivalent to `q`, exact for small angles
+pub(crate) fn rotation_vector(q: Quat) -> Vec3 {
+    let phi = 2. * Vec3::new(q.x, q.y, q.z);
+    if q.w >= 0. {
+        phi
+    } else {
+        -phi
+    }
+}
+
+/// Stand-in for the world in joints attached to a single body
+const WORLD: RigidBody = RigidBody {
+    prev_position: Vec3::ZERO,
+    position: Vec3::ZERO,
+    prev_rotation: Quat::IDENTITY,
+    rotation: Quat::IDENTITY,
+    velocity: Vec3::ZERO,
+    angular_velocity: Vec3::ZERO,
+    inv_mass: 0.,
+    inv_inertia: Vec3::ZERO,
+};
+
+/// The body of `handle`, or the world for `None`
+pub(crate) fn body_or_world(
+    rigid_bodies: &[RigidBody],
+    handle: Option<RigidBodyHandle>,
+) -> RigidBody {
+    handle.map_or(WORLD, |h| rigid_bodies[h.0 as usize])
+}
+
+/// Moves the points at `r` from the centers of mass of two bodies to close the gap `d` from the
+/// first point to the second
+pub(crate) fn solve_positional(
+    rigid_bodies: &mut [RigidBody],
+    handles: [Option<RigidBodyHandle>; 2],
+    r: [Vec3; 2],
+    d: Vec3,
+    compliance: f32,
+    delta: f32,
+) {
+    let c = d.length();
+    if c == 0. {
+        return;
+    }
+    let n = d / c;
+
+    let [a, b] = handles.map(|h| body_or_world(rigid_bodies, h));
+    let w = a.positional_inv_mass(r[0], n) + b.positional_inv_mass(r[1], n);
+    let alpha = compliance / (delta * delta);
+    if w + alpha == 0. {
+        return;
+    }
+    let p = c / (w + alpha) * n;
+
+    if let Some(h) = handles[0] {
+        rigid_bodies[h.0 as usize].apply_positional(p, r[0]);
+    }
+    if let Some(h) = handles[1] {
+        rigid_bodies[h.0 as usize].apply_positional(-p, r[1]);
+    }
+}
+
+/// Rotates the first body by `phi` and the second by `-phi`, weighted by their inertia
+pub(crate) fn solve_angular(
+    rigid_bodies: &mut [RigidBody],
+    handles: [Option<RigidBodyHandle>; 2],
+    phi: Vec3,
+    compliance: f32,
+    delta: f32,
+) {
+    let theta = phi.length();
+    if theta == 0. {
+        return;
+    }
+    let n = phi / theta;
+
+    let [a, b] = handles.map(|h| body_or_world(rigid_bodies, h));
+    let w = a.angular_inv_mass(n) + b.angular_inv_mass(n);
+    let alpha = compliance / (delta * delta);
+    if w + alpha == 0. {
+        return;
+    }
+    let p = theta / (w + alpha) * n;
+
+    if let Some(h) = handles[0] {
+        rigid_bodies[h.0 as usize].apply_angular(p);
+    }
+    if let Some(h) = handles[1] {
+        rigid_bodies[h.0 as usize].apply_angular(-p);
+    }
+}
+
+/// Identifies a rigid body added to a simulation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RigidBodyHandle(pub(crate) u32);
+
+/// Ties a particle of a soft body to a point of a rigid body, pulling on both
+#[derive(Clone, Copy, Debug)]
+pub struct AttachmentC {
+    pub(crate) particle: ParticleHandle,
+    pub(crate) body: RigidBodyHandle,
+    /// Point relative to the center of mass of the body, in body space
+    pub(crate) local_point: Vec3,
+    pub(crate) compliance: f32,
+}
+
+impl AttachmentC {
+    pub fn new(
+        particle: ParticleHandle,
+        body: RigidBodyHandle,
+        local_point: Vec3,
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            particle,
+            body,
+            local_point,
+            compliance: non_negative("compliance", compliance)?,
+        })
+    }
+
+    /// `particle_idx` is the global index of the attached particle
+    pub(crate) fn solve(
+        &self,
+        particle_idx: u32,
+        particles: &mut [Particle],
+        rigid_bodies: &mut [RigidBody],
+        delta: f32,
+    ) {
+        let particle = &mut particles[particle_idx as usize];
+        let body = &mut rigid_bodies[self.body.0 as usize];
+        let r = body.rotation * self.local_point;
+        let d = particle.position - (body.position + r);
+        let c = d.length();
+        if c == 0. {
+            return;
+        }
+        let n = d / c;
+
+        let w = particle.inv_mass + body.positional_inv_mass(r, n);
+        let alpha = self.compliance / (delta * delta);
+        if w + alpha == 0. {
+            return;
+        }
+        let lambda = c / (w + alpha);
+
+        particle.position -= lambda * particle.inv_mass * n;
+        body.apply_positional(lambda * n, r);
+    }
+}
@@ -0,0 +1,316 @@
+use std::f32::consts::PI;
+
+use glam::{Quat, Vec3};
+
+use crate::{
+    mass, non_negative,
+    rigid::{rotate, rotation_vector},
+    validate_indices, Error, Particle,
+};
+
+/// Orientation of a rod segment, the segment's direction being the rotated z axis
+#[derive(Clone, Copy, Debug)]
+pub struct QuatParticle {
+    prev_rotation: Quat,
+    pub rotation: Quat,
+    angular_velocity: Vec3,
+    /// Inverse of the moment of inertia of the segment about an axis through its center
+    pub inv_inertia: f32,
+}
+
+impl QuatParticle {
+    pub fn new(rotation: Quat, inv_inertia: f32) -> Self {
+        let rotation = rotation.normalize();
+        Self {
+            prev_rotation: rotation,
+            rotation,
+            angular_velocity: Vec3::ZERO,
+            inv_inertia,
+        }
+    }
+
+    pub fn angular_velocity(&self) -> Vec3 {
+        self.angular_velocity
+    }
+
+    /// Inverse mass of the quaternion coordinates, which turn twice as fast as the segment
+    fn inv_mass(&self) -> f32 {
+        self.inv_inertia / 4.
+    }
+
+    pub(crate) fn integrate(&mut self, delta: f32) {
+        self.prev_rotation = self.rotation;
+        self.rotation = rotate(self.rotation, self.angular_velocity * delta);
+    }
+
+    pub(crate) fn update_velocity(&mut self, delta: f32, velocity_scale: f32) {
+        self.angular_velocity =
+            velocity_scale * rotation_vector(self.rotation * self.prev_rotation.inverse()) / delta;
+    }
+}
+
+/// Keeps a rod segment at its rest length and its orientation along the segment (Kugelstadt
+/// and Schömer 2016, "Position and Orientation Based Cosserat Rods")
+#[derive(Clone, Copy, Debug)]
+pub struct StretchShearC {
+    pub(crate) particles_idx: [u32; 2],
+    pub(crate) orientation_idx: u32,
+    pub(crate) rest_length: f32,
+    pub(crate) compliance: f32,
+}
+
+impl StretchShearC {
+    pub fn new(
+        particles_idx: [u32; 2],
+        orientation_idx: u32,
+        rest_length: f32,
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        if !(rest_length > 0. && rest_length.is_finite()) {
+            return Err(Error::InvalidParameter {
+                name: "rest length",
+                value: rest_length,
+            });
+        }
+        Ok(Self {
+            particles_idx,
+            orientation_idx,
+            rest_length,
+            compliance: non_negative("compliance", compliance)?,
+        })
+    }
+
+    fn offset(&self, particles: u32, orientations: u32) -> Self {
+        Self {
+            particles_idx: self.particles_idx.map(|i| i + particles),
+            orientation_idx: self.orientation_idx + orientations,
+            ..*self
+        }
+    }
+
+    pub(crate) fn solve(
+        &self,
+        particles: &mut [Particle],
+        orientations: &mut [QuatParticle],
+        delta: f32,
+    ) {
+        let [a, b] = self.particles_idx.map(|i| particles[i as usize]);
+        let q = orientations[self.orientation_idx as usize];
+        let l = self.rest_length;
+
+        let gamma = (b.position - a.position) / l - q.rotation * Vec3::Z;
+        let w = (a.inv_mass + b.inv_mass) / (l * l)
+            + 4. * q.inv_mass()
+            + self.compliance / (delta * delta);
+        if w == 0. {
+            return;
+        }
+        let gamma = gamma / w;
+
+        particles[self.particles_idx[0] as usize].position += a.inv_mass / l * gamma;
+        particles[self.particles_idx[1] as usize].position -= b.inv_mass / l * gamma;
+        // q * conjugate(e3), e3 being the z axis as a quaternion
+        let q_e3_bar = q.rotation * Quat::from_xyzw(0., 0., -1., 0.);
+        let dq = Quat::from_xyzw(gamma.x, gamma.y, gamma.z, 0.) * q_e3_bar * (2. * q.inv_mass());
+        orientations[self.orientation_idx as usize].rotation = (q.rotation + dq).normalize();
+    }
+}
+
+/// Keeps the relative rotation of two neighbouring rod segments at its rest value, resisting
+/// bending about the segments' x and y axes and twisting about their z axis
+#[derive(Clone, Copy, Debug)]
+pub struct BendTwistC {
+    pub(crate) orientations_idx: [u32; 2],
+    /// Rest Darboux vector, the relative rotation of the segments
+    pub(crate) rest_darboux: Quat,
+    /// Compliance of bending about x and y and of twisting about z
+    pub(crate) compliance: Vec3,
+}
+
+impl BendTwistC {
+    pub fn new(
+        orientations_idx: [u32; 2],
+        rest_darboux: Quat,
+        compliance: Vec3,
+    ) -> Result<Self, Error> {
+        for c in compliance.to_array() {
+            non_negative("compliance", c)?;
+        }
+        let rest_darboux = rest_darboux.normalize();
+        // Of the two quaternions of the rotation, pick the one closest to the identity
+        let rest_darboux = if (rest_darboux - Quat::IDENTITY).length_squared()
+            > (rest_darboux + Quat::IDENTITY).length_squared()
+        {
+            -rest_darboux
+        } else {
+            rest_darboux
+        };
+        Ok(Self {
+            orientations_idx,
+            rest_darboux,
+            compliance,
+        })
+    }
+
+    /// Constraint keeping the current relative rotation of the segments
+    pub fn from_orientations(
+        orientations_idx: [u32; 2],
+        orientations: &[QuatParticle],
+        compliance: Vec3,
+    ) -> Result<Self, Error> {
+        validate_indices([&orientations_idx[..]], orientations.len() as u32)?;
+        let [q0, q1] = orientations_idx.map(|i| orientations[i as usize].rotation);
+        Self::new(orientations_idx, q0.conjugate() * q1, compliance)
+    }
+
+    fn offset(&self, orientations: u32) -> Self {
+        Self {
+            orientations_idx: self.orientations_idx.map(|i| i + orientations),
+            ..*self
+        }
+    }
+
+    pub(crate) fn solve(&self, orientations: &mut [QuatParticle], delta: f32) {
+        let [q0, q1] = self.orientations_idx.map(|i| orientations[i as usize]);
+        let darboux = q0.rotation.conjugate() * q1.rotation;
+        let minus = darboux - self.rest_darboux;
+        let plus = darboux + self.rest_darboux;
+        let omega = if minus.length_squared() > plus.length_squared() {
+            plus
+        } else {
+            minus
+        };
+
+        let w = q0.inv_mass() + q1.inv_mass();
+        let alpha = self.compliance / (delta * delta);
+        let omega = Vec3::new(omega.x, omega.y, omega.z) / (w + alpha);
+        if !omega.is_finite() {
+            return;
+        }
+        let omega = Quat::from_xyzw(omega.x, omega.y, omega.z, 0.);
+
+        let [i0, i1] = self.orientations_idx.map(|i| i as usize);
+        orientations[i0].rotation = (q0.rotation + q1.rotation * omega * q0.inv_mass()).normalize();
+        orientations[i1].rotation = (q1.rotation - q0.rotation * omega * q1.inv_mass()).normalize();
+    }
+}
+
+/// A Cosserat rod: particles along its centerline and an orientation per segment
+#[derive(Clone, Default)]
+pub struct Rod {
+    pub particles: Vec<Particle>,
+    pub orientations: Vec<QuatParticle>,
+    pub stretch_shear_constraints: Vec<StretchShearC>,
+    pub bend_twist_constraints: Vec<BendTwistC>,
+}
+
+impl Rod {
+    /// Rod through `points` with a circular cross section of `radius`, its segments' frames
+    /// parallel transported along it so it starts untwisted
+    pub fn from_polyline(
+        points: &[Vec3],
+        radius: f32,
+        density: f32,
+        stretch_shear_compliance: f32,
+        bend_twist_compliance: Vec3,
+    ) -> Result<Self, Error> {
+        if points.len() < 2 {
+            return Err(Error::InvalidParameter {
+                name: "polyline points",
+                value: points.len() as f32,
+            });
+        }
+        // Segments without mass or rotational inertia can't be simulated
+        for (name, value) in [("radius", radius), ("density", density)] {
+            if !(value > 0. && value.is_finite()) {
+                return Err(Error::InvalidParameter { name, value });
+            }
+        }
+
+        let mut masses = vec![0.; points.len()];
+        let mut orientations = Vec::with_capacity(points.len() - 1);
+        let mut stretch_shear_constraints = Vec::with_capacity(points.len() - 1);
+        let mut rotation = Quat::IDENTITY;
+        let mut prev_dir = Vec3::Z;
+        for (i, segment) in points.windows(2).enumerate() {
+            let length = segment[0].distance(segment[1]);
+            let dir = (segment[1] - segment[0]) / length;
+            stretch_shear_constraints.push(StretchShearC::new(
+                [i as u32, i as u32 + 1],
+                i as u32,
+                length,
+                stretch_shear_compliance,
+            )?);
+
+            let mass = density * PI * radius * radius * length;
+            masses[i] += mass / 2.;
+            masses[i + 1] += mass / 2.;
+
+            rotation = Quat::from_rotation_arc(prev_dir, dir) * rotation;
+            prev_dir = dir;
+            let inertia = mass * (3. * radius * radius + length * length) / 12.;
+            orientations.push(QuatParticle::new(rotation, 1. / inertia));
+        }
+
+        let mut particles: Vec<_> = points.iter().map(|p| Particle::new(*p, 0.)).collect();
+        mass::set_masses(&mut particles, &masses);
+
+        let bend_twist_constraints = (0..orientations.len() as u32 - 1)
+            .map(|i| {
+                BendTwistC::from_orientations([i, i + 1], &orientations, bend_twist_compliance)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            particles,
+            orientations,
+            stretch_shear_constraints,
+            bend_twist_constraints,
+        })
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        for q in &self.orientations {
+            non_negative("inverse inertia", q.inv_inertia)?;
+        }
+        let particles_n = self.particles.len() as u32;
+        let orientations_n = self.orientations.len() as u32;
+        validate_indices(
+            self.stretch_shear_constraints
+                .iter()
+                .map(|c| &c.particles_idx[..]),
+            particles_n,
+        )?;
+        validate_indices(
+            self.stretch_shear_constraints
+                .iter()
+                .map(|c| std::slice::from_ref(&c.orientation_idx)),
+            orientations_n,
+        )?;
+        validate_indices(
+            self.bend_twist_constraints
+                .iter()
+                .map(|c| &c.orientations_idx[..]),
+            orientations_n,
+        )?;
+        Ok(())
+    }
+
+    /// Constraints with their particle and orientation indices moved by the given offsets
+    pub(crate) fn offset_constraints(
+        &self,
+        particles: u32,
+        orientations: u32,
+    ) -> (Vec<StretchShearC>, Vec<BendTwistC>) {
+        (
+            self.stretch_shear_constraints
+                .iter()
+                .map(|c| c.offset(particles, orientations))
+                .collect(),
+            self.bend_twist_constraints
+                .iter()
+                .map(|c| c.offset(orientations))
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,365 @@
+use glam::{Mat3, Quat, Vec3};
+
+use crate::{lerp, validate_indices, Error, Particle};
+
+/// How far a shape matching cluster may deform away from a rotation of its rest shape
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShapeMatchingMode {
+    Rigid,
+    /// Blends the rotation with the best fitting volume preserving linear transform, `beta` going
+    /// from 0 (rigid) to 1
+    Linear {
+        beta: f32,
+    },
+    /// Like `Linear`, but also allows quadratic deformations such as bending and twisting
+    Quadratic {
+        beta: f32,
+    },
+}
+
+/// Pulls a cluster of particles towards the best fitting transform of its rest shape
+/// (Müller et al. 2005, "Meshless Deformations Based on Shape Matching")
+#[derive(Clone, Debug)]
+pub struct ShapeMatchingC {
+    pub(crate) particles_idx: Vec<u32>,
+    /// Masses at construction, zero for particles with infinite mass
+    pub(crate) masses: Vec<f32>,
+    /// Rest positions relative to the rest center of mass
+    pub(crate) rest_offsets: Vec<Vec3>,
+    pub(crate) stiffness: f32,
+    pub(crate) mode: ShapeMatchingMode,
+    /// Inverse of the rest shape covariance, 3x3 in linear mode and 9x9 in quadratic mode, column
+    /// major
+    pub(crate) aqq_inv: Vec<f32>,
+}
+
+impl ShapeMatchingC {
+    /// Cluster of `particles_idx` keeping their current positions as the rest shape. `stiffness`
+    /// goes from 0 to 1, 1 moving the particles all the way to their goal every iteration. The
+    /// Jacobi solvers overshoot unless `stiffness * jacobi_weight` stays below 4/3.
+    pub fn new(
+        particles_idx: Vec<u32>,
+        particles: &[Particle],
+        stiffness: f32,
+        mode: ShapeMatchingMode,
+    ) -> Result<Self, Error> {
+        validate_indices([&particles_idx[..]], particles.len() as u32)?;
+        if !(0. ..=1.).contains(&stiffness) {
+            return Err(Error::InvalidParameter {
+                name: "stiffness",
+                value: stiffness,
+            });
+        }
+        if let ShapeMatchingMode::Linear { beta } | ShapeMatchingMode::Quadratic { beta } = mode {
+            if !(0. ..=1.).contains(&beta) {
+                return Err(Error::InvalidParameter {
+                    name: "beta",
+                    value: beta,
+                });
+            }
+        }
+
+        let masses: Vec<_> = particles_idx
+            .iter()
+            .map(|i| {
+                let inv_mass = particles[*i as usize].inv_mass;
+                if inv_mass > 0. {
+                    1. / inv_mass
+                } else {
+                    0.
+                }
+            })
+            .collect();
+        let positions: Vec<_> = particles_idx
+            .iter()
+            .map(|i| particles[*i as usize].position)
+            .collect();
+        let (rest_offsets, aqq_inv) = rest_shape(&positions, &masses, mode)?;
+
+        Ok(Self {
+            particles_idx,
+            masses,
+            rest_offsets,
+            stiffness,
+            mode,
+            aqq_inv,
+        })
+    }
+
+    pub(crate) fn offset(&self, offset: u32) -> Self {
+        Self {
+            particles_idx: self.particles_idx.iter().map(|i| i + offset).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Cluster with the `rest` positions of its particles as the rest shape
+    pub(crate) fn with_rest_shape(&self, rest: &[Vec3]) -> Result<Self, Error> {
+        let positions: Vec<_> = self
+            .particles_idx
+            .iter()
+            .map(|i| rest[*i as usize])
+            .collect();
+        let (rest_offsets, aqq_inv) = rest_shape(&positions, &self.masses, self.mode)?;
+        Ok(Self {
+            rest_offsets,
+            aqq_inv,
+            ..self.clone()
+        })
+    }
+
+    pub(crate) fn blend_rest(&mut self, from: &Self, to: &Self, t: f32) {
+        for ((q, from), to) in self
+            .rest_offsets
+            .iter_mut()
+            .zip(&from.rest_offsets)
+            .zip(&to.rest_offsets)
+        {
+            *q = from.lerp(*to, t);
+        }
+        // The inverse covariance is not linear in the offsets, so it is only blended when the
+        // blended shape is degenerate
+        match inverse_covariance(&self.rest_offsets, &self.masses, self.mode) {
+            Ok(aqq_inv) => self.aqq_inv = aqq_inv,
+            Err(_) => {
+                for ((a, from), to) in self.aqq_inv.iter_mut().zip(&from.aqq_inv).zip(&to.aqq_inv) {
+                    *a = lerp(*from, *to, t);
+                }
+            }
+        }
+    }
+
+    /// Goal position of every particle of the cluster
+    pub(crate) fn goals(&self, particles: &[Particle]) -> Vec<Vec3> {
+        let positions: Vec<_> = self
+            .particles_idx
+            .iter()
+            .map(|i| particles[*i as usize].position)
+            .collect();
+        let total_mass: f32 = self.masses.iter().sum();
+        let center = center_of_mass(&positions, &self.masses, total_mass);
+
+        let apq = positions
+            .iter()
+            .zip(&self.rest_offsets)
+            .zip(&self.masses)
+            .fold(Mat3::ZERO, |acc, ((p, q), m)| {
+                acc + outer(*p - center, *q) * *m
+            });
+        let rotation = extract_rotation(apq);
+
+        match self.mode {
+            ShapeMatchingMode::Rigid => self
+                .rest_offsets
+                .iter()
+                .map(|q| rotation * *q + center)
+                .collect(),
+            ShapeMatchingMode::Linear { beta } => {
+                let mut a = apq * Mat3::from_cols_slice(&self.aqq_inv);
+                let det = a.determinant();
+                if det > 0. {
+                    a *= 1. / det.cbrt();
+                }
+                let transform = a * beta + rotation * (1. - beta);
+                self.rest_offsets
+                    .iter()
+                    .map(|q| transform * *q + center)
+                    .collect()
+            }
+            ShapeMatchingMode::Quadratic { beta } => {
+                // Apq~, 3x9 column major
+                let mut apq = [Vec3::ZERO; 9];
+                for ((p, q), m) in positions.iter().zip(&self.rest_offsets).zip(&self.masses) {
+                    for (a, q) in apq.iter_mut().zip(quadratic_terms(*q)) {
+                        *a += *m * q * (*p - center);
+                    }
+                }
+                let mut transform = [Vec3::ZERO; 9];
+                for (col, t) in transform.iter_mut().enumerate() {
+                    let a: Vec3 = (0..9).map(|k| apq[k] * self.aqq_inv[col * 9 + k]).sum();
+                    let r = if col < 3 {
+                        rotation.col(col)
+                    } else {
+                        Vec3::ZERO
+                    };
+                    *t = beta * a + (1. - beta) * r;
+                }
+                self.rest_offsets
+                    .iter()
+                    .map(|q| {
+                        transform
+                            .iter()
+                            .zip(quadratic_terms(*q))
+                            .map(|(t, q)| *t * q)
+                            .sum::<Vec3>()
+                            + center
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Rest positions relative to the rest center of mass and the inverse rest covariance
+fn rest_shape(
+    positions: &[Vec3],
+    masses: &[f32],
+    mode: ShapeMatchingMode,
+) -> Result<(Vec<Vec3>, Vec<f32>), Error> {
+    let total_mass: f32 = masses.iter().sum();
+    if total_mass <= 0. {
+        return Err(Error::InvalidParameter {
+            name: "cluster mass",
+            value: total_mass,
+        });
+    }
+    let center = center_of_mass(positions, masses, total_mass);
+    let rest_offsets: Vec<_> = positions.iter().map(|p| *p - center).collect();
+    let aqq_inv = inverse_covariance(&rest_offsets, masses, mode)?;
+    Ok((rest_offsets, aqq_inv))
+}
+
+/// Inverse of the covariance of the rest offsets in the terms fitted by `mode`, empty for rigid
+/// clusters
+fn inverse_covariance(
+    rest_offsets: &[Vec3],
+    masses: &[f32],
+    mode: ShapeMatchingMode,
+) -> Result<Vec<f32>, Error> {
+    Ok(match mode {
+        ShapeMatchingMode::Rigid => Vec::new(),
+        ShapeMatchingMode::Linear { .. } => {
+            let aqq = rest_offsets
+                .iter()
+                .zip(masses)
+                .fold(Mat3::ZERO, |acc, (q, m)| acc + outer(*q, *q) * *m);
+            if aqq.determinant().abs() <= f32::EPSILON {
+                return Err(Error::InvalidParameter {
+                    name: "rest shape covariance determinant",
+                    value: aqq.determinant(),
+                });
+            }
+            aqq.inverse().to_cols_array().to_vec()
+        }
+        ShapeMatchingMode::Quadratic { .. } => {
+            let mut aqq = [[0f64; 9]; 9];
+            for (q, m) in rest_offsets.iter().zip(masses) {
+                let q = quadratic_terms(*q);
+                for (col, qc) in aqq.iter_mut().zip(q) {
+                    for (a, qr) in col.iter_mut().zip(q) {
+                        *a += (*m * qr * qc) as f64;
+                    }
+                }
+            }
+            invert(aqq)
+                .ok_or(Error::InvalidParameter {
+                    name: "rest shape quadratic covariance determinant",
+                    value: 0.,
+                })?
+                .iter()
+                .flatten()
+                .map(|v| *v as f32)
+                .collect()
+        }
+    })
+}
+
+fn center_of_mass(positions: &[Vec3], masses: &[f32], total_mass: f32) -> Vec3 {
+    positions
+        .iter()
+        .zip(masses)
+        .map(|(p, m)| *p * *m)
+        .sum::<Vec3>()
+        / total_mass
+}
+
+fn outer(a: Vec3, b: Vec3) -> Mat3 {
+    Mat3::from_cols(a * b.x, a * b.y, a * b.z)
+}
+
+/// `[x, y, z, x^2, y^2, z^2, xy, yz, zx]`
+fn quadratic_terms(q: Vec3) -> [f32; 9] {
+    [
+        q.x,
+        q.y,
+        q.z,
+        q.x * q.x,
+        q.y * q.y,
+        q.z * q.z,
+        q.x * q.y,
+        q.y * q.z,
+        q.z * q.x,
+    ]
+}
+
+/// Gauss-Jordan inversion with partial pivoting, `None` if the matrix is singular
+fn invert<const N: usize>(mut a: [[f64; N]; N]) -> Option<[[f64; N]; N]> {
+    let mut inv = [[0.; N]; N];
+    for (i, row) in inv.iter_mut().enumerate() {
+        row[i] = 1.;
+    }
+    let scale = a.iter().flatten().fold(0f64, |m, v| m.max(v.abs()));
+    for col in 0..N {
+        let pivot = (col..N).max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))?;
+        if a[pivot][col].abs() <= scale * 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        inv.swap(col, pivot);
+        let d = a[col][col];
+        for k in 0..N {
+            a[col][k] /= d;
+            inv[col][k] /= d;
+        }
+        for row in 0..N {
+            if row != col {
+                let f = a[row][col];
+                for k in 0..N {
+                    a[row][k] -= f * a[col][k];
+                    inv[row][k] -= f * inv[col][k];
+                }
+            }
+        }
+    }
+    Some(inv)
+}
+
+/// Rotational part of `a`, found iteratively (Müller et al. 2016, "A Robust Method to Extract the
+/// Rotational Part of Deformations") starting from its Gram-Schmidt orthonormalization. Columns
+/// that are mostly rounding error, as in flat clusters, start from the identity instead.
+pub(crate) fn extract_rotation(a: Mat3) -> Mat3 {
+    let eps = 1e-4
+        * a.x_axis
+            .length()
+            .max(a.y_axis.length())
+            .max(a.z_axis.length());
+    let normalize = |v: Vec3| {
+        if v.length() > eps {
+            v.normalize()
+        } else {
+            Vec3::ZERO
+        }
+    };
+    let x = normalize(a.x_axis);
+    let y = normalize(a.y_axis - x * x.dot(a.y_axis));
+    let guess = Mat3::from_cols(x, y, x.cross(y));
+    let mut q = if guess.determinant() > 0.5 {
+        Quat::from_mat3(&guess).normalize()
+    } else {
+        Quat::IDENTITY
+    };
+
+    for _ in 0..20 {
+        let r = Mat3::from_quat(q);
+        let omega = (r.x_axis.cross(a.x_axis)
+            + r.y_axis.cross(a.y_axis)
+            + r.z_axis.cross(a.z_axis))
+            / ((r.x_axis.dot(a.x_axis) + r.y_axis.dot(a.y_axis) + r.z_axis.dot(a.z_axis)).abs()
+                + 1e-9);
+        if omega.length_squared() < 1e-18 {
+            break;
+        }
+        q = (Quat::from_scaled_axis(omega) * q).normalize();
+    }
+    Mat3::from_quat(q)
+}
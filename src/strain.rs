@@ -0,0 +1,158 @@
+use encase::ShaderType;
+use glam::{Mat2, Vec2, Vec3};
+
+use crate::{lerp, non_negative, validate_indices, Error, Particle};
+
+/// Keeps the Green strain of a triangle relative to its rest shape at zero, resisting stretch
+/// along the two material axes and shear between them (Müller et al. 2014, "Strain Based
+/// Dynamics"). Compliances are per unit of rest area, so the response does not depend on how
+/// a surface is triangulated.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, ShaderType)]
+pub struct TriangleStrainC {
+    pub(crate) particles_idx: [u32; 3],
+    /// Inverse of the rest edge vectors in the material frame
+    pub(crate) inv_rest: Mat2,
+    pub(crate) rest_area: f32,
+    /// Compliance of stretching along the warp and weft axes and of shearing between them
+    pub(crate) compliance: Vec3,
+}
+
+impl TriangleStrainC {
+    /// Constraint with `rest` as the rest positions of the particles, the warp axis being
+    /// `warp_direction` projected into the plane of the triangle and the weft axis
+    /// perpendicular to it in that plane
+    pub fn new(
+        particles_idx: [u32; 3],
+        rest: [Vec3; 3],
+        warp_direction: Vec3,
+        compliance: Vec3,
+    ) -> Result<Self, Error> {
+        for c in compliance.to_array() {
+            non_negative("compliance", c)?;
+        }
+        let [e1, e2] = [rest[1] - rest[0], rest[2] - rest[0]];
+        let normal = e1.cross(e2).normalize_or_zero();
+        let warp = (warp_direction - normal * normal.dot(warp_direction))
+            .try_normalize()
+            .unwrap_or(e1.normalize_or_zero());
+        let weft = normal.cross(warp);
+
+        let rest_matrix = Mat2::from_cols(
+            Vec2::new(e1.dot(warp), e1.dot(weft)),
+            Vec2::new(e2.dot(warp), e2.dot(weft)),
+        );
+        let rest_area = rest_matrix.determinant() / 2.;
+        if !(rest_area > f32::EPSILON * e1.length_squared().max(e2.length_squared())
+            && rest_area.is_finite())
+        {
+            return Err(Error::InvalidParameter {
+                name: "rest area",
+                value: rest_area,
+            });
+        }
+        Ok(Self {
+            particles_idx,
+            inv_rest: rest_matrix.inverse(),
+            rest_area,
+            compliance,
+        })
+    }
+
+    /// Constraint keeping the current shape of the triangle
+    pub fn from_particles(
+        particles_idx: [u32; 3],
+        particles: &[Particle],
+        warp_direction: Vec3,
+        compliance: Vec3,
+    ) -> Result<Self, Error> {
+        validate_indices([&particles_idx[..]], particles.len() as u32)?;
+        let rest = particles_idx.map(|i| particles[i as usize].position);
+        Self::new(particles_idx, rest, warp_direction, compliance)
+    }
+
+    pub(crate) fn offset(mut self, offset: u32) -> Self {
+        self.particles_idx = self.particles_idx.map(|i| i + offset);
+        self
+    }
+
+    /// Constraint with the `rest` positions of its particles as the rest shape, the warp axis
+    /// following the deformation from the previous rest shape
+    pub(crate) fn with_rest_shape(&self, rest: &[Vec3]) -> Result<Self, Error> {
+        let rest = self.particles_idx.map(|i| rest[i as usize]);
+        let [e1, e2] = [rest[1] - rest[0], rest[2] - rest[0]];
+        let warp = e1 * self.inv_rest.x_axis.x + e2 * self.inv_rest.x_axis.y;
+        Self::new(self.particles_idx, rest, warp, self.compliance)
+    }
+
+    /// Blends the rest edge vectors in the material frame, falling back to blending their
+    /// inverse if the blended triangle is degenerate
+    pub(crate) fn blend_rest(&mut self, from: &Self, to: &Self, t: f32) {
+        let [from_rest, to_rest] = [from.inv_rest.inverse(), to.inv_rest.inverse()];
+        let rest = from_rest + (to_rest - from_rest) * t;
+        if rest.determinant() > 0. {
+            self.inv_rest = rest.inverse();
+            self.rest_area = rest.determinant() / 2.;
+        } else {
+            self.inv_rest = from.inv_rest + (to.inv_rest - from.inv_rest) * t;
+            self.rest_area = lerp(from.rest_area, to.rest_area, t);
+        }
+    }
+
+    pub(crate) fn positions(&self, particles: &[Particle]) -> [Vec3; 3] {
+        self.particles_idx.map(|i| particles[i as usize].position)
+    }
+
+    /// Weights of each particle in the deformed warp and weft axes
+    fn weights(&self) -> [Vec2; 3] {
+        let [a, b] = [self.inv_rest.row(0), self.inv_rest.row(1)];
+        [-(a + b), a, b]
+    }
+
+    /// Value and gradients of the warp stretch, weft stretch and shear strains
+    pub(crate) fn strains(&self, positions: [Vec3; 3]) -> [(f32, [Vec3; 3]); 3] {
+        let weights = self.weights();
+        let [f1, f2] = [0, 1].map(|j| {
+            positions
+                .iter()
+                .zip(&weights)
+                .map(|(p, w)| *p * w[j])
+                .sum::<Vec3>()
+        });
+        [
+            ((f1.length_squared() - 1.) / 2., weights.map(|w| f1 * w.x)),
+            ((f2.length_squared() - 1.) / 2., weights.map(|w| f2 * w.y)),
+            (f1.dot(f2) / 2., weights.map(|w| (f2 * w.x + f1 * w.y) / 2.)),
+        ]
+    }
+
+    /// Positions after solving each strain in turn, adding to the multipliers `lambdas` of the
+    /// strains accumulated since the start of the substep
+    pub(crate) fn project(
+        &self,
+        mut positions: [Vec3; 3],
+        inv_masses: [f32; 3],
+        delta: f32,
+        lambdas: &mut [f32],
+    ) -> [Vec3; 3] {
+        for (row, compliance) in self.compliance.to_array().into_iter().enumerate() {
+            let xpbd_stiff = compliance / (self.rest_area * delta * delta);
+            let (value, gradients) = self.strains(positions)[row];
+            let denominator = gradients
+                .iter()
+                .zip(inv_masses)
+                .map(|(g, w)| w * g.length_squared())
+                .sum::<f32>()
+                + xpbd_stiff;
+            if denominator == 0. {
+                continue;
+            }
+            let delta_lambda = -(value + xpbd_stiff * lambdas[row]) / denominator;
+            lambdas[row] += delta_lambda;
+            for ((p, g), w) in positions.iter_mut().zip(gradients).zip(inv_masses) {
+                *p += delta_lambda * w * g;
+            }
+        }
+        positions
+    }
+}
@@ -0,0 +1,123 @@
+use glam::Vec3;
+
+use crate::{lerp, non_negative, validate_indices, Error, Particle};
+
+/// Keeps the volume enclosed by a closed triangle surface at `pressure` times its rest volume,
+/// inflating bodies made of a surface mesh only. The triangles are oriented counterclockwise
+/// seen from outside. A `jacobi_weight` above 1 over-corrects the volume with the Jacobi
+/// solvers, so loaded bodies settle somewhat above their target volume.
+#[derive(Clone, Debug)]
+pub struct SurfaceVolumeC {
+    /// Particles of the surface, sorted
+    pub(crate) particles_idx: Vec<u32>,
+    /// Triangles, indexing into `particles_idx`
+    pub(crate) triangles: Vec<[u32; 3]>,
+    pub(crate) rest_volume: f32,
+    pub(crate) pressure: f32,
+    pub(crate) compliance: f32,
+}
+
+impl SurfaceVolumeC {
+    pub fn new(
+        triangles: &[[u32; 3]],
+        rest_volume: f32,
+        pressure: f32,
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        if !(rest_volume > 0. && rest_volume.is_finite()) {
+            return Err(Error::InvalidParameter {
+                name: "rest volume",
+                value: rest_volume,
+            });
+        }
+        let mut particles_idx: Vec<_> = triangles.iter().flatten().copied().collect();
+        particles_idx.sort_unstable();
+        particles_idx.dedup();
+        let triangles = triangles
+            .iter()
+            .map(|t| t.map(|i| particles_idx.binary_search(&i).unwrap_or_default() as u32))
+            .collect();
+        Ok(Self {
+            particles_idx,
+            triangles,
+            rest_volume,
+            pressure: non_negative("pressure", pressure)?,
+            compliance: non_negative("compliance", compliance)?,
+        })
+    }
+
+    /// Constraint with the volume currently enclosed by the triangles as the rest volume
+    pub fn from_particles(
+        triangles: &[[u32; 3]],
+        particles: &[Particle],
+        pressure: f32,
+        compliance: f32,
+    ) -> Result<Self, Error> {
+        validate_indices(triangles.iter().map(|t| &t[..]), particles.len() as u32)?;
+        let rest_volume = triangles
+            .iter()
+            .map(|t| triangle_volume(t.map(|i| particles[i as usize].position)))
+            .sum();
+        Self::new(triangles, rest_volume, pressure, compliance)
+    }
+
+    /// Volume enclosed by the surface
+    pub fn volume(&self, particles: &[Particle]) -> f32 {
+        self.triangles
+            .iter()
+            .map(|t| triangle_volume(t.map(|i| self.position(particles, i))))
+            .sum()
+    }
+
+    pub(crate) fn offset(&self, offset: u32) -> Self {
+        Self {
+            particles_idx: self.particles_idx.iter().map(|i| i + offset).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Constraint with the volume enclosed by the `rest` positions of the surface as the rest
+    /// volume
+    pub(crate) fn with_rest_shape(&self, rest: &[Vec3]) -> Result<Self, Error> {
+        let rest_volume: f32 = self
+            .triangles
+            .iter()
+            .map(|t| triangle_volume(t.map(|i| rest[self.particles_idx[i as usize] as usize])))
+            .sum();
+        if !(rest_volume > 0. && rest_volume.is_finite()) {
+            return Err(Error::InvalidParameter {
+                name: "rest volume",
+                value: rest_volume,
+            });
+        }
+        Ok(Self {
+            rest_volume,
+            ..self.clone()
+        })
+    }
+
+    pub(crate) fn blend_rest(&mut self, from: &Self, to: &Self, t: f32) {
+        self.rest_volume = lerp(from.rest_volume, to.rest_volume, t);
+    }
+
+    fn position(&self, particles: &[Particle], i: u32) -> Vec3 {
+        particles[self.particles_idx[i as usize] as usize].position
+    }
+
+    /// Gradient of the volume with respect to each particle of the surface
+    pub(crate) fn volume_gradients(&self, particles: &[Particle]) -> Vec<Vec3> {
+        let mut gradients = vec![Vec3::ZERO; self.particles_idx.len()];
+        for t in &self.triangles {
+            let [p1, p2, p3] = t.map(|i| self.position(particles, i));
+            gradients[t[0] as usize] += p2.cross(p3) / 6.;
+            gradients[t[1] as usize] += p3.cross(p1) / 6.;
+            gradients[t[2] as usize] += p1.cross(p2) / 6.;
+        }
+        gradients
+    }
+}
+
+/// Signed volume of the tetrahedron between the triangle and the origin
+fn triangle_volume([p1, p2, p3]: [Vec3; 3]) -> f32 {
+    p1.cross(p2).dot(p3) / 6.
+}
@@ -0,0 +1,161 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use encase::ShaderType;
+
+use crate::{non_negative, Body, Error, Particle};
+
+/// Keeps a particle within `max_distance` of an anchor and lets it move freely closer to it
+/// (Kim et al. 2012, "Long Range Attachments"). Tethering cloth and ropes to their pinned
+/// particles keeps them from stretching at low iteration counts.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, ShaderType)]
+pub struct TetherC {
+    /// Anchor and tethered particle
+    pub(crate) particles_idx: [u32; 2],
+    pub(crate) max_distance: f32,
+    pub(crate) compliance: f32,
+}
+
+impl TetherC {
+    pub fn new(particles_idx: [u32; 2], max_distance: f32, compliance: f32) -> Result<Self, Error> {
+        Ok(Self {
+            particles_idx,
+            max_distance: non_negative("max distance", max_distance)?,
+            compliance: non_negative("compliance", compliance)?,
+        })
+    }
+
+    /// Distance between the anchor and the tethered particle
+    pub fn distance(&self, particles: &[Particle]) -> f32 {
+        let [anchor, particle] = self.particles_idx.map(|i| particles[i as usize].position);
+        anchor.distance(particle)
+    }
+
+    pub(crate) fn offset(mut self, offset: u32) -> Self {
+        self.particles_idx = self.particles_idx.map(|i| i + offset);
+        self
+    }
+}
+
+/// How the maximum distance of generated tethers is measured
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TetherDistance {
+    /// Length of the shortest path to the anchor along the constraints of the body, which lets
+    /// the body fold but not stretch
+    Geodesic,
+    /// Straight line distance to the anchor, which also keeps the body from unfolding further
+    /// than its rest shape
+    Euclidean,
+}
+
+impl Body {
+    /// Tethers every particle with finite mass to the pinned particle nearest to it along the
+    /// constraints of the body, measuring distances on the current positions. Particles not
+    /// connected to any pinned particle are left untethered.
+    pub fn tethers(
+        &self,
+        distance: TetherDistance,
+        compliance: f32,
+    ) -> Result<Vec<TetherC>, Error> {
+        self.validate()?;
+        non_negative("compliance", compliance)?;
+
+        let mut edges = vec![Vec::new(); self.particles.len()];
+        let mut connect = |particles_idx: &[u32]| {
+            for (n, i) in particles_idx.iter().enumerate() {
+                for j in &particles_idx[n + 1..] {
+                    let length = self.particles[*i as usize]
+                        .position
+                        .distance(self.particles[*j as usize].position);
+                    edges[*i as usize].push((*j, length));
+                    edges[*j as usize].push((*i, length));
+                }
+            }
+        };
+        self.distance_constraints
+            .iter()
+            .for_each(|c| connect(&c.particles_idx));
+        self.tet_constraints
+            .iter()
+            .for_each(|c| connect(&c.particles_idx));
+        self.triangle_strain_constraints
+            .iter()
+            .for_each(|c| connect(&c.particles_idx));
+        for c in &self.surface_volume_constraints {
+            for t in &c.triangles {
+                connect(&t.map(|i| c.particles_idx[i as usize]));
+            }
+        }
+
+        // Dijkstra from every pinned particle at once, keeping the anchor each path starts from
+        let mut nearest: Vec<Option<(f32, u32)>> = vec![None; self.particles.len()];
+        let mut queue = BinaryHeap::new();
+        for (i, p) in self.particles.iter().enumerate() {
+            if p.inv_mass == 0. {
+                queue.push(Visit {
+                    distance: 0.,
+                    particle: i as u32,
+                    anchor: i as u32,
+                });
+            }
+        }
+        while let Some(visit) = queue.pop() {
+            if nearest[visit.particle as usize].is_some() {
+                continue;
+            }
+            nearest[visit.particle as usize] = Some((visit.distance, visit.anchor));
+            for (j, length) in &edges[visit.particle as usize] {
+                if nearest[*j as usize].is_none() {
+                    queue.push(Visit {
+                        distance: visit.distance + length,
+                        particle: *j,
+                        anchor: visit.anchor,
+                    });
+                }
+            }
+        }
+
+        nearest
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.particles[*i].inv_mass != 0.)
+            .filter_map(|(i, nearest)| {
+                let (geodesic, anchor) = (*nearest)?;
+                let max_distance = match distance {
+                    TetherDistance::Geodesic => geodesic,
+                    TetherDistance::Euclidean => self.particles[anchor as usize]
+                        .position
+                        .distance(self.particles[i].position),
+                };
+                Some(TetherC::new([anchor, i as u32], max_distance, compliance))
+            })
+            .collect()
+    }
+}
+
+/// Particle reached by a path from an anchor, ordered so the shortest path is popped first
+struct Visit {
+    distance: f32,
+    particle: u32,
+    anchor: u32,
+}
+
+impl PartialEq for Visit {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Visit {}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance)
+    }
+}
@@ -0,0 +1,95 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    AeroTriangle, Body, Particle, Turbulence, Wind, WorldParams,
+};
+
+/// Center of a triangle facing the wind after half a second, without gravity
+fn blown_center(wind: Wind, air_density: f32) -> Vec3 {
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        air_density,
+        ..Default::default()
+    };
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, params).unwrap();
+    sim.set_wind(wind).unwrap();
+    sim.add_body(Body {
+        particles: [Vec3::ZERO, Vec3::Y, Vec3::Z]
+            .iter()
+            .map(|p| Particle::new(*p, 1.))
+            .collect(),
+        aero_triangles: vec![AeroTriangle::new([0, 1, 2], 1., 0.).unwrap()],
+        ..Default::default()
+    })
+    .unwrap();
+    for _ in 0..30 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    sim.particles().iter().map(|p| p.position).sum::<Vec3>() / 3.
+}
+
+#[test]
+fn drag_pushes_triangles_along_the_wind() {
+    let start = Vec3::new(0., 1., 1.) / 3.;
+    let blown = blown_center(Wind::Constant(Vec3::new(10., 0., 0.)), 1.2);
+    assert!(blown.x > 0.1, "{blown}");
+    let across = (blown - start) * Vec3::new(0., 1., 1.);
+    assert!(across.length() < 1e-5, "{blown}");
+    let still = blown_center(Wind::Constant(Vec3::new(10., 0., 0.)), 0.);
+    assert_eq!(still, start);
+}
+
+#[test]
+fn turbulence_changes_over_time() {
+    let turbulence = Turbulence {
+        amplitude: 5.,
+        ..Default::default()
+    };
+    let samples: Vec<_> = (0..10)
+        .map(|i| turbulence.velocity(Vec3::ZERO, i as f32 * 0.1))
+        .collect();
+    assert!(samples.windows(2).all(|w| w[0] != w[1]));
+    assert!(samples
+        .iter()
+        .all(|v| v.abs().max_element() <= turbulence.amplitude));
+}
+
+#[test]
+fn turbulence_is_sampled_at_each_substep() {
+    // Ten substeps per frame move the triangle like ten frames of a single substep
+    let blown = |substeps, frames| {
+        let params = WorldParams {
+            gravity: Vec3::ZERO,
+            ground: None,
+            substeps,
+            ..Default::default()
+        };
+        let mut sim = CpuSimulation::new(SolverType::GaussSeidel, params).unwrap();
+        sim.set_wind(Wind::Turbulent(Turbulence {
+            amplitude: 5.,
+            frequency: 20.,
+            ..Default::default()
+        }))
+        .unwrap();
+        sim.add_body(Body {
+            particles: [Vec3::ZERO, Vec3::Y, Vec3::Z]
+                .iter()
+                .map(|p| Particle::new(*p, 1.))
+                .collect(),
+            aero_triangles: vec![AeroTriangle::new([0, 1, 2], 1., 0.).unwrap()],
+            ..Default::default()
+        })
+        .unwrap();
+        for _ in 0..frames {
+            sim.simulate(0.5 / frames as f32, false).unwrap();
+        }
+        sim.particles()[0].position
+    };
+    let substepped = blown(10, 3);
+    let stepped = blown(1, 30);
+    assert!(
+        substepped.abs_diff_eq(stepped, 1e-4),
+        "{substepped} {stepped}"
+    );
+}
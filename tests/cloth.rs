@@ -0,0 +1,89 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, ClothParams, WorldParams,
+};
+
+/// A 2 by 1 rectangle along x and y split along a diagonal
+const VERTICES: [Vec3; 4] = [
+    Vec3::ZERO,
+    Vec3::new(2., 0., 0.),
+    Vec3::new(2., 1., 0.),
+    Vec3::new(0., 1., 0.),
+];
+const TRIANGLES: [[u32; 3]; 2] = [[0, 1, 2], [0, 2, 3]];
+
+/// How far the free edge of a unit square of cloth hanging from its top edge sagged, with
+/// stiff warp threads along `warp_direction` and stretchy weft threads across them
+fn hanging_sag(warp_direction: Vec3) -> f32 {
+    let vertices = [
+        Vec3::ZERO,
+        Vec3::X,
+        Vec3::new(1., 0., -1.),
+        Vec3::new(0., 0., -1.),
+    ];
+    let params = ClothParams {
+        warp_direction,
+        warp_compliance: 0.,
+        weft_compliance: 1.,
+        shear_compliance: 1.,
+        ..Default::default()
+    };
+    let mut cloth = Body::cloth(&vertices, &TRIANGLES, &params).unwrap();
+    cloth.particles[0].inv_mass = 0.;
+    cloth.particles[1].inv_mass = 0.;
+    let params = WorldParams {
+        ground: None,
+        damping: 5.,
+        ..Default::default()
+    };
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, params).unwrap();
+    sim.add_body(cloth).unwrap();
+    for _ in 0..120 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    -1. - sim.particles()[3].position.z
+}
+
+#[test]
+fn cloth_stretches_along_its_weft() {
+    let stiff = hanging_sag(Vec3::Z);
+    let stretchy = hanging_sag(Vec3::X);
+    assert!(stiff < 1e-3, "{stiff}");
+    assert!(stretchy > 0.01, "{stretchy}");
+}
+
+#[test]
+fn cloth_mass_comes_from_its_area() {
+    let cloth = Body::cloth(&VERTICES, &TRIANGLES, &Default::default()).unwrap();
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, WorldParams::default()).unwrap();
+    let handle = sim.add_body(cloth).unwrap();
+    let mass = sim.body_mass(handle).unwrap();
+    assert!(
+        (mass - 2. * ClothParams::default().density).abs() < 1e-6,
+        "{mass}"
+    );
+}
+
+#[test]
+fn invalid_cloth_is_rejected() {
+    let negative = ClothParams {
+        bending_compliance: -1.,
+        ..Default::default()
+    };
+    assert!(Body::cloth(&VERTICES, &TRIANGLES, &negative).is_err());
+    assert!(Body::cloth(&VERTICES, &[[0, 1, 4]], &Default::default()).is_err());
+}
+
+#[test]
+fn strain_cloth_keeps_only_bending_edges() {
+    let strain = ClothParams {
+        strain_constraints: true,
+        drag_coefficient: 1.,
+        ..Default::default()
+    };
+    let cloth = Body::cloth(&VERTICES, &TRIANGLES, &strain).unwrap();
+    assert_eq!(cloth.distance_constraints.len(), 1);
+    assert_eq!(cloth.triangle_strain_constraints.len(), 2);
+    assert_eq!(cloth.aero_triangles.len(), 2);
+}
@@ -0,0 +1,235 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, BodyHandle, DistanceC, Error, Particle, TetrahedralVolumeC, WorldParams,
+};
+
+fn falling_height(damping: f32) -> f32 {
+    let params = WorldParams {
+        damping,
+        ..Default::default()
+    };
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, params).unwrap();
+    sim.add_body(Body {
+        particles: vec![Particle::new(Vec3::new(0., 0., 10.), 1.)],
+        ..Default::default()
+    })
+    .unwrap();
+    for _ in 0..60 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    sim.particles()[0].position.z - 10.
+}
+
+#[test]
+fn global_damping_slows_falling_particles() {
+    let free = falling_height(0.);
+    let damped = falling_height(2.);
+    // Half of g t² after a second
+    assert!((free + 4.905).abs() < 0.1, "{free}");
+    assert!(damped > free + 1., "{damped} {free}");
+}
+
+/// Angle turned by a stretched triangle spun by pushes around it over half a second, and how
+/// far the length of its edges still swings around its mean over the last tenth
+fn spun_triangle(damping: f32, damp: impl Fn(&mut CpuSimulation, BodyHandle)) -> (f32, f32) {
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        damping,
+        ..Default::default()
+    };
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, params).unwrap();
+    let corners = [0., 1., 2.].map(|i| {
+        let (sin, cos) = (i * std::f32::consts::TAU / 3.).sin_cos();
+        let mut corner = Particle::new(Vec3::new(cos * 0.6, sin * 0.6, 10.), 1.);
+        corner.ext_acc = Vec3::new(-sin, cos, 0.) * 2.;
+        corner
+    });
+    // The edges of a triangle half as large
+    let rest = 0.5 * 3f32.sqrt();
+    let handle = sim
+        .add_body(Body {
+            particles: corners.to_vec(),
+            distance_constraints: [[0, 1], [1, 2], [2, 0]]
+                .map(|edge| DistanceC::new(edge, rest, 1e-2).unwrap())
+                .to_vec(),
+            ..Default::default()
+        })
+        .unwrap();
+    damp(&mut sim, handle);
+
+    let mut lengths = Vec::new();
+    for _ in 0..30 {
+        sim.simulate(1. / 60., false).unwrap();
+        let [a, b] = [0, 1].map(|i| sim.particles()[i].position);
+        lengths.push(a.distance(b));
+    }
+    let last = &lengths[24..];
+    let mean = last.iter().sum::<f32>() / last.len() as f32;
+    let swing = last.iter().map(|l| (l - mean).abs()).fold(0., f32::max);
+    let center = sim.particles().iter().map(|p| p.position).sum::<Vec3>() / 3.;
+    let corner = sim.particles()[0].position - center;
+    (corner.y.atan2(corner.x), swing)
+}
+
+#[test]
+fn rigid_mode_damping_keeps_the_rotation_and_damps_the_deformation() {
+    let (free_angle, free_swing) = spun_triangle(0., |_, _| {});
+    let (rigid_angle, rigid_swing) = spun_triangle(0., |sim, handle| {
+        sim.set_rigid_mode_damping(handle, 0.1).unwrap()
+    });
+    let (global_angle, _) = spun_triangle(5., |_, _| {});
+    assert!(free_angle > 0.3, "{free_angle}");
+    assert!(
+        (rigid_angle / free_angle - 1.).abs() < 0.05,
+        "{rigid_angle} {free_angle}"
+    );
+    assert!(rigid_swing < free_swing * 0.1, "{rigid_swing} {free_swing}");
+    // Global damping slows the rotation as well
+    assert!(
+        global_angle < free_angle * 0.8,
+        "{global_angle} {free_angle}"
+    );
+}
+
+/// How far a mass on a stretched spring still swings in its second second
+fn spring_swing(solver: SolverType, damping: f32) -> f32 {
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ..Default::default()
+    };
+    let mut sim = CpuSimulation::new(solver, params).unwrap();
+    sim.add_body(Body {
+        particles: vec![
+            Particle::new(Vec3::new(0., 0., 10.), 0.),
+            Particle::new(Vec3::new(1.5, 0., 10.), 1.),
+        ],
+        distance_constraints: vec![DistanceC::new([0, 1], 1., 1e-2)
+            .unwrap()
+            .with_damping(damping)
+            .unwrap()],
+        ..Default::default()
+    })
+    .unwrap();
+    let mut swing: f32 = 0.;
+    for frame in 0..120 {
+        sim.simulate(1. / 60., false).unwrap();
+        if frame >= 60 {
+            swing = swing.max((sim.particles()[1].position.x - 1.).abs());
+        }
+    }
+    swing
+}
+
+/// Angle turned over half a second by a rigid body centered on the origin, spun around z by
+/// pushes on its particles
+fn rigid_spin(positions: &[Vec3], rigid_mode_damping: f32) -> f32 {
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        ..Default::default()
+    };
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, params).unwrap();
+    let particles: Vec<_> = positions
+        .iter()
+        .map(|p| {
+            let mut particle = Particle::new(*p, 1.);
+            particle.ext_acc = Vec3::Z.cross(*p);
+            particle
+        })
+        .collect();
+    let mut distance_constraints = Vec::new();
+    for a in 0..positions.len() {
+        for b in a + 1..positions.len() {
+            let distance = positions[a].distance(positions[b]);
+            distance_constraints.push(DistanceC::new([a as u32, b as u32], distance, 0.).unwrap());
+        }
+    }
+    let handle = sim
+        .add_body(Body {
+            particles,
+            distance_constraints,
+            ..Default::default()
+        })
+        .unwrap();
+    sim.set_rigid_mode_damping(handle, rigid_mode_damping)
+        .unwrap();
+    for _ in 0..30 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    let corner = sim.particles()[0].position;
+    corner.y.atan2(corner.x)
+}
+
+#[test]
+fn rigid_mode_damping_keeps_the_rotation_of_collinear_and_small_bodies() {
+    let dumbbell = [Vec3::X, Vec3::NEG_X];
+    let triangle = [Vec3::X, Vec3::new(-0.5, 0.8, 0.), Vec3::new(-0.5, -0.8, 0.)];
+    for scale in [1., 1e-2] {
+        for positions in [&dumbbell[..], &triangle[..]] {
+            let positions: Vec<_> = positions.iter().map(|p| *p * scale).collect();
+            let free = rigid_spin(&positions, 0.);
+            let damped = rigid_spin(&positions, 1.);
+            assert!(free > 0.1, "{scale} {free}");
+            assert!((damped / free - 1.).abs() < 0.05, "{scale} {damped} {free}");
+        }
+    }
+}
+
+#[test]
+fn constraint_damping_damps_the_motion_along_the_constraint() {
+    let xpbd = spring_swing(SolverType::GaussSeidel, 10.);
+    for solver in [SolverType::GaussSeidel, SolverType::VertexBlockDescent] {
+        let free = spring_swing(solver, 0.);
+        let damped = spring_swing(solver, 10.);
+        assert!(free > 0.2, "{free}");
+        assert!(damped < free * 0.1, "{damped} {free}");
+        assert!((damped / xpbd - 1.).abs() < 0.05, "{damped} {xpbd}");
+    }
+}
+
+#[test]
+fn constraint_damping_is_non_negative() {
+    let particles = [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z].map(|p| Particle::new(p, 1.));
+    let distance = DistanceC::new([0, 1], 1., 1e-2).unwrap();
+    let tet = TetrahedralVolumeC::from_particles([0, 1, 2, 3], &particles, 1e-2).unwrap();
+    for damping in [-1., f32::NAN, f32::INFINITY] {
+        assert!(matches!(
+            distance.with_damping(damping),
+            Err(Error::InvalidParameter {
+                name: "damping",
+                ..
+            })
+        ));
+        assert!(matches!(
+            tet.with_damping(damping),
+            Err(Error::InvalidParameter {
+                name: "damping",
+                ..
+            })
+        ));
+    }
+}
+
+#[test]
+fn rigid_mode_damping_is_a_fraction() {
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, WorldParams::default()).unwrap();
+    let handle = sim
+        .add_body(Body {
+            particles: vec![Particle::new(Vec3::ZERO, 1.), Particle::new(Vec3::X, 1.)],
+            ..Default::default()
+        })
+        .unwrap();
+    for coefficient in [0., 0.5, 1.] {
+        sim.set_rigid_mode_damping(handle, coefficient).unwrap();
+    }
+    for coefficient in [-0.1, 1.1, f32::NAN, f32::INFINITY] {
+        assert!(matches!(
+            sim.set_rigid_mode_damping(handle, coefficient),
+            Err(Error::InvalidParameter {
+                name: "rigid mode damping",
+                ..
+            })
+        ));
+    }
+}
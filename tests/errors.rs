@@ -0,0 +1,75 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, DistanceC, Error, IndexError, Particle, TetrahedralVolumeC, WorldParams,
+};
+
+fn particles() -> Vec<Particle> {
+    [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z]
+        .iter()
+        .map(|p| Particle::new(*p, 1.))
+        .collect()
+}
+
+#[test]
+fn invalid_bodies_are_rejected_without_changing_the_simulation() {
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, WorldParams::default()).unwrap();
+    let out_of_range = Body {
+        particles: particles(),
+        distance_constraints: vec![DistanceC::new([0, 4], 1., 0.).unwrap()],
+        ..Default::default()
+    };
+    assert!(matches!(
+        sim.add_body(out_of_range),
+        Err(Error::Index(IndexError::ParticleOutOfRange {
+            particle_idx: 4,
+            particles_n: 4
+        }))
+    ));
+
+    let mut negative_mass = particles();
+    negative_mass[1].inv_mass = -1.;
+    assert!(matches!(
+        sim.add_body(Body {
+            particles: negative_mass,
+            ..Default::default()
+        }),
+        Err(Error::InvalidParameter {
+            name: "inverse mass",
+            ..
+        })
+    ));
+    assert!(sim.particles().is_empty());
+}
+
+#[test]
+fn invalid_constraint_parameters_are_rejected() {
+    assert!(matches!(
+        DistanceC::new([0, 1], 1., -1.),
+        Err(Error::InvalidParameter {
+            name: "compliance",
+            ..
+        })
+    ));
+    let particles = particles();
+    assert!(TetrahedralVolumeC::from_particles([0, 1, 2, 3], &particles, f32::NAN).is_err());
+    assert!(matches!(
+        TetrahedralVolumeC::from_particles([0, 1, 2, 7], &particles, 0.),
+        Err(Error::Index(IndexError::ParticleOutOfRange { .. }))
+    ));
+}
+
+#[test]
+fn errors_describe_the_invalid_value() {
+    let error = DistanceC::new([0, 1], 1., -2.).unwrap_err();
+    assert_eq!(error.to_string(), "invalid compliance: -2");
+    let error: Error = IndexError::ParticleOutOfRange {
+        particle_idx: 4,
+        particles_n: 4,
+    }
+    .into();
+    assert_eq!(
+        error.to_string(),
+        "particle index 4 is out of range for a body of 4 particles"
+    );
+}
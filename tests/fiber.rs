@@ -0,0 +1,100 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, BodyHandle, DistanceC, FiberC, Particle, WorldParams,
+};
+
+/// A tetrahedron with fibers along x
+fn fibered_tet() -> Body {
+    let particles: Vec<_> = [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z]
+        .iter()
+        .map(|p| Particle::new(*p, 1.))
+        .collect();
+    Body {
+        fiber_constraints: vec![
+            FiberC::from_particles([0, 1, 2, 3], &particles, Vec3::X, 0.).unwrap(),
+        ],
+        particles,
+        ..Default::default()
+    }
+}
+
+/// Fiber stretch of the tetrahedron of each body after another second
+fn fiber_stretches(sim: &mut CpuSimulation, bodies: &[BodyHandle]) -> Vec<f32> {
+    for _ in 0..60 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    let fiber = fibered_tet().fiber_constraints[0];
+    bodies
+        .iter()
+        .map(|h| {
+            let range = sim.body_particles(*h).unwrap();
+            fiber
+                .fiber(&sim.particles()[range.start as usize..range.end as usize])
+                .length()
+        })
+        .collect()
+}
+
+#[test]
+fn activated_fibers_contract_along_their_direction() {
+    for solver in [SolverType::GaussSeidel, SolverType::Jacobi] {
+        let params = WorldParams {
+            gravity: Vec3::ZERO,
+            ground: None,
+            damping: 5.,
+            // Over-relaxing a lone constraint makes it oscillate
+            jacobi_weight: 1.,
+            ..Default::default()
+        };
+        let mut sim = CpuSimulation::new(solver, params).unwrap();
+        let active = sim.add_body(fibered_tet()).unwrap();
+        let passive = sim.add_body(fibered_tet()).unwrap();
+        sim.set_fiber_activation(active, 0.2).unwrap();
+        let stretches = fiber_stretches(&mut sim, &[active, passive]);
+        assert!((stretches[0] - 0.8).abs() < 1e-3, "{stretches:?}");
+        assert!((stretches[1] - 1.).abs() < 1e-6, "{stretches:?}");
+
+        sim.set_fiber_activation(active, 0.).unwrap();
+        let stretches = fiber_stretches(&mut sim, &[active]);
+        assert!((stretches[0] - 1.).abs() < 1e-3, "{stretches:?}");
+    }
+}
+
+#[test]
+fn activation_is_a_fraction_below_one() {
+    let mut fiber = fibered_tet().fiber_constraints[0];
+    assert!(fiber.set_activation(1.).is_err());
+    assert!(fiber.set_activation(-0.1).is_err());
+    fiber.set_activation(0.5).unwrap();
+    assert_eq!(fiber.activation(), 0.5);
+}
+
+#[test]
+fn fiber_compliance_does_not_depend_on_iterations() {
+    // A compliant fiber contracting against a compliant edge along it
+    let stretch = |solver, iterations| {
+        let params = WorldParams {
+            gravity: Vec3::ZERO,
+            ground: None,
+            damping: 5.,
+            jacobi_weight: 1.,
+            iterations,
+            ..Default::default()
+        };
+        let mut body = fibered_tet();
+        body.fiber_constraints[0] =
+            FiberC::from_particles([0, 1, 2, 3], &body.particles, Vec3::X, 1e-2).unwrap();
+        body.distance_constraints = vec![DistanceC::new([0, 1], 1., 1e-2).unwrap()];
+        let mut sim = CpuSimulation::new(solver, params).unwrap();
+        let handle = sim.add_body(body).unwrap();
+        sim.set_fiber_activation(handle, 0.2).unwrap();
+        fiber_stretches(&mut sim, &[handle])[0]
+    };
+    for solver in [SolverType::GaussSeidel, SolverType::Jacobi] {
+        let one = stretch(solver, 1);
+        assert!(one > 0.85 && one < 0.99, "{one}");
+        let several = stretch(solver, 5);
+        assert!((several / one - 1.).abs() < 0.01, "{several} {one}");
+    }
+}
@@ -0,0 +1,74 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, Fluid, FluidParams, Particle, WorldParams,
+};
+
+const SPACING: f32 = 0.05;
+
+/// A cube of 4 by 4 by 4 fluid particles
+fn block(min: Vec3) -> Fluid {
+    let max = min + Vec3::splat(SPACING * 3.);
+    Fluid::block(min, max, SPACING, FluidParams::default()).unwrap()
+}
+
+fn closest(a: Vec3, others: &[Vec3]) -> f32 {
+    others
+        .iter()
+        .map(|b| a.distance(*b))
+        .fold(f32::INFINITY, f32::min)
+}
+
+#[test]
+fn fluid_blocks_have_the_rest_density() {
+    let fluid = block(Vec3::ZERO);
+    assert_eq!(fluid.particles.len(), 64);
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, WorldParams::default()).unwrap();
+    let handle = sim.add_fluid(fluid).unwrap();
+    let mass = sim.body_mass(handle).unwrap();
+    assert!(
+        (mass - 1000. * (SPACING * 4.).powi(3)).abs() < 1e-3,
+        "{mass}"
+    );
+}
+
+#[test]
+fn fluid_spreads_without_compressing_and_flows_around_other_bodies() {
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, WorldParams::default()).unwrap();
+    let obstacle = Vec3::new(0.075, 0.075, 0.02);
+    sim.add_body(Body {
+        particles: vec![Particle::new(obstacle, 0.)],
+        ..Default::default()
+    })
+    .unwrap();
+    let handle = sim.add_fluid(block(Vec3::Z * 0.1)).unwrap();
+    for _ in 0..60 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+
+    let range = sim.body_particles(handle).unwrap();
+    let fluid: Vec<_> = sim.particles()[range.start as usize..range.end as usize]
+        .iter()
+        .map(|p| p.position)
+        .collect();
+    let width = fluid.iter().map(|p| p.x).fold(f32::NEG_INFINITY, f32::max)
+        - fluid.iter().map(|p| p.x).fold(f32::INFINITY, f32::min);
+    assert!(width > SPACING * 6., "{width}");
+    for (i, p) in fluid.iter().enumerate() {
+        assert!(p.z > -1e-3, "{p}");
+        let neighbour = closest(*p, &[&fluid[..i], &fluid[i + 1..]].concat());
+        assert!(neighbour > SPACING * 0.7, "{neighbour}");
+    }
+    let clearance = closest(obstacle, &fluid);
+    assert!(clearance > SPACING * 0.7, "{clearance}");
+}
+
+#[test]
+fn invalid_fluids_are_rejected() {
+    let params = FluidParams {
+        kernel_radius: 0.,
+        ..Default::default()
+    };
+    assert!(Fluid::block(Vec3::ZERO, Vec3::ONE, 0.1, params).is_err());
+    assert!(Fluid::block(Vec3::ZERO, Vec3::ONE, 0., FluidParams::default()).is_err());
+}
@@ -0,0 +1,499 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    gpu::GpuSimulation,
+    AeroTriangle, Body, ClothParams, DistanceC, Error, FiberC, IndexError, Particle,
+    SurfaceVolumeC, TetherDistance, TetrahedralVolumeC, Turbulence, Wind, WorldParams,
+};
+use wgpu::{Device, Queue};
+
+/// A device with the limits of the bunny example. Without an adapter this panics, unless
+/// `PLASTICA_SKIP_GPU_TESTS` is set to skip the tests by returning `None`.
+fn device() -> Option<(Device, Queue)> {
+    if std::env::var_os("PLASTICA_SKIP_GPU_TESTS").is_some() {
+        return None;
+    }
+    let instance = wgpu::Instance::default();
+    let adapter = pollster::block_on(instance.request_adapter(&Default::default()))
+        .expect("no adapter to run the GPU tests on, set PLASTICA_SKIP_GPU_TESTS to skip them");
+    let descriptor = wgpu::DeviceDescriptor {
+        label: None,
+        features: wgpu::Features::empty(),
+        limits: wgpu::Limits::downlevel_defaults(),
+    };
+    let (device, queue) = pollster::block_on(adapter.request_device(&descriptor, None)).unwrap();
+    device.on_uncaptured_error(Box::new(|e| panic!("wgpu error: {e}")));
+    Some((device, queue))
+}
+
+fn step(gpu: &mut GpuSimulation, device: &Device, queue: &Queue, delta: f32) {
+    let mut encoder = device.create_command_encoder(&Default::default());
+    gpu.simulate(device, queue, &mut encoder, delta);
+    queue.submit(Some(encoder.finish()));
+}
+
+/// Particles once the steps submitted so far are done
+fn download(gpu: &GpuSimulation, device: &Device, queue: &Queue) -> Vec<Particle> {
+    gpu.download_particles(device, queue).unwrap();
+    device.poll(wgpu::Maintain::Wait);
+    gpu.download_particles(device, queue).unwrap()
+}
+
+/// A bar of `cells` cubes along x with fibers along it, pinned at x = 0
+fn muscle(cells: u32) -> Body {
+    let index = |x: u32, y: u32, z: u32| x * 4 + y * 2 + z;
+    let particles: Vec<_> = (0..=cells)
+        .flat_map(|x| (0..4).map(move |i| (x, i / 2, i % 2)))
+        .map(|(x, y, z)| {
+            let position = Vec3::new(x as f32, y as f32, z as f32) * 0.2 + Vec3::Z;
+            Particle::new(position, if x == 0 { 0. } else { 1. })
+        })
+        .collect();
+    // Six tetrahedra around the diagonal of each cube
+    let corners = [
+        [0, 1, 3, 7],
+        [0, 1, 5, 7],
+        [0, 2, 3, 7],
+        [0, 2, 6, 7],
+        [0, 4, 5, 7],
+        [0, 4, 6, 7],
+    ];
+    let tets: Vec<[u32; 4]> = (0..cells)
+        .flat_map(|x| corners.map(|tet| tet.map(|c| index(x + (c & 1), (c >> 1) & 1, c >> 2))))
+        .collect();
+    let mut edges: Vec<[u32; 2]> = tets
+        .iter()
+        .flat_map(|t| [[0, 1], [0, 2], [0, 3], [1, 2], [1, 3], [2, 3]].map(|[a, b]| [t[a], t[b]]))
+        .map(|[a, b]| [a.min(b), a.max(b)])
+        .collect();
+    edges.sort();
+    edges.dedup();
+    Body {
+        distance_constraints: edges
+            .iter()
+            .map(|&[a, b]| {
+                let distance = particles[a as usize]
+                    .position
+                    .distance(particles[b as usize].position);
+                DistanceC::new([a, b], distance, 1e-3).unwrap()
+            })
+            .collect(),
+        tet_constraints: tets
+            .iter()
+            .map(|&t| TetrahedralVolumeC::from_particles(t, &particles, 1e-3).unwrap())
+            .collect(),
+        fiber_constraints: tets
+            .iter()
+            .map(|&t| FiberC::from_particles(t, &particles, Vec3::X, 1e-3).unwrap())
+            .collect(),
+        particles,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn downloaded_particles_follow_removals() {
+    let Some((device, queue)) = device() else {
+        return;
+    };
+    let mut gpu = pollster::block_on(GpuSimulation::new(&device, Default::default())).unwrap();
+    let removed = gpu.add_body(&device, &queue, muscle(1)).unwrap();
+    let kept = gpu.add_body(&device, &queue, muscle(2)).unwrap();
+    step(&mut gpu, &device, &queue, 1. / 60.);
+    download(&gpu, &device, &queue);
+    let mass = gpu.body_mass(kept).unwrap();
+    let center = gpu.body_center_of_mass(kept).unwrap();
+
+    gpu.remove_body(&device, &queue, removed).unwrap();
+    assert_eq!(gpu.body_mass(kept).unwrap(), mass);
+    assert_eq!(gpu.body_center_of_mass(kept).unwrap(), center);
+    assert_eq!(gpu.download_particles(&device, &queue).unwrap().len(), 12);
+}
+
+#[test]
+fn removed_bodies_free_their_particles() {
+    let Some((device, queue)) = device() else {
+        return;
+    };
+    let params = WorldParams {
+        ground: None,
+        ..Default::default()
+    };
+    let mut cpu = CpuSimulation::new(SolverType::Jacobi, params).unwrap();
+    let cpu_handle = cpu.add_body(muscle(2)).unwrap();
+    let mut gpu = pollster::block_on(GpuSimulation::new(&device, params)).unwrap();
+    let removed = gpu.add_body(&device, &queue, muscle(3)).unwrap();
+    let kept = gpu.add_body(&device, &queue, muscle(2)).unwrap();
+    assert_eq!(gpu.body_particles(kept).unwrap(), 16..28);
+
+    gpu.remove_body(&device, &queue, removed).unwrap();
+    assert_eq!(gpu.body_particles(kept).unwrap(), 0..12);
+    assert_eq!(gpu.particle_index(kept.particle(11)).unwrap(), 11);
+    assert!(matches!(
+        gpu.body_particles(removed),
+        Err(Error::Index(IndexError::InvalidBody(_)))
+    ));
+    assert!(gpu.remove_body(&device, &queue, removed).is_err());
+    // Handles are not reused
+    let added = gpu.add_body(&device, &queue, muscle(1)).unwrap();
+    assert_ne!(added, removed);
+    assert_eq!(gpu.body_particles(added).unwrap(), 12..20);
+
+    // The kept body is simulated as if it had always been alone
+    for _ in 0..30 {
+        cpu.simulate(1. / 60., false).unwrap();
+        step(&mut gpu, &device, &queue, 1. / 60.);
+    }
+    let particles = download(&gpu, &device, &queue);
+    assert_eq!(particles.len(), 20);
+    let range = cpu.body_particles(cpu_handle).unwrap();
+    for (g, c) in particles[..12]
+        .iter()
+        .zip(&cpu.particles()[range.start as usize..])
+    {
+        let difference = g.position.distance(c.position);
+        assert!(difference < 1e-3, "{} {}", g.position, c.position);
+    }
+}
+
+#[test]
+fn strain_cloth_matches_the_cpu() {
+    let Some((device, queue)) = device() else {
+        return;
+    };
+    // A 3 by 3 grid hanging from two corners, with warp and weft of different stiffness
+    let vertices: Vec<_> = (0..9)
+        .map(|i| Vec3::new((i % 3) as f32 * 0.5, 0., 1. - (i / 3) as f32 * 0.5))
+        .collect();
+    let triangles: Vec<_> = [0, 1, 3, 4]
+        .iter()
+        .flat_map(|&i| [[i, i + 1, i + 4], [i, i + 4, i + 3]])
+        .collect();
+    let cloth_params = ClothParams {
+        warp_compliance: 1e-4,
+        weft_compliance: 1e-2,
+        shear_compliance: 1e-3,
+        strain_constraints: true,
+        ..Default::default()
+    };
+    let mut body = Body::cloth(&vertices, &triangles, &cloth_params).unwrap();
+    body.particles[0].inv_mass = 0.;
+    body.particles[2].inv_mass = 0.;
+    // Several iterations accumulate the multipliers of the strains
+    let params = WorldParams {
+        ground: None,
+        damping: 2.,
+        iterations: 3,
+        ..Default::default()
+    };
+
+    let mut cpu = CpuSimulation::new(SolverType::Jacobi, params).unwrap();
+    cpu.add_body(body.clone()).unwrap();
+    let mut gpu = pollster::block_on(GpuSimulation::new(&device, params)).unwrap();
+    gpu.add_body(&device, &queue, body).unwrap();
+    for _ in 0..30 {
+        cpu.simulate(1. / 60., false).unwrap();
+        step(&mut gpu, &device, &queue, 1. / 60.);
+    }
+
+    let particles = download(&gpu, &device, &queue);
+    assert!(particles[8].position.z < 0.);
+    for (g, c) in particles.iter().zip(cpu.particles()) {
+        let difference = g.position.distance(c.position);
+        assert!(difference < 1e-3, "{} {}", g.position, c.position);
+    }
+}
+
+#[test]
+fn surface_volume_matches_the_cpu() {
+    let Some((device, queue)) = device() else {
+        return;
+    };
+    // A tetrahedron inflated to twice its volume, its edges resisting
+    let particles: Vec<_> = [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z]
+        .iter()
+        .map(|p| Particle::new(*p, 1.))
+        .collect();
+    let faces = [[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]];
+    let body = Body {
+        distance_constraints: [[0, 1], [0, 2], [0, 3], [1, 2], [1, 3], [2, 3]]
+            .iter()
+            .map(|&[a, b]| {
+                let distance = particles[a].position.distance(particles[b].position);
+                DistanceC::new([a as u32, b as u32], distance, 1e-2).unwrap()
+            })
+            .collect(),
+        surface_volume_constraints: vec![SurfaceVolumeC::from_particles(
+            &faces, &particles, 2., 1e-4,
+        )
+        .unwrap()],
+        particles,
+        ..Default::default()
+    };
+    // Several iterations accumulate the multiplier of the volume
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        damping: 2.,
+        jacobi_weight: 1.,
+        iterations: 3,
+        ..Default::default()
+    };
+
+    let mut cpu = CpuSimulation::new(SolverType::Jacobi, params).unwrap();
+    cpu.add_body(body.clone()).unwrap();
+    let mut gpu = pollster::block_on(GpuSimulation::new(&device, params)).unwrap();
+    gpu.add_body(&device, &queue, body).unwrap();
+    for _ in 0..30 {
+        cpu.simulate(1. / 60., false).unwrap();
+        step(&mut gpu, &device, &queue, 1. / 60.);
+    }
+
+    let particles = download(&gpu, &device, &queue);
+    assert!(particles[3].position.z > 1.1);
+    for (g, c) in particles.iter().zip(cpu.particles()) {
+        let difference = g.position.distance(c.position);
+        assert!(difference < 1e-3, "{} {}", g.position, c.position);
+    }
+}
+
+#[test]
+fn tethers_match_the_cpu() {
+    let Some((device, queue)) = device() else {
+        return;
+    };
+    // A stretchy rope hanging from its start, tethered to it
+    let mut particles: Vec<_> = (0..6)
+        .map(|i| Particle::new(Vec3::X * i as f32 * 0.2, 1.))
+        .collect();
+    particles[0].inv_mass = 0.;
+    let mut body = Body {
+        distance_constraints: (0..5)
+            .map(|i| DistanceC::new([i, i + 1], 0.2, 1e-1).unwrap())
+            .collect(),
+        particles,
+        ..Default::default()
+    };
+    body.tether_constraints = body.tethers(TetherDistance::Geodesic, 0.).unwrap();
+    let params = WorldParams {
+        ground: None,
+        damping: 2.,
+        ..Default::default()
+    };
+
+    let mut cpu = CpuSimulation::new(SolverType::Jacobi, params).unwrap();
+    cpu.add_body(body.clone()).unwrap();
+    let mut gpu = pollster::block_on(GpuSimulation::new(&device, params)).unwrap();
+    gpu.add_body(&device, &queue, body).unwrap();
+    for _ in 0..60 {
+        cpu.simulate(1. / 60., false).unwrap();
+        step(&mut gpu, &device, &queue, 1. / 60.);
+    }
+
+    let particles = download(&gpu, &device, &queue);
+    assert!(particles[5].position.length() < 1. + 1e-3);
+    for (g, c) in particles.iter().zip(cpu.particles()) {
+        let difference = g.position.distance(c.position);
+        assert!(difference < 1e-3, "{} {}", g.position, c.position);
+    }
+}
+
+#[test]
+fn constraint_updates_match_the_cpu() {
+    let Some((device, queue)) = device() else {
+        return;
+    };
+    let params = WorldParams {
+        ground: None,
+        damping: 2.,
+        ..Default::default()
+    };
+    let body = muscle(3);
+    let mut cpu = CpuSimulation::new(SolverType::Jacobi, params).unwrap();
+    let cpu_handle = cpu.add_body(body.clone()).unwrap();
+    // The body is moved down in the buffers by the removal of the one before it
+    let mut gpu = pollster::block_on(GpuSimulation::new(&device, params)).unwrap();
+    let removed = gpu.add_body(&device, &queue, muscle(2)).unwrap();
+    let gpu_handle = gpu.add_body(&device, &queue, body).unwrap();
+    gpu.remove_body(&device, &queue, removed).unwrap();
+
+    // Softens edges near the pinned end, disables some further along and shrinks the tets of
+    // the last cell
+    let soften = |c: &mut DistanceC| c.set_compliance(1e-1);
+    let shrink = |c: &mut TetrahedralVolumeC| c.set_rest_volume(c.rest_volume() * 0.5);
+    let disable = |c: &mut DistanceC| {
+        c.set_enabled(false);
+        Ok(())
+    };
+    cpu.update_distance_constraints(cpu_handle, ..10, soften)
+        .unwrap();
+    gpu.update_distance_constraints(&queue, gpu_handle, ..10, soften)
+        .unwrap();
+    cpu.update_tet_constraints(cpu_handle, 12.., shrink)
+        .unwrap();
+    gpu.update_tet_constraints(&queue, gpu_handle, 12.., shrink)
+        .unwrap();
+    cpu.update_distance_constraints(cpu_handle, 20..25, disable)
+        .unwrap();
+    gpu.update_distance_constraints(&queue, gpu_handle, 20..25, disable)
+        .unwrap();
+    for _ in 0..30 {
+        cpu.simulate(1. / 60., false).unwrap();
+        step(&mut gpu, &device, &queue, 1. / 60.);
+    }
+
+    let particles = download(&gpu, &device, &queue);
+    let range = gpu.body_particles(gpu_handle).unwrap();
+    let gpu_particles = &particles[range.start as usize..range.end as usize];
+    for (g, c) in gpu_particles.iter().zip(cpu.particles()) {
+        let difference = g.position.distance(c.position);
+        assert!(difference < 1e-3, "{} {}", g.position, c.position);
+    }
+}
+
+#[test]
+fn inverted_tets_are_counted_at_the_end_of_the_step() {
+    let Some((device, queue)) = device() else {
+        return;
+    };
+    // Two rigid tetrahedra turned inside out, only the first one enabled
+    let rest = [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z];
+    let mut particles: Vec<_> = rest
+        .iter()
+        .chain(&rest)
+        .map(|p| Particle::new(*p, 0.))
+        .collect();
+    let enabled = TetrahedralVolumeC::from_particles([0, 1, 2, 3], &particles, 0.).unwrap();
+    let mut disabled = TetrahedralVolumeC::from_particles([4, 5, 6, 7], &particles, 0.).unwrap();
+    disabled.set_enabled(false);
+    particles[3].position.z = -1.;
+    particles[7].position.z = -1.;
+    let body = Body {
+        particles,
+        tet_constraints: vec![enabled, disabled],
+        ..Default::default()
+    };
+    let params = WorldParams {
+        ground: None,
+        ..Default::default()
+    };
+
+    let mut cpu = CpuSimulation::new(SolverType::Jacobi, params).unwrap();
+    cpu.add_body(body.clone()).unwrap();
+    let report = cpu.simulate(1. / 60., false).unwrap();
+    assert_eq!(report.inverted_tets, 1);
+
+    let mut gpu = pollster::block_on(GpuSimulation::new(&device, params)).unwrap();
+    gpu.add_body(&device, &queue, body).unwrap();
+    step(&mut gpu, &device, &queue, 1. / 60.);
+    download(&gpu, &device, &queue);
+    assert_eq!(gpu.inverted_tets(), report.inverted_tets);
+}
+
+#[test]
+fn fiber_activation_and_morphs_match_the_cpu() {
+    let Some((device, queue)) = device() else {
+        return;
+    };
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        damping: 2.,
+        ..Default::default()
+    };
+    let body = muscle(4);
+    let stretched: Vec<_> = body
+        .particles
+        .iter()
+        .map(|p| p.position * Vec3::new(1.5, 1., 1.))
+        .collect();
+
+    let mut cpu = CpuSimulation::new(SolverType::Jacobi, params).unwrap();
+    let cpu_handle = cpu.add_body(body.clone()).unwrap();
+    // The body is moved down in the buffers by the removal of the one before it
+    let mut gpu = pollster::block_on(GpuSimulation::new(&device, params)).unwrap();
+    let removed = gpu.add_body(&device, &queue, muscle(2)).unwrap();
+    let gpu_handle = gpu.add_body(&device, &queue, body).unwrap();
+    gpu.remove_body(&device, &queue, removed).unwrap();
+
+    for frame in 0..60 {
+        match frame {
+            0 => {
+                cpu.morph_rest_shape(cpu_handle, &stretched, 0.25).unwrap();
+                gpu.morph_rest_shape(gpu_handle, &stretched, 0.25).unwrap();
+            }
+            30 => {
+                cpu.set_fiber_activation(cpu_handle, 0.3).unwrap();
+                gpu.set_fiber_activation(&queue, gpu_handle, 0.3).unwrap();
+            }
+            _ => {}
+        }
+        cpu.simulate(1. / 60., false).unwrap();
+        step(&mut gpu, &device, &queue, 1. / 60.);
+    }
+
+    let particles = download(&gpu, &device, &queue);
+    let range = gpu.body_particles(gpu_handle).unwrap();
+    let gpu_particles = &particles[range.start as usize..range.end as usize];
+    let range = cpu.body_particles(cpu_handle).unwrap();
+    let cpu_particles = &cpu.particles()[range.start as usize..range.end as usize];
+    for (g, c) in gpu_particles.iter().zip(cpu_particles) {
+        let difference = g.position.distance(c.position);
+        assert!(difference < 1e-3, "{} {}", g.position, c.position);
+    }
+}
+
+#[test]
+fn turbulent_wind_matches_the_cpu() {
+    let Some((device, queue)) = device() else {
+        return;
+    };
+    // A square of two triangles, held together by its edges and a diagonal
+    let particles: Vec<_> = [Vec3::ZERO, Vec3::Y, Vec3::Z, Vec3::Y + Vec3::Z]
+        .iter()
+        .map(|p| Particle::new(*p + Vec3::Z, 1.))
+        .collect();
+    let body = Body {
+        distance_constraints: [[0, 1], [0, 2], [1, 3], [2, 3], [1, 2]]
+            .iter()
+            .map(|&[a, b]| {
+                let distance = particles[a].position.distance(particles[b].position);
+                DistanceC::new([a as u32, b as u32], distance, 1e-4).unwrap()
+            })
+            .collect(),
+        aero_triangles: vec![
+            AeroTriangle::new([0, 1, 2], 1., 0.5).unwrap(),
+            AeroTriangle::new([1, 3, 2], 1., 0.5).unwrap(),
+        ],
+        particles,
+        ..Default::default()
+    };
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        ..Default::default()
+    };
+    let wind = Wind::Turbulent(Turbulence {
+        velocity: Vec3::new(5., 0., 0.),
+        amplitude: 3.,
+        frequency: 4.,
+        ..Default::default()
+    });
+
+    let mut cpu = CpuSimulation::new(SolverType::Jacobi, params).unwrap();
+    cpu.set_wind(wind.clone()).unwrap();
+    cpu.add_body(body.clone()).unwrap();
+    let mut gpu = pollster::block_on(GpuSimulation::new(&device, params)).unwrap();
+    gpu.set_wind(wind).unwrap();
+    gpu.add_body(&device, &queue, body).unwrap();
+    for _ in 0..30 {
+        cpu.simulate(1. / 60., false).unwrap();
+        step(&mut gpu, &device, &queue, 1. / 60.);
+    }
+
+    let particles = download(&gpu, &device, &queue);
+    for (g, c) in particles.iter().zip(cpu.particles()) {
+        let difference = g.position.distance(c.position);
+        assert!(difference < 1e-3, "{} {}", g.position, c.position);
+    }
+}
@@ -0,0 +1,102 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Error, Granular, GranularParams, Particle, WorldParams,
+};
+
+const RADIUS: f32 = 0.025;
+
+/// Grain positions of a slightly uneven column collapsing on the ground, after four seconds
+fn settled_column(params: GranularParams) -> Vec<Vec3> {
+    let max = Vec3::new(3., 3., 7.) * 2. * RADIUS;
+    let mut grains =
+        Granular::block(Vec3::Z * RADIUS, Vec3::Z * RADIUS + max, 1500., params).unwrap();
+    for (i, grain) in grains.particles.iter_mut().enumerate() {
+        let jitter = ((i as f32 * 0.618).fract() - 0.5) * 0.4 * RADIUS;
+        grain.position.x += jitter;
+        grain.position.y -= jitter;
+    }
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, WorldParams::default()).unwrap();
+    sim.add_granular(grains).unwrap();
+    for _ in 0..240 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    sim.particles().iter().map(|p| p.position).collect()
+}
+
+#[test]
+fn friction_keeps_grains_piled_up() {
+    let piled = settled_column(GranularParams::default());
+    let height = piled.iter().map(|p| p.z).fold(0., f32::max);
+    assert!(height > 1.5 * RADIUS, "{height}");
+    for (i, a) in piled.iter().enumerate() {
+        for b in &piled[i + 1..] {
+            assert!(a.distance(*b) > 1.95 * RADIUS, "{a} {b}");
+        }
+    }
+
+    let frictionless = GranularParams {
+        static_friction: 0.,
+        kinetic_friction: 0.,
+        tangential_damping: 0.,
+        ..Default::default()
+    };
+    let spread = settled_column(frictionless);
+    let height = spread.iter().map(|p| p.z).fold(0., f32::max);
+    assert!(height < 0.5 * RADIUS, "{height}");
+}
+
+/// Distance between two grains a small gap apart after a second without gravity
+fn gap_after(cohesion: f32) -> f32 {
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        ..Default::default()
+    };
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, params).unwrap();
+    sim.add_granular(Granular {
+        particles: vec![
+            Particle::new(Vec3::ZERO, 1.),
+            Particle::new(Vec3::X * 2.2 * RADIUS, 1.),
+        ],
+        params: GranularParams {
+            cohesion,
+            ..Default::default()
+        },
+    })
+    .unwrap();
+    for _ in 0..60 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    let [a, b] = [0, 1].map(|i| sim.particles()[i].position);
+    a.distance(b) - 2. * RADIUS
+}
+
+#[test]
+fn cohesion_pulls_nearby_grains_into_contact() {
+    let dry = gap_after(0.);
+    assert!((dry - 0.2 * RADIUS).abs() < 1e-6, "{dry}");
+    let wet = gap_after(0.5);
+    assert!(wet.abs() < 1e-3 * RADIUS, "{wet}");
+}
+
+#[test]
+fn invalid_granular_materials_are_rejected() {
+    let params = GranularParams {
+        radius: -1.,
+        ..Default::default()
+    };
+    assert!(Granular::block(Vec3::ZERO, Vec3::ONE, 1000., params).is_err());
+    assert!(Granular::block(Vec3::ZERO, Vec3::ONE, 0., GranularParams::default()).is_err());
+    let params = GranularParams {
+        tangential_damping: -0.1,
+        ..Default::default()
+    };
+    assert!(matches!(
+        Granular::block(Vec3::ZERO, Vec3::ONE, 1000., params),
+        Err(Error::InvalidParameter {
+            name: "tangential damping",
+            ..
+        })
+    ));
+}
@@ -0,0 +1,67 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, DistanceC, Error, IndexError, Particle, WorldParams,
+};
+
+fn body(particles_n: u32) -> Body {
+    Body {
+        particles: (0..particles_n)
+            .map(|i| Particle::new(Vec3::X * i as f32, 1.))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn particle_handles_are_local_to_their_body() {
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, WorldParams::default()).unwrap();
+    let first = sim.add_body(body(3)).unwrap();
+    let second = sim.add_body(body(2)).unwrap();
+    assert_eq!(sim.body_particles(second).unwrap(), 3..5);
+    assert_eq!(sim.particle_index(first.particle(2)).unwrap(), 2);
+    assert_eq!(sim.particle_index(second.particle(1)).unwrap(), 4);
+    assert!(matches!(
+        sim.particle_index(second.particle(2)),
+        Err(Error::Index(IndexError::ParticleOutOfRange {
+            particle_idx: 2,
+            particles_n: 2
+        }))
+    ));
+}
+
+#[test]
+fn handles_of_other_simulations_are_rejected() {
+    let mut other = CpuSimulation::new(SolverType::GaussSeidel, WorldParams::default()).unwrap();
+    other.add_body(body(1)).unwrap();
+    let foreign = other.add_body(body(1)).unwrap();
+
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, WorldParams::default()).unwrap();
+    sim.add_body(body(1)).unwrap();
+    assert!(matches!(
+        sim.body_particles(foreign),
+        Err(Error::Index(IndexError::InvalidBody(handle))) if handle == foreign
+    ));
+    assert!(sim.set_rigid_mode_damping(foreign, 0.5).is_err());
+}
+
+#[test]
+fn constraints_index_into_their_body() {
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, WorldParams::default()).unwrap();
+    sim.add_body(body(3)).unwrap();
+    let handle = sim.add_body(body(2)).unwrap();
+    sim.add_distance_constraints(handle, vec![DistanceC::new([0, 1], 1., 0.).unwrap()])
+        .unwrap();
+    assert!(matches!(
+        sim.add_distance_constraints(handle, vec![DistanceC::new([0, 2], 1., 0.).unwrap()]),
+        Err(Error::Index(IndexError::ParticleOutOfRange {
+            particle_idx: 2,
+            particles_n: 2
+        }))
+    ));
+    let invalid = Body {
+        distance_constraints: vec![DistanceC::new([0, 3], 1., 0.).unwrap()],
+        ..body(3)
+    };
+    assert!(sim.add_body(invalid).is_err());
+}
@@ -0,0 +1,101 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, DistanceC, Particle, TetrahedralVolumeC, WorldParams,
+};
+
+const REST: [Vec3; 4] = [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z];
+
+fn particles(inv_mass: f32) -> Vec<Particle> {
+    REST.iter()
+        .map(|p| Particle::new(*p + Vec3::Z, inv_mass))
+        .collect()
+}
+
+/// Rigid edges holding the apex of the tetrahedron once it recovered
+fn edges() -> Vec<DistanceC> {
+    [[0, 3], [1, 3], [2, 3]]
+        .map(|[a, b]| {
+            let distance = REST[a as usize].distance(REST[b as usize]);
+            DistanceC::new([a, b], distance, 0.).unwrap()
+        })
+        .to_vec()
+}
+
+#[test]
+fn tetrahedra_are_oriented_at_construction() {
+    let particles = particles(1.);
+    let tet = TetrahedralVolumeC::from_particles([0, 1, 3, 2], &particles, 0.).unwrap();
+    assert!((tet.volume(&particles) - 1. / 6.).abs() < 1e-6);
+}
+
+#[test]
+fn inverted_tetrahedra_recover() {
+    for solver in [SolverType::GaussSeidel, SolverType::Jacobi] {
+        let mut sim = CpuSimulation::new(solver, WorldParams::default()).unwrap();
+        // A pinned base, the edges alone would keep the apex mirrored through it
+        let mut particles = particles(0.);
+        particles[3].inv_mass = 1.;
+        let tet = TetrahedralVolumeC::from_particles([0, 1, 2, 3], &particles, 1e-3).unwrap();
+        particles[3].position.z = 0.;
+        sim.add_body(Body {
+            particles,
+            distance_constraints: edges(),
+            tet_constraints: vec![tet],
+            ..Default::default()
+        })
+        .unwrap();
+        for _ in 0..60 {
+            sim.simulate(1. / 60., false).unwrap();
+        }
+        let volume = tet.volume(sim.particles());
+        assert!((volume - 1. / 6.).abs() < 1e-2, "{volume}");
+    }
+}
+
+#[test]
+fn inverted_tetrahedra_are_hard_whatever_their_compliance() {
+    let solvers = [
+        SolverType::GaussSeidel,
+        SolverType::Jacobi,
+        SolverType::VertexBlockDescent,
+    ];
+    for solver in solvers {
+        let params = WorldParams {
+            gravity: Vec3::ZERO,
+            ..Default::default()
+        };
+        let mut sim = CpuSimulation::new(solver, params).unwrap();
+        let mut particles = particles(0.);
+        particles[3].inv_mass = 1.;
+        let tet = TetrahedralVolumeC::from_particles([0, 1, 2, 3], &particles, 1.).unwrap();
+        particles[3].position.z = 0.5;
+        sim.add_body(Body {
+            particles,
+            tet_constraints: vec![tet],
+            ..Default::default()
+        })
+        .unwrap();
+        let report = sim.simulate(1. / 60., false).unwrap();
+        assert_eq!(report.inverted_tets, 0);
+        let volume = tet.volume(sim.particles());
+        assert!(volume > -1e-3, "{volume}");
+    }
+}
+
+#[test]
+fn inverted_tetrahedra_are_reported() {
+    // Infinite masses can't move back
+    let mut particles = particles(0.);
+    let tet = TetrahedralVolumeC::from_particles([0, 1, 2, 3], &particles, 0.).unwrap();
+    particles[3].position.z = 0.5;
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, WorldParams::default()).unwrap();
+    sim.add_body(Body {
+        particles,
+        tet_constraints: vec![tet],
+        ..Default::default()
+    })
+    .unwrap();
+    let report = sim.simulate(1. / 60., false).unwrap();
+    assert_eq!(report.inverted_tets, 1);
+}
@@ -0,0 +1,116 @@
+use glam::{Quat, Vec3};
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    JointC, JointFrame, Motor, RigidBody, RigidBodyHandle, WorldParams,
+};
+
+/// Simulation with a bar lying along x from the origin, jointed to the world at the origin
+/// about the y axis
+fn jointed_bar(
+    gravity: Vec3,
+    joint: impl Fn([Option<RigidBodyHandle>; 2], [JointFrame; 2]) -> JointC,
+) -> (CpuSimulation, RigidBodyHandle, JointC) {
+    let params = WorldParams {
+        gravity,
+        ground: None,
+        ..Default::default()
+    };
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, params).unwrap();
+    let bar = RigidBody::cuboid(
+        Vec3::new(0.5, 0., 0.),
+        Quat::IDENTITY,
+        1.,
+        Vec3::new(0.5, 0.1, 0.1),
+    )
+    .unwrap();
+    let frames = [
+        JointFrame::from_world(None, Vec3::ZERO, Vec3::Y),
+        JointFrame::from_world(Some(&bar), Vec3::ZERO, Vec3::Y),
+    ];
+    let handle = sim.add_rigid_body(bar).unwrap();
+    let joint = joint([None, Some(handle)], frames);
+    sim.add_joints(vec![joint]).unwrap();
+    (sim, handle, joint)
+}
+
+fn run(sim: &mut CpuSimulation, steps: u32) {
+    for _ in 0..steps {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+}
+
+#[test]
+fn hinges_turn_about_their_axis_within_their_limits() {
+    let (mut sim, bar, joint) = jointed_bar(WorldParams::default().gravity, |b, f| {
+        JointC::hinge(b, f, Some([-0.3, 0.3]), None, 0.).unwrap()
+    });
+    run(&mut sim, 60);
+    let angle = sim.joint_angle(&joint);
+    assert!((angle.abs() - 0.3).abs() < 1e-2, "{angle}");
+    let bar = sim.rigid_body(bar).unwrap();
+    assert!(bar.local_to_world(Vec3::new(-0.5, 0., 0.)).length() < 1e-3);
+    assert!((bar.rotation * Vec3::Y).distance(Vec3::Y) < 1e-3);
+}
+
+#[test]
+fn hinge_motors_drive_the_angle() {
+    let motor = Motor::Velocity {
+        speed: 1.,
+        compliance: 0.,
+    };
+    let (mut sim, _, joint) = jointed_bar(Vec3::ZERO, |b, f| {
+        JointC::hinge(b, f, None, Some(motor), 0.).unwrap()
+    });
+    run(&mut sim, 30);
+    let angle = sim.joint_angle(&joint);
+    assert!((angle - 0.5).abs() < 1e-2, "{angle}");
+
+    let motor = Motor::Angle {
+        target: -1.,
+        compliance: 0.,
+    };
+    let (mut sim, _, joint) = jointed_bar(Vec3::ZERO, |b, f| {
+        JointC::hinge(b, f, None, Some(motor), 0.).unwrap()
+    });
+    run(&mut sim, 30);
+    let angle = sim.joint_angle(&joint);
+    assert!((angle + 1.).abs() < 1e-2, "{angle}");
+}
+
+#[test]
+fn prismatic_joints_slide_along_their_axis_within_their_limits() {
+    // Gravity along the joint axis and across it
+    let gravity = Vec3::new(0., 5., -5.);
+    let (mut sim, bar, _) = jointed_bar(gravity, |b, f| {
+        JointC::prismatic(b, f, Some([-0.5, 0.5]), 0.).unwrap()
+    });
+    run(&mut sim, 120);
+    let bar = sim.rigid_body(bar).unwrap();
+    let anchor = bar.local_to_world(Vec3::new(-0.5, 0., 0.));
+    assert!(anchor.x.abs() < 1e-3 && anchor.z.abs() < 1e-3, "{anchor}");
+    assert!((anchor.y - 0.5).abs() < 1e-3, "{anchor}");
+    let angle = bar.rotation.angle_between(Quat::IDENTITY);
+    assert!(angle < 1e-2, "{angle}");
+}
+
+#[test]
+fn fixed_joints_hold_the_body_in_place() {
+    let (mut sim, bar, _) = jointed_bar(WorldParams::default().gravity, |b, f| {
+        JointC::fixed(b, f, 0.).unwrap()
+    });
+    run(&mut sim, 60);
+    let bar = sim.rigid_body(bar).unwrap();
+    assert!(
+        bar.position.distance(Vec3::new(0.5, 0., 0.)) < 1e-2,
+        "{}",
+        bar.position
+    );
+    assert!(bar.rotation.angle_between(Quat::IDENTITY) < 1e-2);
+}
+
+#[test]
+fn invalid_limits_are_rejected() {
+    let frames = [JointFrame::new(Vec3::ZERO, Vec3::X); 2];
+    assert!(JointC::hinge([None, None], frames, Some([1., -1.]), None, 0.).is_err());
+    assert!(JointC::prismatic([None, None], frames, Some([0., f32::NAN]), 0.).is_err());
+}
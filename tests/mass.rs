@@ -0,0 +1,101 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    mass, Body, Error, Particle, TetrahedralVolumeC, WorldParams,
+};
+
+fn particles() -> Vec<Particle> {
+    [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z]
+        .iter()
+        .map(|p| Particle::new(*p, 1.))
+        .collect()
+}
+
+#[test]
+fn set_masses_round_trips_through_total_mass() {
+    let mut particles = particles();
+    let masses = mass::tet_masses(&particles, &[[0, 1, 2, 3]], 600.).unwrap();
+    // A sixth of a unit cube, split evenly
+    for m in &masses {
+        assert!((m - 25.).abs() < 1e-4, "{m}");
+    }
+    mass::set_masses(&mut particles, &masses);
+    assert!((mass::total_mass(&particles) - 100.).abs() < 1e-3);
+    let center = mass::center_of_mass(&particles).unwrap();
+    assert!(center.abs_diff_eq(Vec3::splat(0.25), 1e-6), "{center}");
+
+    let masses = mass::triangle_masses(&particles, &[[0, 1, 2]], 100., 0.03).unwrap();
+    assert_eq!(masses[3], 0.);
+    mass::set_masses(&mut particles, &masses);
+    assert_eq!(particles[3].inv_mass, 0.);
+    assert!((mass::total_mass(&particles) - 1.5).abs() < 1e-5);
+}
+
+#[test]
+fn bodies_get_their_mass_from_their_density() {
+    let particles = particles();
+    let mut body = Body {
+        tet_constraints: vec![
+            TetrahedralVolumeC::from_particles([0, 1, 2, 3], &particles, 0.).unwrap(),
+        ],
+        particles,
+        ..Default::default()
+    };
+    body.set_masses_from_density(600.).unwrap();
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, WorldParams::default()).unwrap();
+    sim.add_body(Body {
+        particles: vec![Particle::new(Vec3::ZERO, 1.)],
+        ..Default::default()
+    })
+    .unwrap();
+    let handle = sim.add_body(body).unwrap();
+    assert!((sim.body_mass(handle).unwrap() - 100.).abs() < 1e-3);
+    let center = sim.body_center_of_mass(handle).unwrap().unwrap();
+    assert!(center.abs_diff_eq(Vec3::splat(0.25), 1e-6), "{center}");
+}
+
+#[test]
+fn pins_are_kept_only_on_request() {
+    let masses = [2.; 4];
+    let mut pinned = particles();
+    pinned[0].inv_mass = 0.;
+    let mut overridden = pinned.clone();
+
+    mass::set_masses_keeping_pins(&mut pinned, &masses);
+    assert_eq!(pinned[0].inv_mass, 0.);
+    assert_eq!(pinned[1].inv_mass, 0.5);
+    assert_eq!(mass::total_mass(&pinned), 6.);
+
+    mass::set_masses(&mut overridden, &masses);
+    assert_eq!(overridden[0].inv_mass, 0.5);
+    assert_eq!(mass::total_mass(&overridden), 8.);
+}
+
+#[test]
+fn invalid_densities_are_rejected() {
+    let particles = particles();
+    for density in [-1., f32::NAN] {
+        assert!(matches!(
+            mass::tet_masses(&particles, &[[0, 1, 2, 3]], density),
+            Err(Error::InvalidParameter {
+                name: "density",
+                ..
+            })
+        ));
+        assert!(matches!(
+            mass::triangle_masses(&particles, &[[0, 1, 2]], density, 1.),
+            Err(Error::InvalidParameter {
+                name: "density",
+                ..
+            })
+        ));
+    }
+    assert!(matches!(
+        mass::triangle_masses(&particles, &[[0, 1, 2]], 1., f32::INFINITY),
+        Err(Error::InvalidParameter {
+            name: "thickness",
+            ..
+        })
+    ));
+    assert!(mass::tet_masses(&particles, &[[0, 1, 2, 4]], 1.).is_err());
+}
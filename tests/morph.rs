@@ -0,0 +1,134 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, BodyHandle, DistanceC, Error, Particle, TetrahedralVolumeC, WorldParams,
+};
+
+const REST: [Vec3; 4] = [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z];
+const EDGES: [[u32; 2]; 6] = [[0, 1], [0, 2], [0, 3], [1, 2], [1, 3], [2, 3]];
+
+/// A soft tetrahedron floating without gravity
+fn simulation(solver: SolverType) -> (CpuSimulation, BodyHandle) {
+    let particles: Vec<_> = REST.iter().map(|p| Particle::new(*p, 1.)).collect();
+    let body = Body {
+        distance_constraints: EDGES
+            .map(|[a, b]| {
+                let distance = REST[a as usize].distance(REST[b as usize]);
+                DistanceC::new([a, b], distance, 1e-4).unwrap()
+            })
+            .to_vec(),
+        tet_constraints: vec![
+            TetrahedralVolumeC::from_particles([0, 1, 2, 3], &particles, 1e-4).unwrap(),
+        ],
+        particles,
+        ..Default::default()
+    };
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        damping: 5.,
+        ..Default::default()
+    };
+    let mut sim = CpuSimulation::new(solver, params).unwrap();
+    let handle = sim.add_body(body).unwrap();
+    (sim, handle)
+}
+
+/// Edge lengths relative to those of `REST`
+fn scales(sim: &CpuSimulation) -> Vec<f32> {
+    let p = |i: u32| sim.particles()[i as usize].position;
+    EDGES
+        .iter()
+        .map(|&[a, b]| p(a).distance(p(b)) / REST[a as usize].distance(REST[b as usize]))
+        .collect()
+}
+
+#[test]
+fn bodies_settle_into_their_morphed_rest_shape() {
+    for solver in [SolverType::GaussSeidel, SolverType::ProjectiveDynamics] {
+        let (mut sim, handle) = simulation(solver);
+        sim.morph_rest_shape(handle, &REST.map(|p| p * 2.), 1.)
+            .unwrap();
+        for _ in 0..30 {
+            sim.simulate(1. / 60., false).unwrap();
+        }
+        // Halfway through the morph
+        for scale in scales(&sim) {
+            assert!((1.2..1.8).contains(&scale), "{scale}");
+        }
+        for _ in 0..150 {
+            sim.simulate(1. / 60., false).unwrap();
+        }
+        for scale in scales(&sim) {
+            assert!((scale - 2.).abs() < 1e-2, "{scale}");
+        }
+    }
+}
+
+/// How far the apex of a compliant tetrahedron sinks under its weight with Projective Dynamics
+/// once it settled, its base being pinned at `REST * 2.`, after morphing to that rest shape from
+/// `REST * from` over `duration`
+fn sunken_apex(from: f32, duration: f32) -> f32 {
+    let params = WorldParams {
+        ground: None,
+        damping: 5.,
+        ..Default::default()
+    };
+    let rest: Vec<_> = REST.iter().map(|p| Particle::new(*p * from, 1.)).collect();
+    let mut particles: Vec<_> = REST.iter().map(|p| Particle::new(*p * 2., 0.)).collect();
+    particles[3].inv_mass = 1.;
+    let mut sim = CpuSimulation::new(SolverType::ProjectiveDynamics, params).unwrap();
+    let handle = sim
+        .add_body(Body {
+            tet_constraints: vec![
+                TetrahedralVolumeC::from_particles([0, 1, 2, 3], &rest, 1e-2).unwrap(),
+            ],
+            particles,
+            ..Default::default()
+        })
+        .unwrap();
+    // Factorizes the system with the initial rest shape
+    sim.simulate(1. / 60., false).unwrap();
+    sim.morph_rest_shape(handle, &REST.map(|p| p * 2.), duration)
+        .unwrap();
+    for _ in 0..240 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    2. - sim.particles()[3].position.z
+}
+
+#[test]
+fn projective_dynamics_stiffness_follows_morphs() {
+    let built = sunken_apex(2., 0.);
+    let morphed = sunken_apex(1.5, 0.5);
+    assert!((morphed / built - 1.).abs() < 1e-2, "{morphed} {built}");
+    let set = sunken_apex(1.9, 0.);
+    assert!((set / built - 1.).abs() < 1e-2, "{set} {built}");
+}
+
+#[test]
+fn rest_shapes_can_be_set_at_once() {
+    let (mut sim, handle) = simulation(SolverType::GaussSeidel);
+    sim.set_rest_shape(handle, &REST.map(|p| p * 0.5)).unwrap();
+    for _ in 0..60 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    for scale in scales(&sim) {
+        assert!((scale - 0.5).abs() < 1e-2, "{scale}");
+    }
+}
+
+#[test]
+fn invalid_rest_shapes_are_rejected() {
+    let (mut sim, handle) = simulation(SolverType::GaussSeidel);
+    assert!(matches!(
+        sim.set_rest_shape(handle, &REST[..3]),
+        Err(Error::InvalidParameter {
+            name: "rest positions count",
+            ..
+        })
+    ));
+    let mut rest = REST;
+    rest[2].y = f32::NAN;
+    assert!(sim.morph_rest_shape(handle, &rest, 1.).is_err());
+}
@@ -0,0 +1,102 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, DistanceC, Particle, WorldParams,
+};
+
+/// Distance of a particle from a pinned one after a second, without gravity
+fn settled_distance(start: f32, constraint: DistanceC) -> f32 {
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        damping: 1.,
+        ..Default::default()
+    };
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, params).unwrap();
+    sim.add_body(Body {
+        particles: vec![
+            Particle::new(Vec3::ZERO, 0.),
+            Particle::new(Vec3::new(start, 0., 0.), 1.),
+        ],
+        distance_constraints: vec![constraint],
+        ..Default::default()
+    })
+    .unwrap();
+    for _ in 0..60 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    sim.particles()[1].position.length()
+}
+
+#[test]
+fn range_constraints_are_slack_inside_their_range() {
+    let range = DistanceC::range([0, 1], 1., 2., 0.).unwrap();
+    assert_eq!(settled_distance(1.5, range), 1.5);
+    assert_eq!(
+        settled_distance(0.5, DistanceC::at_most([0, 1], 1., 0.).unwrap()),
+        0.5
+    );
+    assert_eq!(
+        settled_distance(5., DistanceC::at_least([0, 1], 1., 0.).unwrap()),
+        5.
+    );
+}
+
+#[test]
+fn range_constraints_bring_particles_back_into_their_range() {
+    // The correction gives the particle a velocity that may carry it across the range
+    let range = DistanceC::range([0, 1], 1., 2., 0.).unwrap();
+    for start in [3., 0.5] {
+        let distance = settled_distance(start, range);
+        assert!(
+            (1. - 1e-3..=2. + 1e-3).contains(&distance),
+            "{start} {distance}"
+        );
+    }
+    let rope = settled_distance(3., DistanceC::at_most([0, 1], 1., 0.).unwrap());
+    assert!(rope <= 1. + 1e-3, "{rope}");
+    let cushion = settled_distance(0.5, DistanceC::at_least([0, 1], 1., 0.).unwrap());
+    assert!(cushion >= 1. - 1e-3, "{cushion}");
+}
+
+#[test]
+fn range_constraints_only_act_outside_their_range_with_projective_dynamics() {
+    // A unit mass hanging from a compliant spring, with or without a rope along it
+    let stretch = |rope: Option<f32>| {
+        let params = WorldParams {
+            ground: None,
+            damping: 5.,
+            ..Default::default()
+        };
+        let mut distance_constraints = vec![DistanceC::new([0, 1], 1., 1e-3).unwrap()];
+        if let Some(length) = rope {
+            distance_constraints.push(DistanceC::at_most([0, 1], length, 0.).unwrap());
+        }
+        let mut sim = CpuSimulation::new(SolverType::ProjectiveDynamics, params).unwrap();
+        sim.add_body(Body {
+            particles: vec![
+                Particle::new(Vec3::ZERO, 0.),
+                Particle::new(Vec3::new(0., 0., -1.), 1.),
+            ],
+            distance_constraints,
+            ..Default::default()
+        })
+        .unwrap();
+        for _ in 0..240 {
+            sim.simulate(1. / 60., false).unwrap();
+        }
+        -sim.particles()[1].position.z - 1.
+    };
+    let free = stretch(None);
+    let slack = stretch(Some(2.));
+    assert!((slack / free - 1.).abs() < 0.02, "{slack} {free}");
+    let taut = stretch(Some(1.002));
+    assert!((taut - 0.002).abs() < 1e-4, "{taut}");
+}
+
+#[test]
+fn invalid_ranges_are_rejected() {
+    assert!(DistanceC::range([0, 1], 2., 1., 0.).is_err());
+    assert!(DistanceC::range([0, 1], -1., 1., 0.).is_err());
+    assert!(DistanceC::range([0, 1], 0., f32::NAN, 0.).is_err());
+}
@@ -0,0 +1,89 @@
+use glam::{Quat, Vec3};
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    AttachmentC, Body, JointC, JointFrame, Particle, RigidBody, WorldParams,
+};
+
+fn simulation(gravity: Vec3) -> CpuSimulation {
+    let params = WorldParams {
+        gravity,
+        ground: None,
+        ..Default::default()
+    };
+    CpuSimulation::new(SolverType::GaussSeidel, params).unwrap()
+}
+
+fn run(sim: &mut CpuSimulation, steps: u32) {
+    for _ in 0..steps {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+}
+
+fn bar(position: Vec3) -> RigidBody {
+    RigidBody::cuboid(position, Quat::IDENTITY, 1., Vec3::new(0.5, 0.1, 0.1)).unwrap()
+}
+
+#[test]
+fn free_rigid_bodies_fall_with_gravity() {
+    let mut sim = simulation(WorldParams::default().gravity);
+    let body = sim.add_rigid_body(bar(Vec3::ZERO)).unwrap();
+    let fixed = sim
+        .add_rigid_body(RigidBody::fixed(Vec3::X, Quat::IDENTITY))
+        .unwrap();
+    run(&mut sim, 60);
+    let fallen = sim.rigid_body(body).unwrap().position;
+    assert!((fallen.z + 9.81 / 2.).abs() < 0.1, "{fallen}");
+    assert_eq!(sim.rigid_body(fixed).unwrap().position, Vec3::X);
+}
+
+#[test]
+fn positional_joints_keep_their_points_together() {
+    let mut sim = simulation(WorldParams::default().gravity);
+    let anchor = sim
+        .add_rigid_body(RigidBody::fixed(Vec3::ZERO, Quat::IDENTITY))
+        .unwrap();
+    let pendulum = sim.add_rigid_body(bar(Vec3::new(0.5, 0., 0.))).unwrap();
+    let frames = [Vec3::ZERO, Vec3::NEG_X * 0.5].map(|p| JointFrame::new(p, Vec3::X));
+    let joint = JointC::ball([Some(anchor), Some(pendulum)], frames, 0.).unwrap();
+    sim.add_joints(vec![joint]).unwrap();
+    run(&mut sim, 30);
+    let pendulum = sim.rigid_body(pendulum).unwrap();
+    assert!(pendulum.position.z < -0.2, "{}", pendulum.position);
+    let end = pendulum.local_to_world(Vec3::NEG_X * 0.5);
+    assert!(end.length() < 1e-3, "{end}");
+}
+
+#[test]
+fn angular_joints_keep_the_relative_rotation() {
+    let mut sim = simulation(Vec3::ZERO);
+    let mut spinning = bar(Vec3::ZERO);
+    spinning.angular_velocity = Vec3::new(0., 1., 2.);
+    let spinning = sim.add_rigid_body(spinning).unwrap();
+    let follower = sim.add_rigid_body(bar(Vec3::Y)).unwrap();
+    let frames = [JointFrame::new(Vec3::ZERO, Vec3::X); 2];
+    let joint = JointC::angular([Some(spinning), Some(follower)], frames, 0.).unwrap();
+    sim.add_joints(vec![joint]).unwrap();
+    run(&mut sim, 60);
+    let [a, b] = [spinning, follower].map(|h| sim.rigid_body(h).unwrap().rotation);
+    assert!(a.angle_between(Quat::IDENTITY) > 0.3, "{a}");
+    assert!(a.angle_between(b) < 1e-3, "{a} {b}");
+}
+
+#[test]
+fn attachments_hold_rigid_bodies_to_particles() {
+    let mut sim = simulation(WorldParams::default().gravity);
+    let soft = sim
+        .add_body(Body {
+            particles: vec![Particle::new(Vec3::ZERO, 0.)],
+            ..Default::default()
+        })
+        .unwrap();
+    let hanging = sim.add_rigid_body(bar(Vec3::new(0.5, 0., 0.))).unwrap();
+    let attachment = AttachmentC::new(soft.particle(0), hanging, Vec3::NEG_X * 0.5, 0.).unwrap();
+    sim.add_attachments(vec![attachment]).unwrap();
+    run(&mut sim, 30);
+    let hanging = sim.rigid_body(hanging).unwrap();
+    assert!(hanging.position.z < -0.2, "{}", hanging.position);
+    let end = hanging.local_to_world(Vec3::NEG_X * 0.5);
+    assert!(end.length() < 1e-3, "{end}");
+}
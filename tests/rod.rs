@@ -0,0 +1,75 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Rod, WorldParams,
+};
+
+/// Particles and segment directions of a horizontal rod clamped at its start, after sagging
+/// under gravity for two seconds
+fn sagged_rod(bend_twist_compliance: f32) -> (Vec<Vec3>, Vec<Vec3>) {
+    let params = WorldParams {
+        ground: None,
+        damping: 2.,
+        // Rod constraints are solved anew every iteration, so they need substeps to stiffen
+        substeps: 40,
+        ..Default::default()
+    };
+    let points: Vec<_> = (0..11).map(|i| Vec3::X * i as f32 * 0.1).collect();
+    let mut rod = Rod::from_polyline(
+        &points,
+        0.01,
+        1000.,
+        1e-6,
+        Vec3::splat(bend_twist_compliance),
+    )
+    .unwrap();
+    rod.particles[0].inv_mass = 0.;
+    rod.particles[1].inv_mass = 0.;
+    rod.orientations[0].inv_inertia = 0.;
+
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, params).unwrap();
+    let handle = sim.add_rod(rod).unwrap();
+    for _ in 0..120 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    let orientations = sim.rod_orientations(handle).unwrap();
+    (
+        sim.particles().iter().map(|p| p.position).collect(),
+        sim.quat_particles()[orientations.start as usize..orientations.end as usize]
+            .iter()
+            .map(|q| q.rotation * Vec3::Z)
+            .collect(),
+    )
+}
+
+#[test]
+fn rod_segments_keep_their_length_and_follow_their_orientation() {
+    let (positions, directions) = sagged_rod(1e-2);
+    assert!(
+        positions[10].z < -0.1,
+        "the rod did not sag: {}",
+        positions[10]
+    );
+    for (segment, direction) in positions.windows(2).zip(directions) {
+        let d = segment[1] - segment[0];
+        assert!((d.length() - 0.1).abs() < 1e-3, "{}", d.length());
+        assert!(d.normalize().distance(direction) < 1e-2, "{d} {direction}");
+    }
+}
+
+#[test]
+fn stiffer_rods_bend_less() {
+    let (soft, _) = sagged_rod(1e-2);
+    let (stiff, _) = sagged_rod(1e-6);
+    assert!(stiff[10].z > soft[10].z, "{} {}", stiff[10], soft[10]);
+    assert!(stiff[10].z > -0.05, "{}", stiff[10]);
+}
+
+#[test]
+fn invalid_rods_are_rejected() {
+    assert!(Rod::from_polyline(&[Vec3::ZERO], 0.01, 1000., 0., Vec3::ZERO).is_err());
+    for (radius, density) in [(-0.01, 1000.), (0., 1000.), (0.01, 0.), (0.01, f32::NAN)] {
+        let segment = [Vec3::ZERO, Vec3::X];
+        assert!(Rod::from_polyline(&segment, radius, density, 0., Vec3::ZERO).is_err());
+    }
+}
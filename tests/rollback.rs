@@ -0,0 +1,80 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    AeroTriangle, Body, BodyHandle, DistanceC, Particle, StabilityParams, TetrahedralVolumeC,
+    Turbulence, Wind, WorldParams,
+};
+
+/// A tetrahedron morphing to twice its size in a turbulent wind
+fn simulation(stability: StabilityParams) -> (CpuSimulation, BodyHandle) {
+    let positions = [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z].map(|p| p + Vec3::Z);
+    let particles: Vec<_> = positions.iter().map(|p| Particle::new(*p, 1.)).collect();
+    let edges = [[0, 1], [0, 2], [0, 3], [1, 2], [1, 3], [2, 3]];
+    let body = Body {
+        distance_constraints: edges
+            .iter()
+            .map(|&[a, b]| {
+                let distance = positions[a as usize].distance(positions[b as usize]);
+                DistanceC::new([a, b], distance, 1e-4).unwrap()
+            })
+            .collect(),
+        tet_constraints: vec![
+            TetrahedralVolumeC::from_particles([0, 1, 2, 3], &particles, 0.).unwrap(),
+        ],
+        aero_triangles: vec![AeroTriangle::new([1, 2, 3], 1., 0.5).unwrap()],
+        particles,
+        ..Default::default()
+    };
+    let params = WorldParams {
+        stability,
+        ..Default::default()
+    };
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, params).unwrap();
+    sim.set_wind(Wind::Turbulent(Turbulence {
+        velocity: Vec3::new(5., 0., 0.),
+        ..Default::default()
+    }))
+    .unwrap();
+    let handle = sim.add_body(body).unwrap();
+    sim.morph_rest_shape(handle, &positions.map(|p| p * 2.), 0.5)
+        .unwrap();
+    (sim, handle)
+}
+
+fn positions(sim: &CpuSimulation) -> Vec<Vec3> {
+    sim.particles().iter().map(|p| p.position).collect()
+}
+
+#[test]
+fn unstable_steps_are_retried_with_more_substeps() {
+    let (mut sim, _) = simulation(StabilityParams {
+        max_speed: 0.,
+        max_retries: 2,
+    });
+    let report = sim.simulate(1. / 60., false).unwrap();
+    assert!(!report.stable);
+    assert_eq!(report.instabilities.len(), 3);
+    assert_eq!(report.substeps, WorldParams::default().substeps * 4);
+}
+
+#[test]
+fn failed_steps_leave_the_simulation_unchanged() {
+    let (mut failing, _) = simulation(StabilityParams {
+        max_speed: 0.,
+        max_retries: 1,
+    });
+    let (mut reference, _) = simulation(Default::default());
+    let before = positions(&failing);
+    for _ in 0..3 {
+        assert!(!failing.simulate(1. / 60., false).unwrap().stable);
+        assert_eq!(positions(&failing), before);
+    }
+
+    // The particles, rest shape morph and time all pick up where they were
+    failing.set_world_params(WorldParams::default()).unwrap();
+    for _ in 0..10 {
+        assert!(failing.simulate(1. / 60., false).unwrap().stable);
+        assert!(reference.simulate(1. / 60., false).unwrap().stable);
+        assert_eq!(positions(&failing), positions(&reference));
+    }
+}
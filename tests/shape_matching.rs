@@ -0,0 +1,88 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, Particle, ShapeMatchingC, ShapeMatchingMode, WorldParams,
+};
+
+/// Corners of a unit cube after a second of being twisted by opposite pushes on two corners,
+/// without gravity
+fn twisted_cube(solver: SolverType, mode: ShapeMatchingMode) -> Vec<Vec3> {
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        ..Default::default()
+    };
+    let mut particles: Vec<_> = (0..8)
+        .map(|i| {
+            let corner = Vec3::new((i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2) as f32);
+            Particle::new(corner, 1.)
+        })
+        .collect();
+    particles[0].ext_acc = Vec3::Y * 10.;
+    particles[7].ext_acc = Vec3::Y * -10.;
+    let cluster = ShapeMatchingC::new((0..8).collect(), &particles, 0.5, mode).unwrap();
+    let mut sim = CpuSimulation::new(solver, params).unwrap();
+    sim.add_body(Body {
+        particles,
+        shape_matching_constraints: vec![cluster],
+        ..Default::default()
+    })
+    .unwrap();
+    for _ in 0..60 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    sim.particles().iter().map(|p| p.position).collect()
+}
+
+#[test]
+fn rigid_clusters_keep_their_shape() {
+    for solver in [SolverType::GaussSeidel, SolverType::Jacobi] {
+        let corners = twisted_cube(solver, ShapeMatchingMode::Rigid);
+        assert!(corners[0].y > 0.5, "the cube did not turn: {}", corners[0]);
+        for (i, a) in corners.iter().enumerate() {
+            for (j, b) in corners.iter().enumerate().skip(i + 1) {
+                let rest = ((i ^ j).count_ones() as f32).sqrt();
+                assert!((a.distance(*b) - rest).abs() < 0.05, "{a} {b}");
+            }
+        }
+    }
+}
+
+#[test]
+fn linear_clusters_deform_more_than_rigid_ones() {
+    // Mean deviation of the edges of the cube from their rest length
+    let deviation = |corners: Vec<Vec3>| {
+        let edges: Vec<_> = (0..8u32)
+            .flat_map(|i| [1, 2, 4].map(|bit| (i, i | bit)))
+            .filter(|(i, j)| i != j)
+            .collect();
+        edges
+            .iter()
+            .map(|(i, j)| (corners[*i as usize].distance(corners[*j as usize]) - 1.).abs())
+            .sum::<f32>()
+            / edges.len() as f32
+    };
+    let rigid = deviation(twisted_cube(
+        SolverType::GaussSeidel,
+        ShapeMatchingMode::Rigid,
+    ));
+    let linear = deviation(twisted_cube(
+        SolverType::GaussSeidel,
+        ShapeMatchingMode::Linear { beta: 1. },
+    ));
+    assert!(linear > rigid, "{linear} {rigid}");
+}
+
+#[test]
+fn invalid_clusters_are_rejected() {
+    let particles = vec![Particle::new(Vec3::ZERO, 1.), Particle::new(Vec3::X, 1.)];
+    assert!(ShapeMatchingC::new(vec![0, 1], &particles, 1.5, ShapeMatchingMode::Rigid).is_err());
+    assert!(ShapeMatchingC::new(
+        vec![0, 1],
+        &particles,
+        1.,
+        ShapeMatchingMode::Quadratic { beta: -1. }
+    )
+    .is_err());
+    assert!(ShapeMatchingC::new(vec![0, 2], &particles, 1., ShapeMatchingMode::Rigid).is_err());
+}
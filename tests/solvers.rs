@@ -0,0 +1,84 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, DistanceC, Particle, TetrahedralVolumeC, WorldParams,
+};
+
+/// Stretch of a compliant spring a unit mass hangs from once it settled
+fn hanging_stretch(solver: SolverType, substeps: u32) -> f32 {
+    let params = WorldParams {
+        ground: None,
+        damping: 5.,
+        substeps,
+        ..Default::default()
+    };
+    let mut sim = CpuSimulation::new(solver, params).unwrap();
+    sim.add_body(Body {
+        particles: vec![
+            Particle::new(Vec3::ZERO, 0.),
+            Particle::new(Vec3::new(0., 0., -1.), 1.),
+        ],
+        distance_constraints: vec![DistanceC::new([0, 1], 1., 1e-3).unwrap()],
+        ..Default::default()
+    })
+    .unwrap();
+    for _ in 0..240 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    -sim.particles()[1].position.z - 1.
+}
+
+/// Checks that the spring settles on the static stretch m g α whatever the substeps
+fn assert_static_stretch(solver: SolverType) {
+    for substeps in [5, 10, 20] {
+        let stretch = hanging_stretch(solver, substeps);
+        assert!((stretch - 9.81e-3).abs() < 2e-4, "{substeps}: {stretch}");
+    }
+}
+
+#[test]
+fn vertex_block_descent_matches_xpbd_stiffness() {
+    assert_static_stretch(SolverType::VertexBlockDescent);
+}
+
+#[test]
+fn projective_dynamics_matches_xpbd_stiffness() {
+    assert_static_stretch(SolverType::ProjectiveDynamics);
+}
+
+/// How far the apex of a compliant tetrahedron sinks under its weight once it settled, its base
+/// being pinned
+fn sunken_apex(solver: SolverType) -> f32 {
+    let params = WorldParams {
+        ground: None,
+        damping: 5.,
+        ..Default::default()
+    };
+    let mut particles: Vec<_> = [Vec3::ZERO, Vec3::X * 2., Vec3::Y * 2., Vec3::Z * 2.]
+        .iter()
+        .map(|p| Particle::new(*p, 0.))
+        .collect();
+    particles[3].inv_mass = 1.;
+    let mut sim = CpuSimulation::new(solver, params).unwrap();
+    sim.add_body(Body {
+        tet_constraints: vec![
+            TetrahedralVolumeC::from_particles([0, 1, 2, 3], &particles, 1e-2).unwrap(),
+        ],
+        particles,
+        ..Default::default()
+    })
+    .unwrap();
+    for _ in 0..240 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    2. - sim.particles()[3].position.z
+}
+
+#[test]
+fn projective_dynamics_matches_xpbd_volume_stiffness() {
+    let xpbd = sunken_apex(SolverType::GaussSeidel);
+    let pd = sunken_apex(SolverType::ProjectiveDynamics);
+    // m g α / |∇C|² for a base of area 2
+    assert!((xpbd - 9.81e-2 / 16.).abs() < 2e-4, "{xpbd}");
+    assert!((pd / xpbd - 1.).abs() < 0.05, "{pd} {xpbd}");
+}
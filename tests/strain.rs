@@ -0,0 +1,64 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, Particle, TriangleStrainC, WorldParams,
+};
+
+/// Stretch along y of a right triangle with its edge along x pinned, pulled at its third corner
+/// along y for two seconds, the warp running along x
+fn weft_stretch(solver: SolverType, compliance: Vec3, iterations: u32) -> f32 {
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        damping: 5.,
+        iterations,
+        ..Default::default()
+    };
+    let mut particles = vec![
+        Particle::new(Vec3::ZERO, 0.),
+        Particle::new(Vec3::X, 0.),
+        Particle::new(Vec3::Y, 1.),
+    ];
+    particles[2].ext_acc = Vec3::Y * 10.;
+    let strain = TriangleStrainC::from_particles([0, 1, 2], &particles, Vec3::X, compliance);
+    let mut sim = CpuSimulation::new(solver, params).unwrap();
+    sim.add_body(Body {
+        particles,
+        triangle_strain_constraints: vec![strain.unwrap()],
+        ..Default::default()
+    })
+    .unwrap();
+    for _ in 0..120 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    sim.particles()[2].position.y - 1.
+}
+
+#[test]
+fn strain_resists_stretch_along_each_material_axis_separately() {
+    for solver in [SolverType::GaussSeidel, SolverType::Jacobi] {
+        let stiff = weft_stretch(solver, Vec3::ZERO, 1);
+        assert!(stiff.abs() < 1e-3, "{stiff}");
+        let soft_weft = weft_stretch(solver, Vec3::new(0., 1e-2, 0.), 1);
+        assert!(soft_weft > 0.02, "{soft_weft}");
+        let soft_warp = weft_stretch(solver, Vec3::new(1e-2, 0., 0.), 1);
+        assert!(soft_warp.abs() < 1e-3, "{soft_warp}");
+    }
+}
+
+#[test]
+fn strain_compliance_does_not_depend_on_iterations() {
+    for solver in [SolverType::GaussSeidel, SolverType::Jacobi] {
+        let one = weft_stretch(solver, Vec3::new(0., 1e-2, 0.), 1);
+        let several = weft_stretch(solver, Vec3::new(0., 1e-2, 0.), 5);
+        assert!((several / one - 1.).abs() < 0.02, "{several} {one}");
+    }
+}
+
+#[test]
+fn degenerate_triangles_are_rejected() {
+    let rest = [Vec3::ZERO, Vec3::X, Vec3::X * 2.];
+    assert!(TriangleStrainC::new([0, 1, 2], rest, Vec3::X, Vec3::ZERO).is_err());
+    let rest = [Vec3::ZERO, Vec3::X, Vec3::Y];
+    assert!(TriangleStrainC::new([0, 1, 2], rest, Vec3::X, Vec3::splat(-1.)).is_err());
+}
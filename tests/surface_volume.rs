@@ -0,0 +1,98 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, DistanceC, Particle, SurfaceVolumeC, WorldParams,
+};
+
+const CORNERS: [Vec3; 4] = [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::Z];
+/// Faces of the tetrahedron of `CORNERS`, counterclockwise seen from outside
+const FACES: [[u32; 3]; 4] = [[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]];
+
+/// Volume enclosed by the surface of a tetrahedron inflated for a second without gravity
+fn inflated_volume(solver: SolverType, pressure: f32) -> f32 {
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        damping: 5.,
+        // Over-relaxing a lone constraint makes it oscillate
+        jacobi_weight: 1.,
+        ..Default::default()
+    };
+    let particles: Vec<_> = CORNERS.iter().map(|p| Particle::new(*p, 1.)).collect();
+    let surface = SurfaceVolumeC::from_particles(&FACES, &particles, pressure, 0.).unwrap();
+    let mut sim = CpuSimulation::new(solver, params).unwrap();
+    sim.add_body(Body {
+        particles,
+        surface_volume_constraints: vec![surface.clone()],
+        ..Default::default()
+    })
+    .unwrap();
+    for _ in 0..60 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    surface.volume(sim.particles())
+}
+
+#[test]
+fn surfaces_enclose_their_rest_volume_times_the_pressure() {
+    let particles: Vec<_> = CORNERS.iter().map(|p| Particle::new(*p, 1.)).collect();
+    let surface = SurfaceVolumeC::from_particles(&FACES, &particles, 1., 0.).unwrap();
+    assert!((surface.volume(&particles) - 1. / 6.).abs() < 1e-6);
+
+    for solver in [SolverType::GaussSeidel, SolverType::Jacobi] {
+        for pressure in [0.8, 2.] {
+            let volume = inflated_volume(solver, pressure);
+            assert!((volume * 6. - pressure).abs() < 1e-2, "{pressure} {volume}");
+        }
+    }
+}
+
+/// Volume reached by a tetrahedron pressurized to twice its rest volume against compliant edges
+fn balloon_volume(solver: SolverType, iterations: u32) -> f32 {
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        damping: 5.,
+        jacobi_weight: 1.,
+        iterations,
+        ..Default::default()
+    };
+    let particles: Vec<_> = CORNERS.iter().map(|p| Particle::new(*p, 1.)).collect();
+    let surface = SurfaceVolumeC::from_particles(&FACES, &particles, 2., 1e-2).unwrap();
+    let edges = [[0, 1], [0, 2], [0, 3], [1, 2], [1, 3], [2, 3]].map(|[a, b]| {
+        let distance = CORNERS[a as usize].distance(CORNERS[b as usize]);
+        DistanceC::new([a, b], distance, 1e-2).unwrap()
+    });
+    let mut sim = CpuSimulation::new(solver, params).unwrap();
+    sim.add_body(Body {
+        particles,
+        distance_constraints: edges.to_vec(),
+        surface_volume_constraints: vec![surface.clone()],
+        ..Default::default()
+    })
+    .unwrap();
+    for _ in 0..120 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    surface.volume(sim.particles())
+}
+
+#[test]
+fn volume_compliance_does_not_depend_on_iterations() {
+    for solver in [SolverType::GaussSeidel, SolverType::Jacobi] {
+        let one = balloon_volume(solver, 1);
+        let several = balloon_volume(solver, 5);
+        assert!(one * 6. > 1.05 && one * 6. < 1.9, "{one}");
+        assert!((several / one - 1.).abs() < 0.02, "{several} {one}");
+    }
+}
+
+#[test]
+fn invalid_surfaces_are_rejected() {
+    assert!(SurfaceVolumeC::new(&FACES, 0., 1., 0.).is_err());
+    assert!(SurfaceVolumeC::new(&FACES, 1., -1., 0.).is_err());
+    // Inside out
+    let particles: Vec<_> = CORNERS.iter().map(|p| Particle::new(*p, 1.)).collect();
+    let inverted = FACES.map(|[a, b, c]| [a, c, b]);
+    assert!(SurfaceVolumeC::from_particles(&inverted, &particles, 1., 0.).is_err());
+}
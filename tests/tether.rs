@@ -0,0 +1,98 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, DistanceC, Particle, TetherDistance, WorldParams,
+};
+
+/// An L shaped rope of unit segments pinned at its start, bending at its third particle, with
+/// a loose particle besides it
+fn rope() -> Body {
+    let positions = [Vec3::ZERO, Vec3::X, Vec3::X * 2., Vec3::new(2., 1., 0.)];
+    let mut particles: Vec<_> = positions.iter().map(|p| Particle::new(*p, 1.)).collect();
+    particles[0].inv_mass = 0.;
+    particles.push(Particle::new(Vec3::Z, 1.));
+    Body {
+        distance_constraints: (0..3)
+            .map(|i| DistanceC::new([i, i + 1], 1., 1.).unwrap())
+            .collect(),
+        particles,
+        ..Default::default()
+    }
+}
+
+/// Distance of the end of the rope from its pinned start after being pulled along x for two
+/// seconds without gravity
+fn pulled_end(tethers: Option<TetherDistance>) -> f32 {
+    pulled_end_with(tethers, 0., 1)
+}
+
+fn pulled_end_with(tethers: Option<TetherDistance>, compliance: f32, iterations: u32) -> f32 {
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        damping: 5.,
+        iterations,
+        ..Default::default()
+    };
+    let mut body = rope();
+    if let Some(distance) = tethers {
+        body.tether_constraints = body.tethers(distance, compliance).unwrap();
+    }
+    body.particles[3].ext_acc = Vec3::X * 50.;
+    let mut sim = CpuSimulation::new(SolverType::GaussSeidel, params).unwrap();
+    sim.add_body(body).unwrap();
+    for _ in 0..120 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    sim.particles()[3].position.length()
+}
+
+#[test]
+fn tethers_are_generated_for_particles_connected_to_a_pin() {
+    let tethers = rope().tethers(TetherDistance::Geodesic, 0.).unwrap();
+    assert_eq!(tethers.len(), 3);
+    assert!(rope().tethers(TetherDistance::Euclidean, -1.).is_err());
+}
+
+#[test]
+fn tethers_keep_particles_within_their_distance_of_the_anchor() {
+    let untethered = pulled_end(None);
+    assert!(untethered > 3.5, "{untethered}");
+    let geodesic = pulled_end(Some(TetherDistance::Geodesic));
+    assert!((geodesic - 3.).abs() < 1e-3, "{geodesic}");
+    let euclidean = pulled_end(Some(TetherDistance::Euclidean));
+    assert!((euclidean - 5f32.sqrt()).abs() < 1e-3, "{euclidean}");
+}
+
+#[test]
+fn tether_compliance_does_not_depend_on_iterations() {
+    let one = pulled_end_with(Some(TetherDistance::Geodesic), 1e-2, 1);
+    assert!(one > 3.1 && one < pulled_end(None), "{one}");
+    let several = pulled_end_with(Some(TetherDistance::Geodesic), 1e-2, 5);
+    assert!((several / one - 1.).abs() < 0.01, "{several} {one}");
+}
+
+#[test]
+fn tethers_let_particles_move_closer_to_the_anchor() {
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        ..Default::default()
+    };
+    let pushed_end = |tethered: bool| {
+        let mut body = rope();
+        if tethered {
+            body.tether_constraints = body.tethers(TetherDistance::Euclidean, 0.).unwrap();
+        }
+        body.particles[3].ext_acc = Vec3::NEG_X * 2.;
+        let mut sim = CpuSimulation::new(SolverType::GaussSeidel, params).unwrap();
+        sim.add_body(body).unwrap();
+        for _ in 0..30 {
+            sim.simulate(1. / 60., false).unwrap();
+        }
+        sim.particles()[3].position
+    };
+    let end = pushed_end(true);
+    assert!(end.length() < 5f32.sqrt() - 0.1, "{end}");
+    assert_eq!(end, pushed_end(false));
+}
@@ -0,0 +1,133 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, BodyHandle, DistanceC, Error, IndexError, Particle, WorldParams,
+};
+
+/// A pinned particle tied to particles at unit distance along x and y
+fn star() -> Body {
+    Body {
+        particles: vec![
+            Particle::new(Vec3::ZERO, 0.),
+            Particle::new(Vec3::X, 1.),
+            Particle::new(Vec3::Y, 1.),
+        ],
+        distance_constraints: vec![
+            DistanceC::new([0, 1], 1., 0.).unwrap(),
+            DistanceC::new([0, 2], 1., 0.).unwrap(),
+        ],
+        ..Default::default()
+    }
+}
+
+/// Distance of the tied particles of every body from their pinned particle after a second
+fn distances(sim: &mut CpuSimulation, bodies: &[BodyHandle]) -> Vec<[f32; 2]> {
+    for _ in 0..60 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    bodies
+        .iter()
+        .map(|h| {
+            let range = sim.body_particles(*h).unwrap();
+            let [pin, x, y] = [0, 1, 2].map(|i| sim.particles()[(range.start + i) as usize]);
+            [x, y].map(|p| p.position.distance(pin.position))
+        })
+        .collect()
+}
+
+fn weightless(solver: SolverType) -> CpuSimulation {
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        damping: 5.,
+        ..Default::default()
+    };
+    CpuSimulation::new(solver, params).unwrap()
+}
+
+#[test]
+fn updates_apply_to_the_constraints_in_range_of_the_body() {
+    for solver in [SolverType::GaussSeidel, SolverType::ProjectiveDynamics] {
+        let mut sim = weightless(solver);
+        let first = sim.add_body(star()).unwrap();
+        let second = sim.add_body(star()).unwrap();
+        sim.update_distance_constraints(second, 1.., |c| c.set_rest_distance(1.5))
+            .unwrap();
+        let distances = distances(&mut sim, &[first, second]);
+        for (distance, expected) in distances.iter().flatten().zip([1., 1., 1., 1.5]) {
+            assert!((distance - expected).abs() < 1e-3, "{distances:?}");
+        }
+    }
+}
+
+#[test]
+fn failed_updates_change_nothing() {
+    let mut sim = weightless(SolverType::GaussSeidel);
+    let handle = sim.add_body(star()).unwrap();
+    let mut updated = 0;
+    let result = sim.update_distance_constraints(handle, .., |c| {
+        updated += 1;
+        c.set_rest_distance(2.)?;
+        match updated {
+            1 => Ok(()),
+            _ => c.set_compliance(-1.),
+        }
+    });
+    assert!(matches!(
+        result,
+        Err(Error::InvalidParameter {
+            name: "compliance",
+            ..
+        })
+    ));
+    assert_eq!(distances(&mut sim, &[handle]), [[1., 1.]]);
+
+    assert!(matches!(
+        sim.update_distance_constraints(handle, 1..3, |_| Ok(())),
+        Err(Error::Index(IndexError::ConstraintOutOfRange {
+            constraint_idx: 2,
+            constraints_n: 2
+        }))
+    ));
+}
+
+#[test]
+fn compliance_and_enabled_updates_reach_projective_dynamics() {
+    let params = WorldParams {
+        ground: None,
+        damping: 5.,
+        ..Default::default()
+    };
+    let mut sim = CpuSimulation::new(SolverType::ProjectiveDynamics, params).unwrap();
+    let handle = sim
+        .add_body(Body {
+            particles: vec![
+                Particle::new(Vec3::ZERO, 0.),
+                Particle::new(Vec3::NEG_Z, 1.),
+            ],
+            distance_constraints: vec![DistanceC::new([0, 1], 1., 1e-3).unwrap()],
+            ..Default::default()
+        })
+        .unwrap();
+    let hanging_stretch = |sim: &mut CpuSimulation| {
+        for _ in 0..240 {
+            sim.simulate(1. / 60., false).unwrap();
+        }
+        -sim.particles()[1].position.z - 1.
+    };
+    let stiff = hanging_stretch(&mut sim);
+    assert!((stiff - 9.81e-3).abs() < 2e-4, "{stiff}");
+
+    sim.update_distance_constraints(handle, .., |c| c.set_compliance(2e-3))
+        .unwrap();
+    let soft = hanging_stretch(&mut sim);
+    assert!((soft - 2. * 9.81e-3).abs() < 4e-4, "{soft}");
+
+    sim.update_distance_constraints(handle, .., |c| {
+        c.set_enabled(false);
+        Ok(())
+    })
+    .unwrap();
+    let fallen = hanging_stretch(&mut sim);
+    assert!(fallen > 5., "{fallen}");
+}
@@ -0,0 +1,120 @@
+use glam::Vec3;
+use plastica::{
+    cpu::{CpuSimulation, SolverType},
+    Body, DistanceC, Error, Particle, WorldParams,
+};
+
+const SOLVERS: [(&str, SolverType); 2] = [
+    ("Gauss-Seidel", SolverType::GaussSeidel),
+    ("Jacobi", SolverType::Jacobi),
+];
+
+/// A particle hanging from a pinned one by a compliant spring
+fn spring(rest_distance: f32, length: f32, compliance: f32) -> Body {
+    Body {
+        particles: vec![
+            Particle::new(Vec3::ZERO, 0.),
+            Particle::new(Vec3::new(0., 0., -length), 1.),
+        ],
+        distance_constraints: vec![DistanceC::new([0, 1], rest_distance, compliance).unwrap()],
+        ..Default::default()
+    }
+}
+
+fn hanging_stretch(solver: SolverType, iterations: u32) -> f32 {
+    let params = WorldParams {
+        ground: None,
+        damping: 5.,
+        iterations,
+        ..Default::default()
+    };
+    let mut sim = CpuSimulation::new(solver, params).unwrap();
+    sim.add_body(spring(1., 1., 1e-3)).unwrap();
+    for _ in 0..240 {
+        sim.simulate(1. / 60., false).unwrap();
+    }
+    -sim.particles()[1].position.z - 1.
+}
+
+#[test]
+fn invalid_world_params_are_rejected() {
+    let invalid = [
+        WorldParams {
+            iterations: 0,
+            ..Default::default()
+        },
+        WorldParams {
+            substeps: 0,
+            ..Default::default()
+        },
+        WorldParams {
+            gravity: Vec3::new(0., 0., f32::NAN),
+            ..Default::default()
+        },
+        WorldParams {
+            damping: -1.,
+            ..Default::default()
+        },
+        WorldParams {
+            jacobi_weight: 0.,
+            ..Default::default()
+        },
+        WorldParams {
+            air_density: -1.,
+            ..Default::default()
+        },
+    ];
+    let mut sim = CpuSimulation::new(SolverType::Jacobi, WorldParams::default()).unwrap();
+    for params in invalid {
+        assert!(matches!(
+            CpuSimulation::new(SolverType::Jacobi, params),
+            Err(Error::InvalidParameter { .. })
+        ));
+        assert!(matches!(
+            sim.set_world_params(params),
+            Err(Error::InvalidParameter { .. })
+        ));
+    }
+    assert_eq!(
+        sim.world_params().iterations,
+        WorldParams::default().iterations
+    );
+}
+
+#[test]
+fn distance_constraint_converges_to_rest_length() {
+    // Over-relaxing a lone rigid constraint makes it oscillate
+    let params = WorldParams {
+        gravity: Vec3::ZERO,
+        ground: None,
+        damping: 1.,
+        jacobi_weight: 1.,
+        ..Default::default()
+    };
+    for (name, solver) in SOLVERS {
+        let mut sim = CpuSimulation::new(solver, params).unwrap();
+        sim.add_body(spring(1., 1.5, 0.)).unwrap();
+        for _ in 0..60 {
+            sim.simulate(1. / 60., false).unwrap();
+        }
+        let distance = sim.particles()[1].position.length();
+        assert!((distance - 1.).abs() < 1e-3, "{name} {distance}");
+    }
+}
+
+#[test]
+fn compliance_does_not_depend_on_iterations() {
+    // Gauss-Seidel settles on the static stretch m g α
+    let stretch = hanging_stretch(SolverType::GaussSeidel, 1);
+    assert!((stretch - 9.81e-3).abs() < 1e-4, "{stretch}");
+    for (name, solver) in SOLVERS {
+        let one = hanging_stretch(solver, 1);
+        for iterations in [4, 10] {
+            let stretch = hanging_stretch(solver, iterations);
+            assert!(
+                (stretch - one).abs() < 1e-4,
+                "{name} {iterations} iterations: {stretch} instead of {one}"
+            );
+        }
+    }
+}